@@ -1,2 +1,3 @@
 
-pub mod withdraw;
\ No newline at end of file
+pub mod note;
+pub mod withdraw;
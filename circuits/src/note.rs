@@ -0,0 +1,149 @@
+// Copyright (c) Lone G. All rights reserved.
+use ark_ff::PrimeField;
+use plonk_core::{constraint_system::*, error::Error};
+use plonk_hashing::hasher::FieldHasherGadget;
+
+/// Note of an amount of the asset `identifier`, which is spent by knowing
+/// its secret. Its leaf is `H(identifier, amount, H(secret))` and its
+/// nullifier is `H(1 / secret)`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Note<F: PrimeField> {
+    pub secret: F,
+    pub identifier: F,
+    pub amount: u64,
+}
+
+impl<F: PrimeField> Note<F> {
+    pub fn new(secret: F, identifier: F, amount: u64) -> Self {
+        Self { secret, identifier, amount }
+    }
+
+    ///
+    pub fn commitment<H: FieldHasherGadget<F>>(&self, hasher: &H) -> F {
+        hasher.native(&[self.secret])
+    }
+
+    /// Returns the nullifier, or the one of a zero inverse for a zero secret,
+    /// which can't be spent in circuit.
+    pub fn nullifier<H: FieldHasherGadget<F>>(&self, hasher: &H) -> F {
+        hasher.native(&[self.secret.inverse().unwrap_or_default()])
+    }
+
+    ///
+    pub fn leaf<H: FieldHasherGadget<F>>(&self, hasher: &H) -> F {
+        hasher.native(&[self.identifier, F::from(self.amount), self.commitment(hasher)])
+    }
+}
+
+/// Note in circuit, whose amount is range checked to 64 bits.
+#[derive(Clone, Debug)]
+pub struct NoteVar<F: PrimeField> {
+    pub secret: LTVariable<F>,
+    pub identifier: LTVariable<F>,
+    pub amount: UInt64,
+    amount_var: LTVariable<F>,
+}
+
+impl<F: PrimeField> AllocVar<Note<F>, F> for NoteVar<F> {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        note: &Note<F>,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        let secret = LTVariable::new_variable(cs, &note.secret, mode)?;
+        let identifier = LTVariable::new_variable(cs, &note.identifier, mode)?;
+        let amount_var = LTVariable::new_variable(cs, &F::from(note.amount), mode)?;
+        let amount = UInt64::from_variable(cs, &amount_var);
+
+        Ok(Self { secret, identifier, amount, amount_var })
+    }
+}
+
+impl<F: PrimeField> NoteVar<F> {
+    /// Builds a note of an amount computed in circuit.
+    pub fn from_parts(
+        cs: &mut ConstraintSystem<F>,
+        secret: LTVariable<F>,
+        identifier: LTVariable<F>,
+        amount: UInt64,
+    ) -> Self {
+        let amount_var = amount.to_variable(cs).into();
+
+        Self { secret, identifier, amount, amount_var }
+    }
+
+    ///
+    pub fn commitment<H: FieldHasherGadget<F>>(
+        &self,
+        cs: &mut ConstraintSystem<F>,
+        hasher: &H,
+    ) -> LTVariable<F> {
+        cs.exprs(|s| s.gadget(hasher, &[s.var(self.secret)]).lt())
+    }
+
+    /// Returns the nullifier, which constrains the secret to be nonzero.
+    pub fn nullifier<H: FieldHasherGadget<F>>(
+        &self,
+        cs: &mut ConstraintSystem<F>,
+        hasher: &H,
+    ) -> LTVariable<F> {
+        cs.exprs(|s| s.gadget(hasher, &[s.constant(F::one()) / s.var(self.secret)]).lt())
+    }
+
+    ///
+    pub fn leaf<H: FieldHasherGadget<F>>(
+        &self,
+        cs: &mut ConstraintSystem<F>,
+        hasher: &H,
+    ) -> LTVariable<F> {
+        let commitment = self.commitment(cs, hasher);
+        cs.exprs(|s| {
+            s.gadget(
+                hasher,
+                &[s.var(self.identifier), s.var(self.amount_var), s.var(commitment)],
+            )
+            .lt()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use plonk_hashing::hasher::poseidon::*;
+
+    use super::*;
+
+    const WIDTH: usize = 4;
+
+    type Hasher = PoseidonRef<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>;
+
+    #[test]
+    fn test_note() {
+        let hasher = Hasher::default();
+        let note = Note::new(Fr::from(11u64), Fr::from(3u64), 70);
+
+        test_gate_constraints(
+            |cs| {
+                let note_var = NoteVar::new_witness(cs, &note).unwrap();
+                let amount = UInt64::new_witness(cs, &30).unwrap();
+                let amount = note_var.amount.checked_sub(cs, &amount);
+                let change = Note::new(Fr::from(13u64), Fr::from(2u64), 40);
+                let change_var = NoteVar::from_parts(
+                    cs,
+                    LTVariable::constant(change.secret),
+                    LTVariable::constant(change.identifier),
+                    amount,
+                );
+
+                [
+                    (note_var.commitment(cs, &hasher), note.commitment(&hasher)),
+                    (note_var.nullifier(cs, &hasher), note.nullifier(&hasher)),
+                    (note_var.leaf(cs, &hasher), note.leaf(&hasher)),
+                    (change_var.leaf(cs, &hasher), change.leaf(&hasher)),
+                ]
+            },
+            &[],
+        );
+    }
+}
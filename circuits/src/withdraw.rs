@@ -1,137 +1,363 @@
 // Copyright (c) Lone G. All rights reserved.
-use core::{iter::Sum, ops::Sub};
-use ark_ff::Field;
-use bitvec::{prelude::Lsb0, view::BitView};
-use itertools::{Itertools, izip};
-use plonk_core::constraint_system::*;
-use plonk_hashing::{hasher::FieldHasher, merkle::binary::PoECircuit};
+use ark_ff::PrimeField;
+use plonk_core::{constraint_system::*, error::Error, plonkup::Circuit};
+use plonk_hashing::{
+    hasher::FieldHasherGadget,
+    merkle::binary::{PoECircuit, PoEGadget, PoEVar},
+};
 
+use crate::note::{Note, NoteVar};
+
+/// Circuit spending `INPUTS` notes of a Merkle tree of height `HEIGHT` into
+/// a withdraw amount and a new note, whose hashes are computed by `H`.
 pub struct WithdrawCircuit<
     F,
-    A,
+    H,
     const INPUTS: usize,
     const HEIGHT: usize,
     const SIZE: usize,
 > where
-    F: Field,
-    A: Clone + BitView + PartialOrd + Sum<A> + Sub<Output = A> + Into<F>,
+    F: PrimeField,
+{
+    hasher: H,
+    notes: [Option<Note<F>>; INPUTS],
+    poe_circuits: [Option<PoECircuit<F, HEIGHT>>; INPUTS],
+    new_secret: Option<F>,
+    new_identifier: Option<F>,
+    withdraw_amount: Option<u64>,
+}
+
+impl<
+    F,
+    H,
+    const INPUTS: usize,
+    const HEIGHT: usize,
+    const SIZE: usize,
+> Default for WithdrawCircuit<F, H, INPUTS, HEIGHT, SIZE>
+where
+    F: PrimeField,
+    H: Default,
+{
+    /// The circuit without witness, which is enough for setup.
+    fn default() -> Self {
+        Self {
+            hasher: H::default(),
+            notes: [None; INPUTS],
+            poe_circuits: [(); INPUTS].map(|_| None),
+            new_secret: None,
+            new_identifier: None,
+            withdraw_amount: None,
+        }
+    }
+}
+
+impl<
+    F,
+    H,
+    const INPUTS: usize,
+    const HEIGHT: usize,
+    const SIZE: usize,
+> WithdrawCircuit<F, H, INPUTS, HEIGHT, SIZE>
+where
+    F: PrimeField,
 {
-    secrets: [F; INPUTS],
-    identifiers: [F; INPUTS],
-    amount_inputs: [A; INPUTS],
-    poe_circuits: [PoECircuit<F, HEIGHT>; INPUTS],
-    new_secret: F,
-    new_identifier: F,
-    withdraw_amount: A,
+    pub fn new(
+        hasher: H,
+        notes: [Note<F>; INPUTS],
+        poe_circuits: [PoECircuit<F, HEIGHT>; INPUTS],
+        new_secret: F,
+        new_identifier: F,
+        withdraw_amount: u64,
+    ) -> Self {
+        Self {
+            hasher,
+            notes: notes.map(Some),
+            poe_circuits: poe_circuits.map(Some),
+            new_secret: Some(new_secret),
+            new_identifier: Some(new_identifier),
+            withdraw_amount: Some(withdraw_amount),
+        }
+    }
 }
 
 impl<
     F,
-    A,
+    H,
     const INPUTS: usize,
     const HEIGHT: usize,
     const SIZE: usize,
-> WithdrawCircuit<F, A, INPUTS, HEIGHT, SIZE>
+> Circuit<F> for WithdrawCircuit<F, H, INPUTS, HEIGHT, SIZE>
 where
-    F: Field,
-    A: Clone + BitView + PartialOrd + Sum<A> + Sub<Output = A> + Into<F>,
+    F: PrimeField,
+    H: FieldHasherGadget<F>,
 {
-    pub fn synthesize<H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>>(
-        self,
-        cs: &mut ConstraintSystem<F>,
-        hasher: &mut H,
-    ) {
-        let amount_in = self.amount_inputs.iter().cloned().sum::<A>();
-        assert!(amount_in >= self.withdraw_amount, "invalid withdraw amount");
-        let amount_out = amount_in - self.withdraw_amount;
+    /// Synthesizes the circuit. Witness values are only needed when proving,
+    /// so the [`Default`] circuit is enough for setup.
+    fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+        let hasher = &self.hasher;
 
         // step 1: Existence proof of inputs
 
         // assign variables
-        let amount_in_vars = self.amount_inputs
-            .into_iter()
-            .map(|amount| cs.assign_variable(amount.into()))
-            .collect_vec();
-        let identifier_vars = self.identifiers
-            .into_iter()
-            .map(|identifier| cs.assign_variable(identifier))
-            .collect_vec();
-
-        let one_var = LTVariable::constant(F::one());
-        for (
-            &amount_var,
-            identifier_var,
-            secret,
-            poe_circuit,
-        ) in izip!(&amount_in_vars, identifier_vars, self.secrets, self.poe_circuits) {
-            let secret_var = cs.assign_variable(secret).into();
-            let commitment_var = hasher.hash(cs, &[secret_var]);
-            
-            let secret_var_inv = cs.div_gate(&one_var, &secret_var);
-            let nullifier_var = hasher.hash(cs, &[secret_var_inv.into()]);
+        let note_vars = self.notes
+            .iter()
+            .map(|note| NoteVar::new_optional(cs, note.as_ref(), AllocMode::Witness))
+            .collect::<Result<Vec<_>, _>>()?;
+        let poe_gadget = PoEGadget::<_, HEIGHT>::new(hasher);
+
+        for (note_var, poe_circuit) in note_vars.iter().zip(self.poe_circuits) {
             // make nullifier public
-            cs.set_variable_public(&nullifier_var);
+            let nullifier = note_var.nullifier(cs, hasher);
+            cs.set_variable_public(&nullifier);
 
-            let leaf_var = hasher.hash(
-                cs,
-                &[identifier_var.into(), amount_var.into(), commitment_var],
-            );
+            // lookup identifier from subset
+            cs.lookup_constrain(&note_var.identifier);
 
-            let (root_var, _) = poe_circuit.synthesize(cs, hasher, &leaf_var);
+            let leaf_var = note_var.leaf(cs, hasher);
+            let path_var = PoEVar::new_optional(cs, poe_circuit.as_ref(), AllocMode::Witness)?;
+            let root_var = poe_gadget.synthesize(cs, &(leaf_var, path_var));
             // make root public
             cs.set_variable_public(&root_var);
-
-            // lookup identifier from subset
-            cs.lookup_constrain(&identifier_var.into());
         }
 
         // step 2: Balance proof
 
-        // bit range constrain for amount out
-        let amount_out_bits = amount_out
-            .view_bits::<Lsb0>()
-            .iter()
-            .map(|bit| {
-                let var = cs.assign_variable((*bit).into());
-                cs.boolean_gate(var)
-            })
-            .collect_vec();
-        let amount_out_var = cs.bits_le_constrain(&amount_out_bits);
-
-        let left_var = amount_in_vars[0];
-        let mut right_var = Variable::Zero;
-        for &amount_var in &amount_in_vars[1..] {
-            right_var = cs.add_gate(&right_var.into(), &amount_var.into());
-        }
-        let sels = Selectors::new()
-            .with_left(-F::one())
-            .with_right(-F::one())
-            .with_out(F::one());
-        cs.arith_constrain(
-            left_var,
-            right_var,
-            amount_out_var,
-            sels,
-            Some(amount_out.into()),
-        );
+        // the amounts are range constrained, their sum must not overflow
+        let amount_in = note_vars
+            .into_iter()
+            .map(|note_var| note_var.amount)
+            .reduce(|acc, amount| acc.checked_add(cs, &amount))
+            .ok_or(Error::InvalidCircuitShape)?;
+
+        // make withdraw amount public
+        let withdraw_amount = UInt64::new_optional(cs, self.withdraw_amount.as_ref(), AllocMode::Input)?;
+
+        // underflow means the inputs can't afford the withdraw amount
+        let amount_out = amount_in.checked_sub(cs, &withdraw_amount);
 
         // step 3: hash new secret and commitment
 
-        let new_secret_var = cs.assign_variable(self.new_secret).into();
-        let new_identifier_var = cs.assign_variable(self.new_identifier).into();
-        let new_commitment_var = hasher.hash(cs, &[new_secret_var]);
-        let new_leaf_var = hasher.hash(
-            cs,
-            &[new_identifier_var, amount_out_var.into(), new_commitment_var],
-        );
+        let new_secret_var = LTVariable::new_optional(cs, self.new_secret.as_ref(), AllocMode::Witness)?;
+        let new_identifier_var = LTVariable::new_optional(cs, self.new_identifier.as_ref(), AllocMode::Witness)?;
+        let new_note_var = NoteVar::from_parts(cs, new_secret_var, new_identifier_var, amount_out);
         // set new leaf public
-        cs.set_variable_public(&new_leaf_var);
+        let new_leaf = new_note_var.leaf(cs, hasher);
+        cs.set_variable_public(&new_leaf);
+
+        Ok(())
     }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Rc;
+    use ark_bn254::{Bn254, Fr};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::test_rng;
+    use plonk_core::{
+        commitment::KZG10,
+        lookup::LookupTable,
+        plonkup::ZKTPlonkup,
+        transcript::MerlinTranscript,
+    };
+    use plonk_hashing::{
+        hasher::poseidon::*,
+        merkle::{store::MemoryNodeStore, tree::MerkleTree},
+    };
+
+    use super::*;
+
+    const WIDTH: usize = 4;
+    const SIZE: usize = 2;
+
+    type Hasher = PoseidonRef<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>;
+    type Withdraw<const INPUTS: usize, const HEIGHT: usize> = WithdrawCircuit<Fr, Hasher, INPUTS, HEIGHT, SIZE>;
 
+    fn identifiers() -> LookupTable<Fr> {
+        [1u64, 2, 3].map(Fr::from).into()
+    }
+
+    /// Notes of identifiers 1 and 3 in turn in a tree, spent to a note of
+    /// identifier 2.
+    struct Withdrawal<const INPUTS: usize, const HEIGHT: usize> {
+        notes: [Note<Fr>; INPUTS],
+        poe_circuits: [PoECircuit<Fr, HEIGHT>; INPUTS],
+        new_secret: Fr,
+        new_identifier: Fr,
+        withdraw_amount: u64,
+        nullifiers: [Fr; INPUTS],
+        root: Fr,
+        new_leaf: Fr,
+    }
+
+    impl<const INPUTS: usize, const HEIGHT: usize> Withdrawal<INPUTS, HEIGHT> {
+        fn new(amount_inputs: [u64; INPUTS], withdraw_amount: u64) -> Self {
+            let hasher = Hasher::default();
+            let notes: [_; INPUTS] = std::array::from_fn(|i| {
+                let identifier = if i % 2 == 0 { 1u64 } else { 3 };
+                Note::new(Fr::from(11 + 2 * i as u64), Fr::from(identifier), amount_inputs[i])
+            });
+            let indices: [u64; INPUTS] = std::array::from_fn(|i| 5 * i as u64 % (1 << HEIGHT));
+
+            let mut tree = MerkleTree::new(
+                HEIGHT,
+                PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::default(),
+                MemoryNodeStore::new(),
+            );
+            for (note, index) in notes.iter().zip(indices) {
+                tree.update(index, note.leaf(&hasher)).unwrap();
+            }
+            let root = tree.root().unwrap();
+            let poe_circuits = indices.map(|index| tree.poe_circuit::<HEIGHT>(index).unwrap());
+            let nullifiers = notes.map(|note| note.nullifier(&hasher));
+
+            let new_secret = Fr::from(17u64);
+            let new_identifier = Fr::from(2u64);
+            // meaningless if the inputs overflow or can't afford the withdraw
+            // amount
+            let amount_out = amount_inputs
+                .iter()
+                .fold(0u64, |acc, amount| acc.wrapping_add(*amount))
+                .wrapping_sub(withdraw_amount);
+            let new_leaf = Note::new(new_secret, new_identifier, amount_out).leaf(&hasher);
+
+            Self {
+                notes,
+                poe_circuits,
+                new_secret,
+                new_identifier,
+                withdraw_amount,
+                nullifiers,
+                root,
+                new_leaf,
+            }
+        }
+
+        fn circuit(&self) -> Withdraw<INPUTS, HEIGHT> {
+            WithdrawCircuit::new(
+                Hasher::default(),
+                self.notes,
+                self.poe_circuits.clone(),
+                self.new_secret,
+                self.new_identifier,
+                self.withdraw_amount,
+            )
+        }
+
+        /// Nullifier and root of each input, then the withdraw amount and the
+        /// new leaf.
+        fn pub_inputs(&self) -> Vec<Fr> {
+            self.nullifiers
+                .iter()
+                .flat_map(|&nullifier| [nullifier, self.root])
+                .chain([Fr::from(self.withdraw_amount), self.new_leaf])
+                .collect()
+        }
+
+        /// Public inputs as assigned by the circuit.
+        fn synthesized_pub_inputs(&self) -> Vec<Fr> {
+            let mut cs = ConstraintSystem::new_with_witness(identifiers());
+            self.circuit().synthesize(&mut cs).unwrap();
+            let proving = cs.composer.proving.unwrap();
+
+            proving.pi.get_vals().copied().collect()
+        }
+
+        /// Checks the circuit against the shape of the [`Default`] circuit.
+        fn check(&self, pub_inputs: &[Fr]) {
+            check_circuit(Withdraw::default(), self.circuit(), pub_inputs, identifiers())
+        }
+    }
+
+    #[test]
+    fn test_withdraw() {
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 100);
+        withdrawal.check(&withdrawal.pub_inputs());
+    }
+
+    #[test]
+    fn test_withdraw_public_input_layout() {
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 100);
+        let pub_inputs = withdrawal.pub_inputs();
+        assert_eq!(pub_inputs.len(), 2 * 2 + 2);
+        assert_eq!(withdrawal.synthesized_pub_inputs(), pub_inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "public input value at 0 is not correct")]
+    fn test_withdraw_swapped_public_inputs() {
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 100);
+        let mut pub_inputs = withdrawal.pub_inputs();
+        pub_inputs.swap(0, 1);
+        withdrawal.check(&pub_inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not satisfied")]
+    fn test_overdraw() {
+        // even the public inputs of the circuit don't satisfy the gates
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 121);
+        withdrawal.check(&withdrawal.synthesized_pub_inputs());
+    }
+
+    #[test]
+    fn test_withdraw_whole_balance() {
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 120);
+        withdrawal.check(&withdrawal.pub_inputs());
+
+        let withdrawal = Withdrawal::<2, 4>::new([u64::MAX - 50, 50], u64::MAX);
+        withdrawal.check(&withdrawal.pub_inputs());
+    }
 
-}
\ No newline at end of file
+    #[test]
+    #[should_panic(expected = "is not satisfied")]
+    fn test_input_sum_overflow() {
+        // the sum wraps to 1 in 64 bits, which would afford the withdrawal
+        let withdrawal = Withdrawal::<2, 4>::new([u64::MAX, 2], 1);
+        withdrawal.check(&withdrawal.synthesized_pub_inputs());
+    }
+
+    #[test]
+    fn test_withdraw_mock_prover() {
+        let withdrawal = Withdrawal::<2, 4>::new([70, 50], 100);
+        MockProver::run(Withdraw::<2, 4>::default(), withdrawal.circuit(), identifiers(), &withdrawal.pub_inputs())
+            .unwrap()
+            .verify()
+            .unwrap();
+
+        // the identifiers must be in the table
+        let failures = MockProver::run(
+            Withdraw::<2, 4>::default(),
+            withdrawal.circuit(),
+            [Fr::from(1u64), Fr::from(2u64)],
+            &withdrawal.pub_inputs(),
+        )
+        .unwrap()
+        .verify()
+        .unwrap_err();
+        assert!(!failures.is_empty());
+    }
+
+    #[test]
+    fn test_withdraw_proof() {
+        type PC = KZG10<Bn254>;
+        type Plonkup = ZKTPlonkup<Fr, GeneralEvaluationDomain<Fr>, PC, MerlinTranscript, Withdraw<1, 1>>;
+
+        let withdrawal = Withdrawal::<1, 1>::new([70], 50);
+        let rng = &mut test_rng();
+        let pp = PC::setup(1 << 15, None, rng).unwrap();
+        let (ck, cvk, pk, epk, vk) = Plonkup::compile(true, &pp, identifiers(), Withdraw::default()).unwrap();
+        let proof = Plonkup::prove(&ck, &pk, epk.map(Rc::new), &vk, identifiers(), withdrawal.circuit(), rng)
+            .unwrap();
+        Plonkup::verify(&cvk, &vk, &proof, &withdrawal.pub_inputs()).unwrap();
+    }
+
+    #[test]
+    fn test_withdraw_without_inputs() {
+        let mut cs = ConstraintSystem::new(true, identifiers());
+        let result = Withdraw::<0, 4>::default().synthesize(&mut cs);
+        assert!(matches!(result, Err(Error::InvalidCircuitShape)));
+    }
+}
@@ -8,152 +8,114 @@
 
 use ark_ff::Field;
 
-use super::{Variable, ConstraintSystem, Composer, Selectors, LTVariable};
+use super::{Variable, ConstraintSystem, Selectors, LTVariable};
 
 impl<F: Field> ConstraintSystem<F> {
     /// x + y - z = 0
     pub fn add_gate(&mut self, x: &LTVariable<F>, y: &LTVariable<F>) -> Variable {
-        let z: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_left(F::one())
-                    .with_right(F::one())
-                    .with_out(-F::one())
-                    .by_left_lt(x)
-                    .by_right_lt(y);
-
-                composer.gate_constrain(x.var, y.var, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = composer.var_map.value_of_lt_var(y);
-                let z_value = x_value + y_value;
-
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(x.var, y.var, z, None);
-            }
+        if let Some(z) = self.fold(x, y, |x, y| x + y) {
+            return z;
         }
 
+        let z_value = self.value_of_lt_var(x) + self.value_of_lt_var(y);
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_right(F::one())
+            .with_out(-F::one())
+            .by_left_lt(x)
+            .by_right_lt(y);
+
+        self.arith_constrain(x.var, y.var, z, sels, None);
+
         z
     }
 
     /// x - y - z = 0
     pub fn sub_gate(&mut self, x: &LTVariable<F>, y: &LTVariable<F>) -> Variable {
-        let z: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_left(F::one())
-                    .with_right(-F::one())
-                    .with_out(-F::one())
-                    .by_left_lt(x)
-                    .by_right_lt(y);
-
-                composer.gate_constrain(x.var, y.var, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = composer.var_map.value_of_lt_var(y);
-                let z_value = x_value - y_value;
-
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(x.var, y.var, z, None);
-            }
+        if let Some(z) = self.fold(x, y, |x, y| x - y) {
+            return z;
         }
 
+        let z_value = self.value_of_lt_var(x) - self.value_of_lt_var(y);
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_right(-F::one())
+            .with_out(-F::one())
+            .by_left_lt(x)
+            .by_right_lt(y);
+
+        self.arith_constrain(x.var, y.var, z, sels, None);
+
         z
     }
 
     /// x * y - z = 0
     pub fn mul_gate(&mut self, x: &LTVariable<F>, y: &LTVariable<F>) -> Variable {
-        let z: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one())
-                    .by_left_lt(x)
-                    .by_right_lt(y);
-
-                composer.gate_constrain(x.var, y.var, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = composer.var_map.value_of_lt_var(y);
-                let z_value = x_value * y_value;
-
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(x.var, y.var, z, None);
-            }
+        if let Some(z) = self.fold(x, y, |x, y| x * y) {
+            return z;
         }
 
+        let z_value = self.value_of_lt_var(x) * self.value_of_lt_var(y);
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one())
+            .by_left_lt(x)
+            .by_right_lt(y);
+
+        self.arith_constrain(x.var, y.var, z, sels, None);
+
         z
     }
 
     /// y * z - x = 0
+    ///
+    /// Division by zero has no valid witness, so the gate fails unless `x` is
+    /// zero as well.
     pub fn div_gate(&mut self, x: &LTVariable<F>, y: &LTVariable<F>) -> Variable {
-        let z: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one())
-                    .by_left_lt(y)
-                    .by_out_lt(x);
-
-                composer.gate_constrain(y.var, z, x.var, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = composer.var_map.value_of_lt_var(y);
-                let z_value = x_value / y_value;
-
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(y.var, z, x.var, None);
-            }
+        // division by constant zero is left to fail in the gate
+        let y_inverse = self.constant_value(y).and_then(|y| y.inverse());
+        if let (Some(x_value), Some(y_inverse)) = (self.constant_value(x), y_inverse) {
+            return self.lazy_constant_var(x_value * y_inverse);
         }
 
+        let y_inverse = self.value_of_lt_var(y).inverse().unwrap_or_default();
+        let z_value = self.value_of_lt_var(x) * y_inverse;
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one())
+            .by_left_lt(y)
+            .by_out_lt(x);
+
+        self.arith_constrain(y.var, z, x.var, sels, None);
+
         z
     }
 
     /// x^2 - y = 0
     pub fn square_gate(&mut self, x: &LTVariable<F>) -> Variable {
-        let y: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                y = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one())
-                    .by_left_lt(x)
-                    .by_right_lt(x);
-
-                composer.gate_constrain(x.var, x.var, y, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = x_value.square();
-
-                y = composer.var_map.assign_variable(y_value);
-                
-                composer.input_wires(x.var, x.var, y, None);
-            }
+        if let Some(y) = self.fold(x, x, |x, _| x.square()) {
+            return y;
         }
 
+        let y_value = self.value_of_lt_var(x).square();
+        let y = self.assign_variable(y_value);
+
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one())
+            .by_left_lt(x)
+            .by_right_lt(x);
+
+        self.arith_constrain(x.var, x.var, y, sels, None);
+
         y
     }
 
@@ -166,32 +128,23 @@ impl<F: Field> ConstraintSystem<F> {
         b: F,
         c: F,
     ) -> Variable {
-        let z: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_left(a)
-                    .with_right(b)
-                    .with_out(-F::one())
-                    .with_constant(c)
-                    .by_left_lt(x)
-                    .by_right_lt(y);
-
-                composer.gate_constrain(x.var, y.var, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = composer.var_map.value_of_lt_var(y);
-                let z_value = x_value * a + y_value * b + c;
-
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(x.var, y.var, z, None);
-            }
+        if let Some(z) = self.fold(x, y, |x, y| x * a + y * b + c) {
+            return z;
         }
 
+        let z_value = self.value_of_lt_var(x) * a + self.value_of_lt_var(y) * b + c;
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_left(a)
+            .with_right(b)
+            .with_out(-F::one())
+            .with_constant(c)
+            .by_left_lt(x)
+            .by_right_lt(y);
+
+        self.arith_constrain(x.var, y.var, z, sels, None);
+
         z
     }
 }
@@ -212,11 +165,14 @@ mod test {
                 let rng = &mut test_rng();
                 let x_value = F::rand(rng);
                 let y_value = F::rand(rng);
-                let z_value = x_value + y_value;
+                let (x_coeff, x_offset) = (F::rand(rng), F::rand(rng));
+                let (y_coeff, y_offset) = (F::rand(rng), F::rand(rng));
+                let z_value = (x_value * x_coeff + x_offset)
+                    + (y_value * y_coeff + y_offset);
                 let x = cs.assign_variable(x_value);
                 let y = cs.assign_variable(y_value);
-                let lt_x = x.linear_transform(F::rand(rng), F::rand(rng));
-                let lt_y = y.linear_transform(F::rand(rng), F::rand(rng));
+                let lt_x = x.linear_transform(x_coeff, x_offset);
+                let lt_y = y.linear_transform(y_coeff, y_offset);
                 let lt_z = cs.add_gate(&lt_x, &lt_y).into();
 
                 vec![(lt_z, z_value)]
@@ -231,11 +187,14 @@ mod test {
                 let rng = &mut test_rng();
                 let x_value = F::rand(rng);
                 let y_value = F::rand(rng);
-                let z_value = x_value - y_value;
+                let (x_coeff, x_offset) = (F::rand(rng), F::rand(rng));
+                let (y_coeff, y_offset) = (F::rand(rng), F::rand(rng));
+                let z_value = (x_value * x_coeff + x_offset)
+                    - (y_value * y_coeff + y_offset);
                 let x = cs.assign_variable(x_value);
                 let y = cs.assign_variable(y_value);
-                let lt_x = x.linear_transform(F::rand(rng), F::rand(rng));
-                let lt_y = y.linear_transform(F::rand(rng), F::rand(rng));
+                let lt_x = x.linear_transform(x_coeff, x_offset);
+                let lt_y = y.linear_transform(y_coeff, y_offset);
                 let lt_z = cs.sub_gate(&lt_x, &lt_y).into();
 
                 vec![(lt_z, z_value)]
@@ -250,11 +209,14 @@ mod test {
                 let rng = &mut test_rng();
                 let x_value = F::rand(rng);
                 let y_value = F::rand(rng);
-                let z_value = x_value * y_value;
+                let (x_coeff, x_offset) = (F::rand(rng), F::rand(rng));
+                let (y_coeff, y_offset) = (F::rand(rng), F::rand(rng));
+                let z_value = (x_value * x_coeff + x_offset)
+                    * (y_value * y_coeff + y_offset);
                 let x = cs.assign_variable(x_value);
                 let y = cs.assign_variable(y_value);
-                let lt_x = x.linear_transform(F::rand(rng), F::rand(rng));
-                let lt_y = y.linear_transform(F::rand(rng), F::rand(rng));
+                let lt_x = x.linear_transform(x_coeff, x_offset);
+                let lt_y = y.linear_transform(y_coeff, y_offset);
                 let lt_z = cs.mul_gate(&lt_x, &lt_y).into();
 
                 vec![(lt_z, z_value)]
@@ -269,11 +231,14 @@ mod test {
                 let rng = &mut test_rng();
                 let x_value = F::rand(rng);
                 let y_value = F::rand(rng);
-                let z_value = x_value / y_value;
+                let (x_coeff, x_offset) = (F::rand(rng), F::rand(rng));
+                let (y_coeff, y_offset) = (F::rand(rng), F::rand(rng));
+                let z_value = (x_value * x_coeff + x_offset)
+                    / (y_value * y_coeff + y_offset);
                 let x = cs.assign_variable(x_value);
                 let y = cs.assign_variable(y_value);
-                let lt_x = x.linear_transform(F::rand(rng), F::rand(rng));
-                let lt_y = y.linear_transform(F::rand(rng), F::rand(rng));
+                let lt_x = x.linear_transform(x_coeff, x_offset);
+                let lt_y = y.linear_transform(y_coeff, y_offset);
                 let lt_z = cs.div_gate(&lt_x, &lt_y).into();
 
                 vec![(lt_z, z_value)]
@@ -8,12 +8,24 @@
 
 use ark_ff::Field;
 
-use super::{Selectors, Composer, Variable, ConstraintSystem};
+use super::{Selectors, Variable, LTVariable, ConstraintSystem};
 
 ///
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Boolean(pub(crate) Variable);
 
+impl From<Boolean> for Variable {
+    fn from(bit: Boolean) -> Self {
+        bit.0
+    }
+}
+
+impl<F: Field> From<Boolean> for LTVariable<F> {
+    fn from(bit: Boolean) -> Self {
+        bit.0.into()
+    }
+}
+
 impl<F: Field> ConstraintSystem<F> {
     /// Adds a boolean constraint (also known as binary constraint) where
     /// the gate eq. will enforce that the [`Variable`] received is either `0`
@@ -33,157 +45,86 @@ impl<F: Field> ConstraintSystem<F> {
         Boolean(x)
     }
 
+    /// Returns a [`Boolean`] fixed to `value`. `false` is the zero variable
+    /// and needs no gate, `true` is the shared constant one.
+    pub fn constant_boolean(&mut self, value: bool) -> Boolean {
+        Boolean(self.constant_var(F::from(value)))
+    }
+
+    /// Assigns `z = op(x, y)` and constrains it by the gate of `sels` over
+    /// `(x, y, z)`.
+    fn boolean_op_gate<O>(&mut self, x: Boolean, y: Boolean, sels: Selectors<F>, op: O) -> Boolean
+    where
+        O: Fn(F, F) -> F,
+    {
+        if let Some(z) = self.fold(&x.0.into(), &y.0.into(), &op) {
+            return Boolean(z);
+        }
+
+        let z_value = op(self.value_of_var(x.0), self.value_of_var(y.0));
+        let z = self.assign_variable(z_value);
+
+        self.arith_constrain(x.0, y.0, z, sels, None);
+
+        Boolean(z)
+    }
+
     /// Performs an AND operation over the two operands.
     /// x * y - z = 0
     pub fn and_gate(&mut self, x: Boolean, y: Boolean) -> Boolean {
-        let z: Variable;
-        
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one());
-
-                z = composer.perm.new_variable();
-
-                composer.gate_constrain(x.0, y.0, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_var(x.0);
-                let y_value = composer.var_map.value_of_var(y.0);
-                let z_value = x_value * y_value;
-                
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(x.0, y.0, z, None);
-            }
-        }
-        
-        Boolean(z)
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one());
+
+        self.boolean_op_gate(x, y, sels, |x, y| x * y)
     }
 
     /// Performs an OR operation over the two operands
     /// (1 - x) * (1 - y) - (1 - z) = 0 => xy - x - y + z = 0
     pub fn or_gate(&mut self, x: Boolean, y: Boolean) -> Boolean {
-        let z: Variable;
-        
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_left(-F::one())
-                    .with_right(-F::one())
-                    .with_out(F::one());
-
-                z = composer.perm.new_variable();
-
-                composer.gate_constrain(x.0, y.0, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_var(x.0);
-                let y_value = composer.var_map.value_of_var(y.0);
-                let z_value = (F::one() - x_value) * (F::one() - y_value);
-                
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(x.0, y.0, z, None);
-            }
-        }
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_left(-F::one())
+            .with_right(-F::one())
+            .with_out(F::one());
 
-        Boolean(z)
+        self.boolean_op_gate(x, y, sels, |x, y| x + y - x * y)
     }
 
     /// Calculates `x XOR y`.
     /// 2xy - x - y + z = 0 
     pub fn xor_gate(&mut self, x: Boolean, y: Boolean) -> Boolean {
-        let z: Variable;
-        
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_mul(F::from(2u64))
-                    .with_left(-F::one())
-                    .with_right(-F::one())
-                    .with_out(F::one());
-
-                z = composer.perm.new_variable();
-
-                composer.gate_constrain(x.0, y.0, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_var(x.0);
-                let y_value = composer.var_map.value_of_var(y.0);
-                let z_value = (x_value + y_value) - F::from(2u64) * x_value * y_value;
-                
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(x.0, y.0, z, None);
-            }
-        }
+        let sels = Selectors::new()
+            .with_mul(F::from(2u64))
+            .with_left(-F::one())
+            .with_right(-F::one())
+            .with_out(F::one());
 
-        Boolean(z)
+        self.boolean_op_gate(x, y, sels, |x, y| (x + y) - F::from(2u64) * x * y)
     }
 
     /// Calculates `(NOT x) AND y`.
     /// (1 - x) * y - z = 0 => -xy + y - z = 0
     pub fn not_and_gate(&mut self, x: Boolean, y: Boolean) -> Boolean {
-        let z: Variable;
-
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_mul(-F::one())
-                    .with_right(F::one())
-                    .with_out(-F::one());
-
-                z = composer.perm.new_variable();
-
-                composer.gate_constrain(x.0, y.0, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_var(x.0);
-                let y_value = composer.var_map.value_of_var(y.0);
-                let z_value = y_value * (F::one() - x_value);
-                
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(x.0, y.0, z, None);
-            }
-        }
+        let sels = Selectors::new()
+            .with_mul(-F::one())
+            .with_right(F::one())
+            .with_out(-F::one());
 
-        Boolean(z)
+        self.boolean_op_gate(x, y, sels, |x, y| y * (F::one() - x))
     }
 
     /// Calculates `(NOT a) AND (NOT b)`.
     /// (1 - a) * (1 - b) - c = 0 => ab - a - b + 1 - c = 0
     pub fn nor_gate(&mut self, x: Boolean, y: Boolean) -> Boolean {
-        let z: Variable;
-
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_left(-F::one())
-                    .with_right(-F::one())
-                    .with_out(-F::one())
-                    .with_constant(F::one());
-
-                z = composer.perm.new_variable();
-
-                composer.gate_constrain(x.0, y.0, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_var(x.0);
-                let y_value = composer.var_map.value_of_var(y.0);
-                let z_value = (F::one() - x_value) * (F::one() - y_value);
-                
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(x.0, y.0, z, None);
-            }
-        }
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_left(-F::one())
+            .with_right(-F::one())
+            .with_out(-F::one())
+            .with_constant(F::one());
 
-        Boolean(z)
+        self.boolean_op_gate(x, y, sels, |x, y| (F::one() - x) * (F::one() - y))
     }
 }
 
@@ -0,0 +1,376 @@
+//! Byte Gates
+//!
+//! Bitwise operations over bytes are checked by lookups instead of being
+//! decomposed into bits. The lookup table is a single column, so an entry
+//! packs the operands and the result as `x + 2^8 * y + 2^16 * z` plus a tag
+//! per operation, and the lookup table of the circuit must contain
+//! [`byte_table`].
+
+use ark_ff::{BigInteger, Field, PrimeField};
+use itertools::Itertools;
+
+use crate::lookup::LookupTable;
+use super::{ConstraintSystem, LTVariable, Selectors, Variable};
+
+const XOR_TAG: u64 = 0;
+const AND_TAG: u64 = 1 << 24;
+const RANGE_TAG: u64 = 1 << 25;
+
+/// Returns the lookup table of the byte gates, which holds the results of
+/// XOR and AND over all pairs of bytes, and all bytes for range checks.
+pub fn byte_table<F: Field>() -> LookupTable<F> {
+    let pairs = (0..256u64).cartesian_product(0..256u64);
+    let xor_entries = pairs
+        .clone()
+        .map(|(x, y)| XOR_TAG + x + (y << 8) + ((x ^ y) << 16));
+    let and_entries = pairs.map(|(x, y)| AND_TAG + x + (y << 8) + ((x & y) << 16));
+    let range_entries = (0..256u64).map(|x| RANGE_TAG + x);
+
+    xor_entries
+        .chain(and_entries)
+        .chain(range_entries)
+        .map(F::from)
+        .into()
+}
+
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Byte(pub(crate) Variable);
+
+impl From<Byte> for Variable {
+    fn from(byte: Byte) -> Self {
+        byte.0
+    }
+}
+
+impl<F: Field> From<Byte> for LTVariable<F> {
+    fn from(byte: Byte) -> Self {
+        byte.0.into()
+    }
+}
+
+impl<F: PrimeField> ConstraintSystem<F> {
+    /// Constrains `x` to be a byte.
+    pub fn byte_gate(&mut self, x: Variable) -> Byte {
+        let tag = F::from(RANGE_TAG);
+        self.lookup_constrain(&x.linear_transform(F::one(), tag));
+
+        Byte(x)
+    }
+
+    /// Returns a [`Byte`] fixed to `value`. Zero is the zero variable and
+    /// needs no gate, others are shared constants.
+    pub fn constant_byte(&mut self, value: u8) -> Byte {
+        Byte(self.constant_var(F::from(value)))
+    }
+
+    /// Returns the low byte of a value, or zero without a witness. A value
+    /// that isn't a byte is left to fail in the lookup.
+    fn byte_value(&self, x: &LTVariable<F>) -> u64 {
+        self.value_of_lt_var(x).into_repr().as_ref()[0] & 0xff
+    }
+
+    /// Assigns `z = op(x, y)` and looks up `x + 2^8 * y + 2^16 * z + tag`.
+    /// `x` and `y` must be bytes, `z` is range checked.
+    fn byte_lookup_gate<O>(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        tag: u64,
+        op: O,
+    ) -> Byte
+    where
+        O: Fn(u64, u64) -> u64,
+    {
+        let z = {
+            let value = op(self.byte_value(x), self.byte_value(y));
+            self.assign_variable(F::from(value))
+        };
+
+        // t = x + 2^8 * y, free if y is constant
+        let shift = F::from(1u64 << 8);
+        let t: LTVariable<F> = if y.var == Variable::Zero {
+            x.linear_transform(F::one(), y.offset * shift)
+        } else {
+            self.add_gate(x, &y.linear_transform(shift, F::zero())).into()
+        };
+
+        // key = t + 2^16 * z + tag
+        let key_coeff = F::from(1u64 << 16);
+        let key_value = self.value_of_lt_var(&t) + key_coeff * self.value_of_var(z) + F::from(tag);
+        self.check_lookup(&key_value);
+        let key = self.assign_variable(key_value);
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_right(key_coeff)
+            .with_out(-F::one())
+            .with_constant(F::from(tag))
+            .with_lookup()
+            .by_left_lt(&t);
+
+        self.arith_constrain(t.var, z, key, sels, None);
+
+        self.byte_gate(z)
+    }
+
+    /// Calculates `x XOR y`.
+    pub fn xor_byte_gate(&mut self, x: Byte, y: Byte) -> Byte {
+        self.byte_lookup_gate(&x.into(), &y.into(), XOR_TAG, |x, y| x ^ y)
+    }
+
+    /// Calculates `x AND y`.
+    pub fn and_byte_gate(&mut self, x: Byte, y: Byte) -> Byte {
+        self.byte_lookup_gate(&x.into(), &y.into(), AND_TAG, |x, y| x & y)
+    }
+
+    /// Calculates `(NOT x) AND y`, where `NOT x = 255 - x` costs no gate.
+    pub fn not_and_byte_gate(&mut self, x: Byte, y: Byte) -> Byte {
+        let not_x = LTVariable::from(x).linear_transform(-F::one(), F::from(u8::MAX));
+        self.byte_lookup_gate(&not_x, &y.into(), AND_TAG, |x, y| x & y)
+    }
+
+    /// Calculates `x XOR value`. XOR with zero is free.
+    pub fn xor_constant_byte_gate(&mut self, x: Byte, value: u8) -> Byte {
+        if value == 0 {
+            return x;
+        }
+
+        let y = LTVariable::constant(F::from(value));
+        self.byte_lookup_gate(&x.into(), &y, XOR_TAG, |x, y| x ^ y)
+    }
+
+    /// Calculates `x AND value`.
+    pub fn and_constant_byte_gate(&mut self, x: Byte, value: u8) -> Byte {
+        let y = LTVariable::constant(F::from(value));
+        self.byte_lookup_gate(&x.into(), &y, AND_TAG, |x, y| x & y)
+    }
+
+    /// Decomposes `x` into `num_bytes` little-endian bytes checked by
+    /// lookups, which constrains `x` to be less than `2^(8 * num_bytes)`.
+    /// This is a range check of one lookup and one gate per byte.
+    pub fn to_bytes_le(&mut self, x: &LTVariable<F>, num_bytes: usize) -> Vec<Byte> {
+        assert!(
+            num_bytes > 0 && 8 * num_bytes < F::size_in_bits(),
+            "number of bytes is out of range",
+        );
+
+        let mut byte_values = self.value_of_lt_var(x).into_repr().to_bytes_le();
+        // Higher bytes are dropped, so the gates fail if x is too large.
+        byte_values.resize(num_bytes, 0);
+        let bytes = byte_values
+            .into_iter()
+            .map(|value| {
+                let var = self.assign_variable(F::from(value));
+                self.byte_gate(var)
+            })
+            .collect_vec();
+
+        // acc_i = acc_{i-1} + 2^(8i) * b_i
+        let mut acc = LTVariable::from(bytes[0]);
+        let mut power = F::one();
+        for &byte in &bytes[1..] {
+            power *= F::from(1u64 << 8);
+            let term = LTVariable::from(byte).linear_transform(power, F::zero());
+            acc = self.add_gate(&acc, &term).into();
+        }
+        self.equal_constrain(&acc, x);
+
+        bytes
+    }
+
+    /// Rotates the integer of little-endian `bytes` left by `by` bits.
+    ///
+    /// Rotations by whole bytes are free. Otherwise every byte is split into
+    /// its low `8 - s` and high `s` bits by an AND lookup, and every output
+    /// byte joins the low bits of a byte with the high bits of the previous
+    /// one, which is a byte by construction.
+    pub fn rotl_bytes_le(&mut self, bytes: &[Byte], by: usize) -> Vec<Byte> {
+        let len = bytes.len();
+        assert!(len > 0, "no bytes to rotate");
+        let by = by % (8 * len);
+        let (q, s) = (by / 8, by % 8);
+
+        let mut bytes = bytes.to_vec();
+        bytes.rotate_right(q);
+        if s == 0 {
+            return bytes;
+        }
+
+        // b = lo + 2^(8 - s) * hi
+        let lo_mask = (1u8 << (8 - s)) - 1;
+        let inv_shift = F::from(1u64 << (8 - s)).inverse().unwrap();
+        let splits = bytes
+            .iter()
+            .map(|&byte| {
+                let lo = self.and_constant_byte_gate(byte, lo_mask);
+                let hi = self.sub_gate(
+                    &LTVariable::from(byte).linear_transform(inv_shift, F::zero()),
+                    &LTVariable::from(lo).linear_transform(inv_shift, F::zero()),
+                );
+                (lo, hi)
+            })
+            .collect_vec();
+
+        // out_i = 2^s * lo_i + hi_{i - 1}
+        let shift = F::from(1u64 << s);
+        (0..len)
+            .map(|i| {
+                let lo = splits[i].0;
+                let hi = splits[(i + len - 1) % len].1;
+                let out = self.add_gate(
+                    &LTVariable::from(lo).linear_transform(shift, F::zero()),
+                    &hi.into(),
+                );
+                Byte(out)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::PrimeField;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::Bn254;
+    use ark_bls12_381::Bls12_381;
+
+    use crate::{batch_test_field, constraint_system::test_gate_constraints_with_table};
+    use super::*;
+
+    fn test_byte_gates<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let x_value = rng.gen::<u8>();
+                let y_value = rng.gen::<u8>();
+                let x = cs.assign_variable(F::from(x_value));
+                let x = cs.byte_gate(x);
+                let y = cs.assign_variable(F::from(y_value));
+                let y = cs.byte_gate(y);
+                let c = cs.constant_byte(0xa5);
+
+                [
+                    (cs.xor_byte_gate(x, y), x_value ^ y_value),
+                    (cs.and_byte_gate(x, y), x_value & y_value),
+                    (cs.not_and_byte_gate(x, y), !x_value & y_value),
+                    (cs.xor_constant_byte_gate(x, 0x3c), x_value ^ 0x3c),
+                    (cs.xor_constant_byte_gate(x, 0), x_value),
+                    (cs.and_constant_byte_gate(y, 0x0f), y_value & 0x0f),
+                    (cs.xor_byte_gate(x, c), x_value ^ 0xa5),
+                ]
+                .into_iter()
+                .map(|(z, expected)| (z.into(), F::from(expected)))
+                .collect()
+            },
+            &[],
+            byte_table(),
+        )
+    }
+
+    fn test_rotl_bytes<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let value = rng.gen::<u64>();
+                let bytes = value
+                    .to_le_bytes()
+                    .into_iter()
+                    .map(|byte| {
+                        let x = cs.assign_variable(F::from(byte));
+                        cs.byte_gate(x)
+                    })
+                    .collect_vec();
+
+                [0, 1, 8, 13, 63, 64]
+                    .into_iter()
+                    .flat_map(|by| {
+                        let rotated = cs.rotl_bytes_le(&bytes, by);
+                        let expected = value.rotate_left(by as u32).to_le_bytes();
+                        rotated
+                            .into_iter()
+                            .zip(expected)
+                            .map(|(byte, value)| (byte.into(), F::from(value)))
+                            .collect_vec()
+                    })
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        )
+    }
+
+    fn test_to_bytes_le<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let value = rng.gen::<u64>();
+                let x = cs.assign_variable(F::from(value));
+                let x = LTVariable::from(x).linear_transform(F::one(), F::one());
+                let bytes = cs.to_bytes_le(&x, 9);
+
+                bytes
+                    .into_iter()
+                    .zip((value as u128 + 1).to_le_bytes())
+                    .map(|(byte, value)| (byte.into(), F::from(value)))
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        )
+    }
+
+    fn test_to_bytes_le_out_of_range<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| {
+                let x = cs.assign_variable(F::from(1u64 << 16));
+                cs.to_bytes_le(&x.into(), 2);
+
+                vec![]
+            },
+            &[] as &[F],
+            byte_table(),
+        )
+    }
+
+    fn test_byte_out_of_range<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| {
+                let x = cs.assign_variable(F::from(256u64));
+                cs.byte_gate(x);
+
+                vec![]
+            },
+            &[] as &[F],
+            byte_table(),
+        )
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_byte_gates, test_rotl_bytes, test_to_bytes_le],
+        [test_byte_out_of_range, test_to_bytes_le_out_of_range]
+    );
+
+    batch_test_field!(
+        Bls12_381,
+        [test_byte_gates, test_rotl_bytes, test_to_bytes_le],
+        [test_byte_out_of_range, test_to_bytes_le_out_of_range]
+    );
+
+    #[test]
+    #[should_panic(expected = "element not found in table")]
+    fn test_xor_non_byte() {
+        test_gate_constraints_with_table(
+            |cs| {
+                // bypass the range check of the operand
+                let x = Byte(cs.assign_variable(ark_bn254::Fr::from(259u64)));
+                let y = cs.constant_byte(5);
+                cs.xor_byte_gate(x, y);
+
+                vec![]
+            },
+            &[],
+            byte_table(),
+        )
+    }
+}
@@ -0,0 +1,469 @@
+//! Range and Comparison Gates
+
+use ark_ff::{BigInteger, PrimeField};
+use itertools::Itertools;
+
+use super::{Boolean, ConstraintSystem, LTVariable, Selectors, Variable};
+
+impl<F: PrimeField> ConstraintSystem<F> {
+    /// Assigns the `num_bits` lowest little-endian bits of `x` and constrains
+    /// them to recompose `x`.
+    fn decompose(&mut self, x: &LTVariable<F>, num_bits: usize) -> Vec<Boolean> {
+        let mut bit_values = self.value_of_lt_var(x).into_repr().to_bits_le();
+        // Higher bits are dropped, so the gates fail if x is too large.
+        bit_values.resize(num_bits, false);
+        let bits = bit_values
+            .into_iter()
+            .map(|bit| {
+                let var = self.assign_variable(bit.into());
+                self.boolean_gate(var)
+            })
+            .collect_vec();
+
+        let acc = self.bits_le_constrain(&bits);
+        self.equal_constrain(&acc.into(), x);
+
+        bits
+    }
+
+    /// Decomposes `x` into `num_bits` little-endian bits, which constrains `x`
+    /// to be less than `2^num_bits`.
+    ///
+    /// `num_bits` must be less than the bit size of the field, so that the
+    /// decomposition is unique. See [`ConstraintSystem::to_bits_le_strict`]
+    /// for full-width decompositions.
+    pub fn to_bits_le(&mut self, x: &LTVariable<F>, num_bits: usize) -> Vec<Boolean> {
+        assert!(
+            num_bits > 0 && num_bits < F::size_in_bits(),
+            "number of bits is out of range",
+        );
+
+        self.decompose(x, num_bits)
+    }
+
+    /// Decomposes `x` into the little-endian bits of its canonical
+    /// representation, whose length is the bit size of the field.
+    ///
+    /// The bits are constrained to be less than the modulus, otherwise both
+    /// `x` and `x + p` would be valid decompositions of small values.
+    pub fn to_bits_le_strict(&mut self, x: &LTVariable<F>) -> Vec<Boolean> {
+        let bits = self.decompose(x, F::size_in_bits());
+        self.enforce_bits_le_less_than_modulus(&bits);
+
+        bits
+    }
+
+    /// Constrains little-endian bits to be at most `p - 1`.
+    fn enforce_bits_le_less_than_modulus(&mut self, bits: &[Boolean]) {
+        let modulus_minus_one = (-F::one()).into_repr().to_bits_le();
+        self.enforce_bits_le_at_most(bits, &modulus_minus_one);
+    }
+
+    /// Constrains little-endian bits to be at most the constant of
+    /// little-endian bits `bound`, e.g. to check a scalar of another field.
+    ///
+    /// Walking from the most significant bit, we keep whether all bits so far
+    /// equal those of `bound`. Where `bound` has a zero bit and the prefix is
+    /// still equal, the bit must be zero as well.
+    pub fn enforce_bits_le_at_most(&mut self, bits: &[Boolean], bound: &[bool]) {
+        let mut bound = bound.to_vec();
+        if bound.len() > bits.len() {
+            assert!(
+                bound.drain(bits.len()..).all(|bit| !bit),
+                "bits are fewer than the bound",
+            );
+        }
+        bound.resize(bits.len(), false);
+
+        // None means that the prefix is constantly equal
+        let mut prefix_equal: Option<Boolean> = None;
+        let mut run = Vec::new();
+        for (bit, &bound_bit) in bits.iter().zip(&bound).rev() {
+            if bound_bit {
+                run.push(*bit);
+                continue;
+            }
+
+            // A run of ones ended, the prefix stays equal only if all bits of
+            // the run are set.
+            for run_bit in run.drain(..) {
+                prefix_equal = Some(match prefix_equal {
+                    Some(equal) => self.and_gate(equal, run_bit),
+                    None => run_bit,
+                });
+            }
+
+            // prefix_equal * bit = 0
+            match prefix_equal {
+                Some(equal) => {
+                    let sels = Selectors::new().with_mul(F::one());
+                    self.arith_constrain(equal.0, bit.0, Variable::Zero, sels, None);
+                }
+                None => {
+                    let sels = Selectors::new().with_left(F::one());
+                    self.arith_constrain(bit.0, Variable::Zero, Variable::Zero, sels, None);
+                }
+            }
+        }
+    }
+
+    /// Returns whether `x <= y`, given that `|y - x| < 2^num_bits`.
+    ///
+    /// The bound on the difference is not checked, so this is only sound if
+    /// the inputs are already known to be small, e.g. they are the outputs of
+    /// range checks. In exchange it costs a single range decomposition.
+    pub fn less_or_equal_with_bounded_difference(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Boolean {
+        // y - x + 2^num_bits lies in (0, 2^(num_bits + 1)), its top bit is set
+        // iff y - x >= 0.
+        let two_pow_n = F::from(2u64).pow([num_bits as u64]);
+        let difference = self.sub_gate(y, x);
+        let bits = self.to_bits_le(
+            &difference.linear_transform(F::one(), two_pow_n),
+            num_bits + 1,
+        );
+
+        bits[num_bits]
+    }
+
+    /// Returns whether `x <= y`, with both `x` and `y` constrained to be less
+    /// than `2^num_bits`.
+    pub fn less_or_equal(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Boolean {
+        self.to_bits_le(x, num_bits);
+        self.to_bits_le(y, num_bits);
+
+        self.less_or_equal_with_bounded_difference(x, y, num_bits)
+    }
+
+    /// Returns whether `x < y`, with both `x` and `y` constrained to be less
+    /// than `2^num_bits`.
+    pub fn less_than(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Boolean {
+        self.to_bits_le(x, num_bits);
+        self.to_bits_le(y, num_bits);
+
+        // x < y <=> x + 1 <= y, where |y - x - 1| <= 2^num_bits
+        let x_plus_one = x.linear_transform(F::one(), F::one());
+        self.less_or_equal_with_bounded_difference(&x_plus_one, y, num_bits + 1)
+    }
+
+    /// Returns whether `x >= y`, with both `x` and `y` constrained to be less
+    /// than `2^num_bits`.
+    pub fn greater_or_equal(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Boolean {
+        self.less_or_equal(y, x, num_bits)
+    }
+
+    /// Returns whether `x > y`, with both `x` and `y` constrained to be less
+    /// than `2^num_bits`.
+    pub fn greater_than(
+        &mut self,
+        x: &LTVariable<F>,
+        y: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Boolean {
+        self.less_than(y, x, num_bits)
+    }
+
+    /// Constrains a [`Boolean`] to be true.
+    pub fn enforce_true(&mut self, bit: Boolean) {
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_constant(-F::one());
+
+        self.arith_constrain(bit.0, Variable::Zero, Variable::Zero, sels, None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::{BigInteger, FpParameters, PrimeField};
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::Bn254;
+    use ark_bls12_381::Bls12_381;
+
+    use crate::{batch_test_field, constraint_system::{test_gate_constraints, LTVariable}};
+
+    const NUM_BITS: usize = 32;
+
+    fn test_to_bits_le<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let value = rng.gen::<u32>() as u64;
+                let x = cs.assign_variable(F::from(value));
+                let bits = cs.to_bits_le(&x.into(), NUM_BITS);
+
+                bits
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, bit)| (bit.into(), F::from((value >> i) & 1)))
+                    .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_to_bits_le_out_of_range<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = cs.assign_variable(F::from(1u64 << NUM_BITS));
+                cs.to_bits_le(&x.into(), NUM_BITS);
+
+                vec![]
+            },
+            &[],
+        )
+    }
+
+    fn test_bits_le_constrain<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                (1..=9)
+                    .map(|len| {
+                        let value = rng.gen_range(0..(1u64 << len));
+                        let bits = (0..len)
+                            .map(|i| {
+                                let bit = cs.assign_variable(F::from((value >> i) & 1));
+                                cs.boolean_gate(bit)
+                            })
+                            .collect::<Vec<_>>();
+
+                        (cs.bits_le_constrain(&bits).into(), F::from(value))
+                    })
+                    .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_to_bits_le_strict<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                [F::rand(rng), F::zero(), -F::one()]
+                    .into_iter()
+                    .flat_map(|value| {
+                        let x = cs.assign_variable(value);
+                        let bits = cs.to_bits_le_strict(&x.into());
+                        assert_eq!(bits.len(), F::size_in_bits());
+
+                        let value_bits = value.into_repr().to_bits_le();
+                        bits
+                            .into_iter()
+                            .zip(value_bits)
+                            .map(|(bit, value_bit)| (bit.into(), F::from(value_bit)))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_non_canonical_bits<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                // p + 1 fits in the bit size of the field and recomposes 1
+                let mut value = F::Params::MODULUS;
+                value.add_nocarry(&F::BigInt::from(1u64));
+                let bits = value
+                    .to_bits_le()
+                    .into_iter()
+                    .take(F::size_in_bits())
+                    .map(|bit| {
+                        let var = cs.assign_variable(F::from(bit));
+                        cs.boolean_gate(var)
+                    })
+                    .collect::<Vec<_>>();
+                let x = cs.bits_le_constrain(&bits);
+                cs.enforce_bits_le_less_than_modulus(&bits);
+
+                vec![(x.into(), F::one())]
+            },
+            &[],
+        )
+    }
+
+    fn bits_at_most_100<F: PrimeField>(value: u64) {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = cs.assign_variable(F::from(value));
+                let bits = cs.to_bits_le(&x.into(), 8);
+                // 100 = 0b1100100
+                cs.namespace("bound", |cs| {
+                    cs.enforce_bits_le_at_most(&bits, &[false, false, true, false, false, true, true])
+                });
+
+                vec![]
+            },
+            &[],
+        )
+    }
+
+    fn test_bits_at_most<F: PrimeField>() {
+        bits_at_most_100::<F>(100);
+        bits_at_most_100::<F>(99);
+        bits_at_most_100::<F>(0);
+    }
+
+    fn test_comparisons<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let max = u32::MAX as u64;
+                let small = rng.gen_range(0..max / 2);
+                let large = rng.gen_range(max / 2..=max);
+                let pairs = [
+                    (small, large),
+                    (large, small),
+                    (large, large),
+                    (0, 0),
+                    (0, max),
+                    (max, 0),
+                ];
+
+                pairs
+                    .into_iter()
+                    .flat_map(|(x_value, y_value)| {
+                        let x: LTVariable<F> = cs.assign_variable(F::from(x_value)).into();
+                        let y: LTVariable<F> = cs.assign_variable(F::from(y_value)).into();
+                        let lt = cs.less_than(&x, &y, NUM_BITS);
+                        let le = cs.less_or_equal(&x, &y, NUM_BITS);
+                        let gt = cs.greater_than(&x, &y, NUM_BITS);
+                        let ge = cs.greater_or_equal(&x, &y, NUM_BITS);
+
+                        [
+                            (lt.into(), F::from(x_value < y_value)),
+                            (le.into(), F::from(x_value <= y_value)),
+                            (gt.into(), F::from(x_value > y_value)),
+                            (ge.into(), F::from(x_value >= y_value)),
+                        ]
+                    })
+                    .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_comparisons_with_linear_transform<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                // x = 3 * 5 + 7 = 22, y = 2 * 11 + 1 = 23
+                let x = cs.assign_variable(F::from(5u64))
+                    .linear_transform(F::from(3u64), F::from(7u64));
+                let y = cs.assign_variable(F::from(11u64))
+                    .linear_transform(F::from(2u64), F::one());
+                let lt = cs.less_than(&x, &y, 8);
+                let gt = cs.greater_than(&x, &y, 8);
+                let le = cs.less_or_equal_with_bounded_difference(&y, &x, 1);
+
+                vec![(lt.into(), F::one()), (gt.into(), F::zero()), (le.into(), F::zero())]
+            },
+            &[],
+        )
+    }
+
+    fn test_less_than_out_of_range<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = cs.assign_variable(F::from(1u64 << NUM_BITS));
+                let y = cs.assign_variable(F::from(1u64));
+                cs.less_than(&x.into(), &y.into(), NUM_BITS);
+
+                vec![]
+            },
+            &[],
+        )
+    }
+
+    fn test_enforce_true<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = cs.assign_variable(F::from(3u64));
+                let y = cs.assign_variable(F::from(3u64));
+                let ge = cs.greater_or_equal(&x.into(), &y.into(), NUM_BITS);
+                cs.enforce_true(ge);
+
+                vec![]
+            },
+            &[],
+        )
+    }
+
+    fn test_enforce_true_failure<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = cs.assign_variable(F::from(3u64));
+                let y = cs.assign_variable(F::from(4u64));
+                let ge = cs.greater_or_equal(&x.into(), &y.into(), NUM_BITS);
+                cs.enforce_true(ge);
+
+                vec![]
+            },
+            &[],
+        )
+    }
+
+    batch_test_field!(
+        Bn254,
+        [
+            test_to_bits_le,
+            test_bits_le_constrain,
+            test_to_bits_le_strict,
+            test_comparisons,
+            test_comparisons_with_linear_transform,
+            test_enforce_true,
+            test_bits_at_most
+        ],
+        [
+            test_to_bits_le_out_of_range,
+            test_non_canonical_bits,
+            test_less_than_out_of_range,
+            test_enforce_true_failure
+        ]
+    );
+
+    batch_test_field!(
+        Bls12_381,
+        [
+            test_to_bits_le,
+            test_bits_le_constrain,
+            test_to_bits_le_strict,
+            test_comparisons,
+            test_comparisons_with_linear_transform,
+            test_enforce_true,
+            test_bits_at_most
+        ],
+        [
+            test_to_bits_le_out_of_range,
+            test_non_canonical_bits,
+            test_less_than_out_of_range,
+            test_enforce_true_failure
+        ]
+    );
+
+    #[test]
+    #[should_panic(expected = "in /bound is not satisfied")]
+    fn test_bits_above_bound() {
+        bits_at_most_100::<ark_bn254::Fr>(101);
+    }
+}
@@ -16,9 +16,11 @@
 
 use std::borrow::{Borrow, BorrowMut};
 use ark_ff::Field;
+use ark_serialize::CanonicalSerialize;
+use blake2::{Blake2s, Digest};
 
 use crate::permutation::Permutation;
-use super::{Variable, VariableMap, LTVariable, PublicInputs, PublicPositions};
+use super::{Variable, VariableMap, LTVariable, Namespaces, PublicInputs, PublicPositions};
 
 #[derive(Debug, Clone)]
 ///
@@ -31,6 +33,12 @@ pub struct Selectors<F: Field> {
     q_lookup: F,
 }
 
+impl<F: Field> Default for Selectors<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<F: Field> Selectors<F> {
     ///
     pub fn new() -> Self {
@@ -137,6 +145,8 @@ pub struct SetupComposer<F: Field> {
     pub perm: Permutation,
     /// Positions of public inputs
     pub pp: PublicPositions,
+    /// Namespaces of gates and variables.
+    pub namespaces: Namespaces,
 
     #[cfg(feature = "trace")]
     #[derivative(Debug="ignore")]
@@ -157,6 +167,7 @@ impl<F: Field> SetupComposer<F> {
             q_lookup: Vec::new(),
             perm: Permutation::new(),
             pp: PublicPositions::new(),
+            namespaces: Namespaces::new(),
             #[cfg(feature = "trace")]
             backtrace: Vec::new(),
         }
@@ -174,11 +185,18 @@ impl<F: Field> SetupComposer<F> {
             q_lookup: Vec::with_capacity(constraint_size),
             perm: Permutation::with_capacity(variable_size),
             pp: PublicPositions::new(),
+            namespaces: Namespaces::new(),
             #[cfg(feature = "trace")]
             backtrace: Vec::with_capacity(constraint_size),
         }
     }
 
+    /// Creates a new [`Variable`] in the current namespace.
+    pub fn new_variable(&mut self) -> Variable {
+        self.namespaces.tag_var();
+        self.perm.new_variable()
+    }
+
     /// Adds an arithmetic gate.
     /// This gate gives total freedom to the end user to implement the
     /// corresponding circuits in the most optimized way possible because
@@ -205,6 +223,7 @@ impl<F: Field> SetupComposer<F> {
         self.q_lookup.push(sels.q_lookup);
 
         self.perm.add_variables_to_map(w_l, w_r, w_o, self.n);
+        self.namespaces.tag_gate();
 
         if with_pi {
             self.pp.add_input(self.n);
@@ -218,6 +237,33 @@ impl<F: Field> SetupComposer<F> {
 
         self.n += 1;
     }
+
+    /// Returns a digest of the gate structure, i.e. the selectors, the wire
+    /// variables and the public input positions, which is kept by the prover
+    /// key to check that the circuit is proven as it was compiled.
+    pub fn digest(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        (self.n as u64)
+            .serialize(&mut bytes)
+            .expect("serialization to vec never fails");
+        for selector in [&self.q_m, &self.q_l, &self.q_r, &self.q_o, &self.q_c, &self.q_lookup] {
+            selector
+                .serialize(&mut bytes)
+                .expect("serialization to vec never fails");
+        }
+        for var in self.perm.gate_wires(self.n).iter().flatten() {
+            let index = match var {
+                Variable::Zero => u64::MAX,
+                Variable::Var(i) => *i as u64,
+            };
+            bytes.extend(index.to_le_bytes());
+        }
+        for pos in self.pp.get_pos() {
+            bytes.extend((*pos as u64).to_le_bytes());
+        }
+
+        Blake2s::digest(&bytes).to_vec()
+    }
 }
 
 ///
@@ -302,76 +348,121 @@ impl<F: Field> ProvingComposer<F> {
     }
 }
 
-///
+/// Circuit builder which records the gate structure for setup, the wires
+/// and values for proving, or both, so that every gadget is written once.
+/// Without a witness, every variable reads as zero.
 #[derive(derivative::Derivative)]
 #[derivative(Debug)]
-pub enum Composer<F: Field> {
-    ///
-    Setup(SetupComposer<F>),
-    ///
-    Proving(ProvingComposer<F>),
+pub struct Composer<F: Field> {
+    /// Gate structure, if recorded.
+    pub setup: Option<SetupComposer<F>>,
+    /// Wires and values, if recorded.
+    pub proving: Option<ProvingComposer<F>>,
 }
 
 impl<F: Field> Composer<F> {
+    ///
+    pub fn new(setup: Option<SetupComposer<F>>, proving: Option<ProvingComposer<F>>) -> Self {
+        assert!(setup.is_some() || proving.is_some(), "composer records nothing");
+
+        Self { setup, proving }
+    }
+
     ///
     pub fn size(&self) -> usize {
-        match self {
-            Composer::Setup(composer) => composer.n,
-            Composer::Proving(composer) => composer.n,
+        match (&self.setup, &self.proving) {
+            (Some(composer), _) => composer.n,
+            (None, Some(composer)) => composer.n,
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// Returns whether values are recorded.
+    pub fn has_witness(&self) -> bool {
+        self.proving.is_some()
+    }
+
+    /// Allocates a variable of `value`, which is dropped without a witness.
+    pub fn new_variable(&mut self, value: F) -> Variable {
+        let setup_var = self.setup.as_mut().map(|composer| composer.new_variable());
+        let proving_var = self.proving.as_mut().map(|composer| composer.var_map.assign_variable(value));
+        match (setup_var, proving_var) {
+            (Some(x), Some(y)) => {
+                debug_assert_eq!(x, y, "variables are allocated out of order");
+                x
+            }
+            (Some(x), None) | (None, Some(x)) => x,
+            (None, None) => unreachable!(),
         }
     }
-}
 
-impl<F: Field> Into<SetupComposer<F>> for Composer<F> {
-    fn into(self) -> SetupComposer<F> {
-        match self {
-            Self::Setup(composer) => composer,
-            _ => panic!("constraint system is not in setup mode"),
+    /// Adds the gate `q_m * a * b + q_l * a + q_r * b + q_o * c + q_c + PI = 0`
+    /// of wires `a`, `b` and `c`, whose public input is `pi` if any.
+    pub fn gate_constrain(
+        &mut self,
+        w_l: Variable,
+        w_r: Variable,
+        w_o: Variable,
+        sels: Selectors<F>,
+        pi: Option<F>,
+    ) {
+        if let Some(composer) = &mut self.setup {
+            composer.gate_constrain(w_l, w_r, w_o, sels, pi.is_some());
         }
+        if let Some(composer) = &mut self.proving {
+            composer.input_wires(w_l, w_r, w_o, pi);
+        }
+    }
+
+    /// Returns the value of `var`, or zero without a witness.
+    pub fn value_of_var(&self, var: Variable) -> F {
+        self.proving
+            .as_ref()
+            .map(|composer| composer.var_map.value_of_var(var))
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `x`, or zero without a witness.
+    pub fn value_of_lt_var(&self, x: &LTVariable<F>) -> F {
+        self.proving
+            .as_ref()
+            .map(|composer| composer.var_map.value_of_lt_var(x))
+            .unwrap_or_default()
+    }
+}
+
+impl<F: Field> From<Composer<F>> for SetupComposer<F> {
+    fn from(composer: Composer<F>) -> Self {
+        composer.setup.expect("constraint system records no gate structure")
     }
 }
 
 impl<F: Field> Borrow<SetupComposer<F>> for Composer<F> {
     fn borrow(&self) -> &SetupComposer<F> {
-        match self {
-            Self::Setup(composer) => composer,
-            _ => panic!("constraint system is not in setup mode"),
-        }
+        self.setup.as_ref().expect("constraint system records no gate structure")
     }
 }
 
 impl<F: Field> BorrowMut<SetupComposer<F>> for Composer<F> {
     fn borrow_mut(&mut self) -> &mut SetupComposer<F> {
-        match self {
-            Self::Setup(composer) => composer,
-            _ => panic!("constraint system is not in setup mode"),
-        }
+        self.setup.as_mut().expect("constraint system records no gate structure")
     }
 }
 
-impl<F: Field> Into<ProvingComposer<F>> for Composer<F> {
-    fn into(self) -> ProvingComposer<F> {
-        match self {
-            Self::Proving(composer) => composer,
-            _ => panic!("constraint system is not in proving mode"),
-        }
+impl<F: Field> From<Composer<F>> for ProvingComposer<F> {
+    fn from(composer: Composer<F>) -> Self {
+        composer.proving.expect("constraint system records no witness")
     }
 }
 
 impl<F: Field> Borrow<ProvingComposer<F>> for Composer<F> {
     fn borrow(&self) -> &ProvingComposer<F> {
-        match self {
-            Self::Proving(composer) => composer,
-            _ => panic!("constraint system is not in proving mode"),
-        }
+        self.proving.as_ref().expect("constraint system records no witness")
     }
 }
 
 impl<F: Field> BorrowMut<ProvingComposer<F>> for Composer<F> {
     fn borrow_mut(&mut self) -> &mut ProvingComposer<F> {
-        match self {
-            Self::Proving(composer) => composer,
-            _ => panic!("constraint system is not in proving mode"),
-        }
+        self.proving.as_mut().expect("constraint system records no witness")
     }
-}
\ No newline at end of file
+}
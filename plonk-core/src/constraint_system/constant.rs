@@ -0,0 +1,209 @@
+//! Constant variables, which are shared by every gadget of a circuit, and the
+//! folding of operations whose operands are all constant.
+
+use ark_ff::Field;
+use indexmap::{IndexMap, IndexSet};
+
+use super::*;
+
+/// Variables fixed to constant values, each constrained by a single gate.
+///
+/// Results of folded operations are pending: their gate is only emitted once
+/// they are wired into another gate, so chains of constants cost no gate.
+#[derive(Clone, Debug, Default)]
+pub struct ConstantCache<F: Field> {
+    vars: IndexMap<F, Variable>,
+    values: IndexMap<Variable, F>,
+    pending: IndexSet<Variable>,
+}
+
+impl<F: Field> ConstantCache<F> {
+    /// Returns the variable fixed to `value`, if any.
+    pub fn var_of(&self, value: &F) -> Option<Variable> {
+        self.vars.get(value).copied()
+    }
+
+    /// Returns the value of `var`, if it is fixed.
+    pub fn value_of(&self, var: Variable) -> Option<F> {
+        self.values.get(&var).copied()
+    }
+
+    /// Returns the number of constant variables.
+    pub fn size(&self) -> usize {
+        self.vars.len()
+    }
+
+    fn insert(&mut self, value: F, var: Variable) {
+        self.vars.insert(value, var);
+        self.values.insert(var, value);
+    }
+
+    /// Returns the value of `var` if its gate is pending, which is then
+    /// considered emitted.
+    fn take_pending(&mut self, var: Variable) -> Option<F> {
+        if self.pending.swap_remove(&var) {
+            self.value_of(var)
+        } else {
+            None
+        }
+    }
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Returns a variable fixed to `value`. Zero is the zero variable and
+    /// needs no gate, others are constrained by x - value = 0 once and shared
+    /// by later calls.
+    pub fn constant_var(&mut self, value: F) -> Variable {
+        if value.is_zero() {
+            return Variable::Zero;
+        }
+        let x = self.lazy_constant_var(value);
+        self.emit_pending_constant(x);
+
+        x
+    }
+
+    /// Returns a variable fixed to `value`, whose gate is only emitted once
+    /// it is wired into another gate.
+    pub(super) fn lazy_constant_var(&mut self, value: F) -> Variable {
+        if value.is_zero() {
+            return Variable::Zero;
+        }
+        if let Some(x) = self.constants.var_of(&value) {
+            return x;
+        }
+
+        let x = self.assign_variable(value);
+        self.constants.insert(value, x);
+        self.constants.pending.insert(x);
+
+        x
+    }
+
+    /// Emits the gate x - value = 0 of `x` if it is a pending constant.
+    pub(super) fn emit_pending_constant(&mut self, x: Variable) {
+        if let Some(value) = self.constants.take_pending(x) {
+            let sels = Selectors::new()
+                .with_left(F::one())
+                .with_constant(-value);
+
+            self.composer.gate_constrain(x, Variable::Zero, Variable::Zero, sels, None);
+        }
+    }
+
+    /// Returns the value of `x` if it is known without a witness, i.e. `x` is
+    /// a constant or a transform of a variable from
+    /// [`ConstraintSystem::constant_var`].
+    pub fn constant_value(&self, x: &LTVariable<F>) -> Option<F> {
+        match x.var {
+            Variable::Zero => Some(x.offset),
+            var => self.constants.value_of(var).map(|value| x.coeff * value + x.offset),
+        }
+    }
+
+    /// Returns `x` as a variable without emitting a gate, if it is a
+    /// constant or a variable as is.
+    pub(super) fn as_variable(&mut self, x: &LTVariable<F>) -> Option<Variable> {
+        if let Some(value) = self.constant_value(x) {
+            Some(self.lazy_constant_var(value))
+        } else if x.coeff.is_one() && x.offset.is_zero() {
+            Some(x.var)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `op` of the values of `x` and `y` as a constant variable if
+    /// both are constant, so that no gate is emitted for the operation, nor
+    /// for the result unless it is used by another gate.
+    pub(super) fn fold<O>(&mut self, x: &LTVariable<F>, y: &LTVariable<F>, op: O) -> Option<Variable>
+    where
+        O: FnOnce(F, F) -> F,
+    {
+        let x = self.constant_value(x)?;
+        let y = self.constant_value(y)?;
+
+        Some(self.lazy_constant_var(op(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::batch_test_field;
+    use super::*;
+
+    fn test_constant_cache<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let x = cs.constant_var(F::from(7u64));
+                assert_eq!(cs.constant_var(F::from(7u64)), x);
+                assert_eq!(cs.constant_var(F::zero()), Variable::Zero);
+                assert_eq!(cs.constant_boolean(true).0, cs.constant_var(F::one()));
+                assert_eq!(cs.constants.size(), 2);
+                assert_eq!(cs.composer.size(), 2);
+
+                vec![(x.into(), F::from(7u64))]
+            },
+            &[],
+        );
+    }
+
+    fn test_constant_folding<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let three = LTVariable::constant(F::from(3u64));
+                let five = LTVariable::constant(F::from(5u64));
+                let two = cs.sub_gate(&five, &three);
+                let six = cs.mul_gate(&two.into(), &three);
+                let thirty = cs.mul_gate(&five, &six.into());
+                let one = cs.div_gate(&six.into(), &six.into());
+                let four = cs.square_gate(&two.into());
+                let x = cs.add_gate(&four.into(), &one.into());
+                // the folded 1 is a constant bit as is
+                let bit = Boolean(one);
+                let y = cs.conditional_select(bit, &thirty.into(), &three);
+                assert_eq!(y, thirty);
+                // no gate for the constant chain
+                assert_eq!(cs.composer.size(), 0);
+
+                // shared with the folded 5, which is now constrained
+                assert_eq!(cs.constant_var(F::from(5u64)), x);
+                assert_eq!(cs.composer.size(), 1);
+
+                // a gate over a folded constant constrains it first
+                let z = cs.assign_variable(F::from(9u64));
+                let w = cs.add_gate(&z.into(), &three);
+                let v = cs.mul_gate(&z.into(), &thirty.into());
+                assert_eq!(cs.composer.size(), 4);
+
+                vec![
+                    (thirty.into(), F::from(30u64)),
+                    (y.into(), F::from(30u64)),
+                    (w.into(), F::from(12u64)),
+                    (v.into(), F::from(270u64)),
+                ]
+            },
+            &[],
+        );
+    }
+
+    fn test_division_by_constant_zero<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let x = LTVariable::constant(F::one());
+                cs.div_gate(&x, &LTVariable::zero());
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_constant_cache, test_constant_folding],
+        [test_division_by_constant_zero]
+    );
+}
@@ -0,0 +1,602 @@
+//! Twisted Edwards Curve Gates
+//!
+//! Points of a twisted Edwards curve `a * x^2 + y^2 = 1 + d * x^2 * y^2`
+//! whose base field is the field of the circuit, e.g. Jubjub in circuits over
+//! the scalar field of BLS12-381. The addition law is complete when `a` is a
+//! square and `d` is not, so it also doubles and adds the identity.
+
+use ark_ec::{
+    models::TEModelParameters,
+    twisted_edwards_extended::GroupAffine,
+    AffineCurve,
+    ProjectiveCurve,
+};
+use ark_ff::{BitIteratorBE, Field, One, Zero};
+use core::marker::PhantomData;
+
+use crate::lookup::LookupTable;
+use super::{Boolean, ConstraintSystem, LTVariable, Selectors, Variable};
+
+/// Windows of `2^k` constant points, whose points are looked up by
+/// [`AffinePoint::lookup`] by a `k`-bit index.
+///
+/// The lookup table is a single column, so as in [`byte_table`](super::byte_table)
+/// an entry packs a coordinate with a key: coordinate `c` (0 for x, 1 for y)
+/// of point `m` of window `w` is `x_c + tag + 2 * (2^k * w + m) + c`.
+/// Unlike bytes, the coordinate doesn't fit beside the key, so any entry
+/// minus the key of `(w, m, c)` passes the lookup as a coordinate. Such a
+/// pair of coordinates is on the curve with negligible probability, hence
+/// looked up points are constrained to be on the curve. Tables in the same
+/// circuit must have distinct tags, otherwise the points of a table could be
+/// passed for the other's.
+#[derive(derivative::Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct PointTable<P: TEModelParameters> {
+    tag: P::BaseField,
+    window_bits: usize,
+    windows: Vec<Vec<GroupAffine<P>>>,
+}
+
+impl<P: TEModelParameters> PointTable<P> {
+    /// Creates the table of `windows` of `2^window_bits` points each.
+    pub fn new(tag: P::BaseField, window_bits: usize, windows: Vec<Vec<GroupAffine<P>>>) -> Self {
+        assert!(
+            windows.iter().all(|points| points.len() == 1 << window_bits),
+            "window size must be 2^(number of bits)",
+        );
+
+        Self { tag, window_bits, windows }
+    }
+
+    ///
+    pub fn windows(&self) -> &[Vec<GroupAffine<P>>] {
+        &self.windows
+    }
+
+    /// Returns the key of coordinate `c` of the first point of `window`, to
+    /// which twice the index of the point is added.
+    fn key(&self, window: usize, c: u64) -> P::BaseField {
+        self.tag + P::BaseField::from(2 * ((window as u64) << self.window_bits) + c)
+    }
+
+    /// Returns the entries which the lookup table of the circuit must
+    /// contain.
+    pub fn lookup_table(&self) -> LookupTable<P::BaseField> {
+        self.windows
+            .iter()
+            .enumerate()
+            .flat_map(|(window, points)| {
+                points.iter().enumerate().flat_map(move |(m, point)| {
+                    let index = P::BaseField::from(2 * m as u64);
+                    [
+                        point.x + self.key(window, 0) + index,
+                        point.y + self.key(window, 1) + index,
+                    ]
+                })
+            })
+            .into()
+    }
+}
+
+/// Point of the curve `P` in circuit. Coordinates are [`LTVariable`]s, so
+/// constant points and negation cost no gate.
+#[derive(derivative::Derivative)]
+#[derivative(Debug(bound = ""), PartialEq(bound = ""))]
+pub struct AffinePoint<P: TEModelParameters> {
+    x: LTVariable<P::BaseField>,
+    y: LTVariable<P::BaseField>,
+    _p: PhantomData<P>,
+}
+
+impl<P: TEModelParameters> Clone for AffinePoint<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P: TEModelParameters> Copy for AffinePoint<P> {}
+
+impl<P: TEModelParameters> AffinePoint<P> {
+    fn from_coordinates(x: LTVariable<P::BaseField>, y: LTVariable<P::BaseField>) -> Self {
+        Self { x, y, _p: PhantomData }
+    }
+
+    ///
+    pub fn x(&self) -> LTVariable<P::BaseField> {
+        self.x
+    }
+
+    ///
+    pub fn y(&self) -> LTVariable<P::BaseField> {
+        self.y
+    }
+
+    /// Returns the constant `point`, which needs no gate.
+    pub fn constant(point: &GroupAffine<P>) -> Self {
+        Self::from_coordinates(LTVariable::constant(point.x), LTVariable::constant(point.y))
+    }
+
+    /// Returns the identity `(0, 1)`.
+    pub fn identity() -> Self {
+        Self::constant(&GroupAffine::zero())
+    }
+
+    /// Assigns `point` and constrains it to be on the curve.
+    pub fn new(cs: &mut ConstraintSystem<P::BaseField>, point: &GroupAffine<P>) -> Self {
+        let x = cs.assign_variable(point.x);
+        let y = cs.assign_variable(point.y);
+        let point = Self::from_coordinates(x.into(), y.into());
+        point.enforce_on_curve(cs);
+
+        point
+    }
+
+    /// Constrains the point to be on the curve.
+    /// a * x^2 + y^2 - d * x^2 * y^2 - 1 = 0
+    pub fn enforce_on_curve(&self, cs: &mut ConstraintSystem<P::BaseField>) {
+        let x2 = cs.square_gate(&self.x);
+        let y2 = cs.square_gate(&self.y);
+        let sels = Selectors::new()
+            .with_mul(-P::COEFF_D)
+            .with_left(P::COEFF_A)
+            .with_right(P::BaseField::one())
+            .with_constant(-P::BaseField::one());
+
+        cs.arith_constrain(x2, y2, Variable::Zero, sels, None);
+    }
+
+    /// Constrains both points to be equal.
+    pub fn enforce_equal(&self, cs: &mut ConstraintSystem<P::BaseField>, other: &Self) {
+        cs.equal_constrain(&self.x, &other.x);
+        cs.equal_constrain(&self.y, &other.y);
+    }
+
+    /// Returns `(-x, y)`, which needs no gate.
+    pub fn neg(&self) -> Self {
+        let minus_one = -P::BaseField::one();
+        Self::from_coordinates(
+            self.x.linear_transform(minus_one, P::BaseField::zero()),
+            self.y,
+        )
+    }
+
+    /// Adds both points.
+    /// x3 = (x1 * y2 + y1 * x2) / (1 + d * x1 * x2 * y1 * y2)
+    /// y3 = (y1 * y2 - a * x1 * x2) / (1 - d * x1 * x2 * y1 * y2)
+    pub fn add(&self, cs: &mut ConstraintSystem<P::BaseField>, other: &Self) -> Self {
+        let x1y2 = cs.mul_gate(&self.x, &other.y);
+        let y1x2 = cs.mul_gate(&self.y, &other.x);
+        let x1x2 = cs.mul_gate(&self.x, &other.x);
+        let y1y2 = cs.mul_gate(&self.y, &other.y);
+        let t = cs.mul_gate(&x1y2.into(), &y1x2.into());
+
+        let x_numerator = cs.add_gate(&x1y2.into(), &y1x2.into());
+        let y_numerator = cs.linear_transform_gate(
+            &y1y2.into(),
+            &x1x2.into(),
+            P::BaseField::one(),
+            -P::COEFF_A,
+            P::BaseField::zero(),
+        );
+
+        let x3 = ratio_gate(cs, x_numerator, t, P::COEFF_D);
+        let y3 = ratio_gate(cs, y_numerator, t, -P::COEFF_D);
+
+        Self::from_coordinates(x3.into(), y3.into())
+    }
+
+    ///
+    pub fn double(&self, cs: &mut ConstraintSystem<P::BaseField>) -> Self {
+        self.add(cs, self)
+    }
+
+    /// Selects `choice_a` if `bit` is set, otherwise `choice_b`.
+    pub fn conditional_select(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        bit: Boolean,
+        choice_a: &Self,
+        choice_b: &Self,
+    ) -> Self {
+        let x = cs.conditional_select(bit, &choice_a.x, &choice_b.x);
+        let y = cs.conditional_select(bit, &choice_a.y, &choice_b.y);
+
+        Self::from_coordinates(x.into(), y.into())
+    }
+
+    /// Selects `table[sum(bits[i] * 2^i)]` among `2^m` constant points by an
+    /// `m`-bit little-endian index, without a lookup table.
+    ///
+    /// The first bit selects between pairs of constants linearly, which costs
+    /// no gate, the others go through [`ConstraintSystem::multiplexer`].
+    pub fn select(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        bits: &[Boolean],
+        table: &[GroupAffine<P>],
+    ) -> Self {
+        assert_eq!(table.len(), 1 << bits.len(), "table size must be 2^(number of bits)");
+
+        let (first, rest) = match bits.split_first() {
+            Some(split) => split,
+            None => return Self::constant(&table[0]),
+        };
+        // c0 + bit * (c1 - c0)
+        let bit_var = LTVariable::from(*first);
+        let (xs, ys): (Vec<_>, Vec<_>) = table
+            .chunks(2)
+            .map(|pair| {
+                (
+                    bit_var.linear_transform(pair[1].x - pair[0].x, pair[0].x),
+                    bit_var.linear_transform(pair[1].y - pair[0].y, pair[0].y),
+                )
+            })
+            .unzip();
+
+        let x = cs.multiplexer(rest, &xs);
+        let y = cs.multiplexer(rest, &ys);
+
+        Self::from_coordinates(x, y)
+    }
+
+    /// Looks up point `sum(bits[i] * 2^i)` of `window` in `table`, whose
+    /// entries the lookup table of the circuit must contain.
+    ///
+    /// This costs a lookup per coordinate and the curve check, along with
+    /// the gates summing the index.
+    pub fn lookup(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        table: &PointTable<P>,
+        window: usize,
+        bits: &[Boolean],
+    ) -> Self {
+        assert_eq!(bits.len(), table.window_bits, "invalid number of index bits");
+        let one = P::BaseField::one();
+        let two = P::BaseField::from(2u64);
+
+        // m = sum(bits[i] * 2^i)
+        let mut index = LTVariable::zero();
+        let mut power = one;
+        for (i, &bit) in bits.iter().enumerate() {
+            let term = LTVariable::from(bit).linear_transform(power, P::BaseField::zero());
+            index = if i == 0 { term } else { cs.add_gate(&index, &term).into() };
+            power.double_in_place();
+        }
+        let m = bits
+            .iter()
+            .rev()
+            .fold(0, |m, bit| (m << 1) | usize::from(cs.value_of_var(bit.0) == one));
+        let point = table.windows[window][m];
+
+        let x = cs.assign_variable(point.x);
+        let y = cs.assign_variable(point.y);
+        for (coordinate, c) in [(x, 0), (y, 1)] {
+            // key = coordinate + 2 * m + key of the window
+            let offset = table.key(window, c);
+            let key_value = cs.value_of_var(coordinate) + two * cs.value_of_lt_var(&index) + offset;
+            cs.check_lookup(&key_value);
+            let key = cs.assign_variable(key_value);
+            let sels = Selectors::new()
+                .with_left(one)
+                .with_right(two)
+                .with_out(-one)
+                .with_constant(offset)
+                .with_lookup()
+                .by_right_lt(&index);
+
+            cs.arith_constrain(coordinate, index.var, key, sels, None);
+        }
+
+        let point = Self::from_coordinates(x.into(), y.into());
+        point.enforce_on_curve(cs);
+
+        point
+    }
+
+    /// Multiplies the constant `base` by the scalar of little-endian `bits`.
+    ///
+    /// The multiples `2^i * base` are constants, so selecting either of them
+    /// or the identity by a bit is linear and costs no gate. Only one
+    /// addition per bit after the first is needed.
+    pub fn fixed_base_scalar_mul(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        base: &GroupAffine<P>,
+        bits: &[Boolean],
+    ) -> Self {
+        let mut power = *base;
+        let mut acc: Option<Self> = None;
+        for &bit in bits {
+            // (bit * x, 1 + bit * (y - 1))
+            let bit_var = LTVariable::from(bit);
+            let selected = Self::from_coordinates(
+                bit_var.linear_transform(power.x, P::BaseField::zero()),
+                bit_var.linear_transform(power.y - P::BaseField::one(), P::BaseField::one()),
+            );
+            acc = Some(match acc {
+                Some(acc) => acc.add(cs, &selected),
+                None => selected,
+            });
+            power = power.into_projective().double().into_affine();
+        }
+
+        acc.unwrap_or_else(Self::identity)
+    }
+
+    /// Multiplies the point by the scalar of little-endian `bits`, by double
+    /// and add.
+    pub fn scalar_mul(&self, cs: &mut ConstraintSystem<P::BaseField>, bits: &[Boolean]) -> Self {
+        let identity = Self::identity();
+        let mut acc = identity;
+        let mut power = *self;
+        for (i, &bit) in bits.iter().enumerate() {
+            let selected = Self::conditional_select(cs, bit, &power, &identity);
+            acc = if i == 0 { selected } else { acc.add(cs, &selected) };
+            if i + 1 < bits.len() {
+                power = power.double(cs);
+            }
+        }
+
+        acc
+    }
+
+    /// Multiplies the point by the cofactor of the curve, which maps it into
+    /// the prime order subgroup. The cofactor is a constant, so this is only
+    /// doublings and additions.
+    pub fn mul_by_cofactor(&self, cs: &mut ConstraintSystem<P::BaseField>) -> Self {
+        let mut bits = BitIteratorBE::without_leading_zeros(P::COFACTOR);
+        assert_eq!(bits.next(), Some(true), "cofactor must not be zero");
+
+        bits.fold(*self, |acc, bit| {
+            let acc = acc.double(cs);
+            if bit {
+                acc.add(cs, self)
+            } else {
+                acc
+            }
+        })
+    }
+}
+
+/// Returns `z` such that `z * (1 + coeff * t) = s`.
+/// coeff * z * t + z - s = 0
+fn ratio_gate<F: Field>(
+    cs: &mut ConstraintSystem<F>,
+    s: Variable,
+    t: Variable,
+    coeff: F,
+) -> Variable {
+    let denominator = F::one() + coeff * cs.value_of_var(t);
+    // a zero denominator has no valid witness and is left to fail in the gate
+    let z_value = cs.value_of_var(s) * denominator.inverse().unwrap_or_default();
+    let z = cs.assign_variable(z_value);
+
+    let sels = Selectors::new()
+        .with_mul(coeff)
+        .with_left(F::one())
+        .with_out(-F::one());
+
+    cs.arith_constrain(z, t, s, sels, None);
+
+    z
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{BigInteger, PrimeField, UniformRand};
+    use ark_std::test_rng;
+    use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsParameters, Fq, Fr};
+
+    use crate::constraint_system::{test_gate_constraints, test_gate_constraints_with_table};
+    use super::*;
+
+    type Point = AffinePoint<EdwardsParameters>;
+
+    fn check(point: &Point, expected: &EdwardsAffine) -> [(LTVariable<Fq>, Fq); 2] {
+        [(point.x(), expected.x), (point.y(), expected.y)]
+    }
+
+    fn assign_bits(cs: &mut ConstraintSystem<Fq>, scalar: &Fr) -> Vec<Boolean> {
+        scalar
+            .into_repr()
+            .to_bits_le()
+            .into_iter()
+            .take(Fr::size_in_bits())
+            .map(|bit| {
+                let var = cs.assign_variable(bit.into());
+                cs.boolean_gate(var)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_point_arithmetic() {
+        let rng = &mut test_rng();
+        let p = EdwardsAffine::prime_subgroup_generator()
+            .mul(Fr::rand(rng))
+            .into_affine();
+        let q = EdwardsAffine::prime_subgroup_generator()
+            .mul(Fr::rand(rng))
+            .into_affine();
+        let sum = (p.into_projective() + q.into_projective()).into_affine();
+        let double = p.into_projective().double().into_affine();
+
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let p_var = Point::new(cs, &p);
+                let q_var = Point::new(cs, &q);
+                let identity = Point::identity();
+                let one = cs.assign_variable(1u64.into());
+                let bit = cs.boolean_gate(one);
+
+                [
+                    check(&p_var.add(cs, &q_var), &sum),
+                    check(&p_var.double(cs), &double),
+                    check(&p_var.neg(), &-p),
+                    check(&p_var.add(cs, &p_var.neg()), &EdwardsAffine::zero()),
+                    check(&p_var.add(cs, &identity), &p),
+                    check(&Point::conditional_select(cs, bit, &p_var, &q_var), &p),
+                    check(&Point::conditional_select(cs, Boolean(Variable::Zero), &p_var, &q_var), &q),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_scalar_mul() {
+        let rng = &mut test_rng();
+        let base = EdwardsAffine::prime_subgroup_generator();
+        let p = base.mul(Fr::rand(rng)).into_affine();
+        let scalar = Fr::rand(rng);
+
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let bits = assign_bits(cs, &scalar);
+                let p_var = Point::new(cs, &p);
+                let fixed = Point::fixed_base_scalar_mul(cs, &base, &bits);
+                let variable = p_var.scalar_mul(cs, &bits);
+                let empty = p_var.scalar_mul(cs, &[]);
+
+                [
+                    check(&fixed, &base.mul(scalar).into_affine()),
+                    check(&variable, &p.mul(scalar).into_affine()),
+                    check(&empty, &EdwardsAffine::zero()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_mul_by_cofactor() {
+        let rng = &mut test_rng();
+        let p = EdwardsAffine::prime_subgroup_generator().mul(Fr::rand(rng));
+        // (0, -1) has order 2
+        let torsion = EdwardsAffine::new(Fq::zero(), -Fq::one());
+        let q = (p + torsion.into_projective()).into_affine();
+        assert_eq!(q.mul_by_cofactor(), p.into_affine().mul_by_cofactor());
+
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let q_var = Point::new(cs, &q);
+                check(&q_var.mul_by_cofactor(cs), &q.mul_by_cofactor()).to_vec()
+            },
+            &[],
+        );
+    }
+
+    fn random_points(num: usize) -> Vec<EdwardsAffine> {
+        let rng = &mut test_rng();
+        (0..num)
+            .map(|_| EdwardsAffine::prime_subgroup_generator().mul(Fr::rand(rng)).into_affine())
+            .collect()
+    }
+
+    fn assign_index(cs: &mut ConstraintSystem<Fq>, index: u64, num_bits: usize) -> Vec<Boolean> {
+        (0..num_bits)
+            .map(|i| {
+                let bit = cs.assign_variable(Fq::from((index >> i) & 1));
+                cs.boolean_gate(bit)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let table = random_points(8);
+
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let constant = Point::select(cs, &[], &table[..1]);
+                (0..8)
+                    .flat_map(|index| {
+                        let bits = assign_index(cs, index, 3);
+                        check(&Point::select(cs, &bits, &table), &table[index as usize])
+                    })
+                    .chain(check(&constant, &table[0]))
+                    .collect()
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let points = random_points(16);
+        let table = PointTable::new(Fq::from(7u64), 3, points.chunks(8).map(<[_]>::to_vec).collect());
+        let constant_table = PointTable::new(Fq::from(9u64), 0, vec![points[..1].to_vec()]);
+        let lookup_table = LookupTable(
+            table.lookup_table().0.into_iter().chain(constant_table.lookup_table().0).collect(),
+        );
+        assert_eq!(lookup_table.size(), 2 * 16 + 2);
+
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let constant = Point::lookup(cs, &constant_table, 0, &[]);
+                (0..16)
+                    .flat_map(|index| {
+                        let bits = assign_index(cs, index % 8, 3);
+                        let point = Point::lookup(cs, &table, index as usize / 8, &bits);
+                        check(&point, &points[index as usize])
+                    })
+                    .chain(check(&constant, &points[0]))
+                    .collect()
+            },
+            &[],
+            lookup_table.clone(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "element not found in table")]
+    fn test_lookup_other_table() {
+        let points = random_points(16);
+        let table = PointTable::new(Fq::from(7u64), 3, vec![points[..8].to_vec()]);
+        let other_table = PointTable::new(Fq::from(8u64), 3, vec![points[8..].to_vec()]);
+
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let bits = assign_index(cs, 5, 3);
+                check(&Point::lookup(cs, &other_table, 0, &bits), &points[13]).to_vec()
+            },
+            &[],
+            table.lookup_table(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_point_not_on_curve() {
+        test_gate_constraints(
+            |cs| {
+                let point = GroupAffine::<EdwardsParameters>::new(1u64.into(), 1u64.into());
+                Point::new(cs, &point);
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is not satisfied")]
+    fn test_ratio_zero_denominator() {
+        test_gate_constraints(
+            |cs| {
+                // 1 + d * t = 0
+                let t_value = -EdwardsParameters::COEFF_D.inverse().unwrap();
+                let s = cs.assign_variable(1u64.into());
+                let t = cs.assign_variable(t_value);
+                ratio_gate(cs, s, t, EdwardsParameters::COEFF_D);
+
+                vec![]
+            },
+            &[],
+        );
+    }
+}
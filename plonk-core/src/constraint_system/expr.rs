@@ -0,0 +1,401 @@
+//! Expressions over the variables of a circuit, which lower operators to the
+//! gates of [`ConstraintSystem`].
+//!
+//! Within [`ConstraintSystem::exprs`], `x * y + z * 2` reads as
+//! `cs.add_gate(&cs.mul_gate(&x, &y).into(), &z.linear_transform(2, 0))`.
+//! Operations with a constant are kept as linear transforms and need no gate.
+//! Since `==` can only return a `bool`, equality is either enforced by
+//! [`Expr::enforce_eq`] or computed by [`Expr::is_eq`].
+
+use core::{
+    cell::{RefCell, RefMut},
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+use ark_ff::Field;
+
+use super::*;
+
+/// Scope in which [`Expr`]s of a [`ConstraintSystem`] are created.
+pub struct ExprScope<'a, F: Field> {
+    cs: RefCell<&'a mut ConstraintSystem<F>>,
+}
+
+impl<'a, F: Field> ExprScope<'a, F> {
+    /// Runs `f` on the constraint system, e.g. to call a gadget which is not
+    /// written as expressions.
+    pub fn with<R, P>(&self, f: P) -> R
+    where
+        P: FnOnce(&mut ConstraintSystem<F>) -> R,
+    {
+        f(&mut self.cs())
+    }
+
+    fn cs(&self) -> RefMut<'_, &'a mut ConstraintSystem<F>> {
+        self.cs.borrow_mut()
+    }
+
+    /// Returns `x` as an expression.
+    pub fn var<T: Into<LTVariable<F>>>(&'a self, x: T) -> Expr<'a, F> {
+        Expr {
+            scope: self,
+            lt: x.into(),
+        }
+    }
+
+    /// Assigns `value` to a new variable.
+    pub fn witness(&'a self, value: F) -> Expr<'a, F> {
+        let x = self.cs().assign_variable(value);
+        self.var(x)
+    }
+
+    /// Returns `value` as an expression without any variable.
+    pub fn constant(&'a self, value: F) -> Expr<'a, F> {
+        self.var(LTVariable::constant(value))
+    }
+
+    /// Returns the output of `gadget` on field element `inputs`, e.g. a
+    /// hash.
+    pub fn gadget<G>(&'a self, gadget: &G, inputs: &[Expr<'a, F>]) -> Expr<'a, F>
+    where
+        G: Gadget<F, Input = [LTVariable<F>], Output = LTVariable<F>>,
+    {
+        let inputs = inputs.iter().map(Expr::lt).collect::<Vec<_>>();
+        let z = gadget.synthesize(&mut self.cs(), &inputs);
+        self.var(z)
+    }
+
+    /// Returns `a` if `bit` is 1 and `b` otherwise, see
+    /// [`ConstraintSystem::conditional_select`].
+    pub fn select(&'a self, bit: Boolean, a: Expr<'a, F>, b: Expr<'a, F>) -> Expr<'a, F> {
+        let z = self.cs().conditional_select(bit, &a.lt, &b.lt);
+        self.var(z)
+    }
+}
+
+/// A transformed variable bound to an [`ExprScope`].
+#[derive(Clone, Copy)]
+pub struct Expr<'a, F: Field> {
+    scope: &'a ExprScope<'a, F>,
+    lt: LTVariable<F>,
+}
+
+impl<'a, F: Field> core::fmt::Debug for Expr<'a, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.lt.fmt(f)
+    }
+}
+
+impl<'a, F: Field> Expr<'a, F> {
+    ///
+    pub fn lt(&self) -> LTVariable<F> {
+        self.lt
+    }
+
+    /// Returns the value of the expression, zero without a witness.
+    pub fn value(&self) -> F {
+        self.scope.cs().value_of_lt_var(&self.lt)
+    }
+
+    /// Returns the value of the expression if it is a constant.
+    pub fn constant_value(&self) -> Option<F> {
+        self.scope.cs().constant_value(&self.lt)
+    }
+
+    /// Returns the expression as a variable, which needs a gate unless the
+    /// expression is a constant or a variable as is.
+    pub fn to_variable(&self) -> Variable {
+        let mut cs = self.scope.cs();
+        match cs.as_variable(&self.lt) {
+            Some(x) => x,
+            None => cs.linear_transform_gate(&self.lt, &LTVariable::zero(), F::one(), F::zero(), F::zero()),
+        }
+    }
+
+    fn with_lt(&self, lt: LTVariable<F>) -> Self {
+        self.scope.var(lt)
+    }
+
+    fn binary_gate<O>(&self, other: &Self, op: O) -> Self
+    where
+        O: FnOnce(&mut ConstraintSystem<F>, &LTVariable<F>, &LTVariable<F>) -> Variable,
+    {
+        let z = op(&mut self.scope.cs(), &self.lt, &other.lt);
+        self.with_lt(z.into())
+    }
+
+    ///
+    pub fn square(&self) -> Self {
+        let y = self.scope.cs().square_gate(&self.lt);
+        self.with_lt(y.into())
+    }
+
+    /// Constrains the expression to equal `other`.
+    pub fn enforce_eq(&self, other: &Self) {
+        self.scope.cs().equal_constrain(&self.lt, &other.lt);
+    }
+
+    /// Returns whether the expression equals `other`.
+    pub fn is_eq(&self, other: &Self) -> Boolean {
+        self.scope.cs().is_eq_with_output(&self.lt, &other.lt)
+    }
+
+    /// Returns whether the expression is zero.
+    pub fn is_zero(&self) -> Boolean {
+        self.scope.cs().is_zero_with_output(&self.lt)
+    }
+
+    /// Constrains the expression to be in the lookup table.
+    pub fn lookup(&self) {
+        self.scope.cs().lookup_constrain(&self.lt);
+    }
+
+    /// Sets the expression as a public input.
+    pub fn set_public(&self) {
+        self.scope.cs().set_variable_public(&self.lt);
+    }
+}
+
+impl<'a, F: Field> Add for Expr<'a, F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        match (other.lt.var, self.lt.var) {
+            (Variable::Zero, _) => self + other.lt.offset,
+            (_, Variable::Zero) => other + self.lt.offset,
+            _ => self.binary_gate(&other, ConstraintSystem::add_gate),
+        }
+    }
+}
+
+impl<'a, F: Field> Sub for Expr<'a, F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        match (other.lt.var, self.lt.var) {
+            (Variable::Zero, _) => self - other.lt.offset,
+            (_, Variable::Zero) => -other + self.lt.offset,
+            _ => self.binary_gate(&other, ConstraintSystem::sub_gate),
+        }
+    }
+}
+
+impl<'a, F: Field> Mul for Expr<'a, F> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        match (other.lt.var, self.lt.var) {
+            (Variable::Zero, _) => self * other.lt.offset,
+            (_, Variable::Zero) => other * self.lt.offset,
+            _ => self.binary_gate(&other, ConstraintSystem::mul_gate),
+        }
+    }
+}
+
+impl<'a, F: Field> Div for Expr<'a, F> {
+    type Output = Self;
+
+    /// Division by zero has no valid witness, see
+    /// [`ConstraintSystem::div_gate`].
+    fn div(self, other: Self) -> Self {
+        match (other.lt.var, other.lt.offset.inverse()) {
+            (Variable::Zero, Some(inverse)) => self * inverse,
+            _ => self.binary_gate(&other, ConstraintSystem::div_gate),
+        }
+    }
+}
+
+impl<'a, F: Field> Neg for Expr<'a, F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -F::one()
+    }
+}
+
+impl<'a, F: Field> Add<F> for Expr<'a, F> {
+    type Output = Self;
+
+    fn add(self, value: F) -> Self {
+        self.with_lt(self.lt.linear_transform(F::one(), value))
+    }
+}
+
+impl<'a, F: Field> Sub<F> for Expr<'a, F> {
+    type Output = Self;
+
+    fn sub(self, value: F) -> Self {
+        self + -value
+    }
+}
+
+impl<'a, F: Field> Mul<F> for Expr<'a, F> {
+    type Output = Self;
+
+    fn mul(self, value: F) -> Self {
+        let lt = if value.is_zero() {
+            LTVariable::zero()
+        } else {
+            self.lt.linear_transform(value, F::zero())
+        };
+
+        self.with_lt(lt)
+    }
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Runs `f` with a scope in which the circuit is written as [`Expr`]s.
+    pub fn exprs<R, P>(&mut self, f: P) -> R
+    where
+        P: for<'a> FnOnce(&'a ExprScope<'a, F>) -> R,
+    {
+        let scope = ExprScope { cs: RefCell::new(self) };
+        f(&scope)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::batch_test_field;
+    use super::*;
+
+    fn test_expr_arithmetic<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let (z, w, size) = cs.exprs(|s| {
+                    let x = s.witness(F::from(3u64));
+                    let y = s.witness(F::from(5u64));
+                    let two = F::from(2u64);
+
+                    // (x * y + y * 2 - 1) / x
+                    let z = (x * y + y * two - F::one()) / x;
+                    // operations with constants need no gate
+                    let w = -(x * s.constant(two) + s.constant(F::one())) / s.constant(two);
+                    let size = s.with(|cs| cs.composer.size());
+
+                    (z.to_variable(), w.lt(), size)
+                });
+                assert_eq!(size, 3);
+                assert_eq!(cs.composer.size(), 3);
+
+                vec![
+                    (z.into(), F::from(8u64)),
+                    (w, -F::from(7u64) / F::from(2u64)),
+                ]
+            },
+            &[],
+        );
+    }
+
+    fn test_expr_constraints<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let (eq, ne, zero, selected) = cs.exprs(|s| {
+                    let x = s.witness(F::from(4u64));
+                    let y = s.witness(F::from(2u64));
+
+                    (y.square()).enforce_eq(&x);
+                    (y * F::from(2u64)).enforce_eq(&x);
+                    x.set_public();
+
+                    let eq = (y + y).is_eq(&x);
+                    let ne = y.is_eq(&x);
+                    let zero = (x - y - y).is_zero();
+                    let selected = s.select(ne, x, y);
+
+                    (eq, ne, zero, selected.lt())
+                });
+
+                vec![
+                    (eq.into(), F::one()),
+                    (ne.into(), F::zero()),
+                    (zero.into(), F::one()),
+                    (selected, F::from(2u64)),
+                ]
+            },
+            &[F::from(4u64)],
+        );
+    }
+
+    fn test_expr_unsatisfied<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                cs.exprs(|s| {
+                    let x = s.witness(F::from(3u64));
+                    (x * x).enforce_eq(&s.constant(F::from(10u64)));
+                });
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    fn test_expr_is_zero_transformed<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                cs.exprs(|s| {
+                    let x = s.witness(F::from(3u64));
+                    let three = F::from(3u64);
+
+                    vec![
+                        ((x - three).is_zero().into(), F::one()),
+                        ((x - F::one()).is_zero().into(), F::zero()),
+                        ((x * F::from(2u64)).is_zero().into(), F::zero()),
+                        ((x * F::from(2u64) - F::from(6u64)).is_zero().into(), F::one()),
+                        ((-x + three).is_eq(&s.constant(F::zero())).into(), F::one()),
+                    ]
+                })
+            },
+            &[],
+        );
+    }
+
+    /// Sum of the inputs, as a gadget over expressions.
+    struct SumGadget;
+
+    impl<F: Field> Gadget<F> for SumGadget {
+        type NativeInput = [F];
+        type NativeOutput = F;
+        type Input = [LTVariable<F>];
+        type Output = LTVariable<F>;
+
+        fn native(&self, input: &[F]) -> F {
+            input.iter().sum()
+        }
+
+        fn synthesize(&self, cs: &mut ConstraintSystem<F>, input: &[LTVariable<F>]) -> LTVariable<F> {
+            input
+                .iter()
+                .fold(LTVariable::zero(), |acc, x| cs.add_gate(&acc, x).into())
+        }
+    }
+
+    fn test_expr_gadget<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let sum = cs.exprs(|s| {
+                    let x = s.witness(F::from(3u64));
+                    let y = s.witness(F::from(5u64));
+
+                    s.gadget(&SumGadget, &[x, y * F::from(2u64), x + F::one()]).lt()
+                });
+
+                vec![(sum, SumGadget.native(&[F::from(3u64), F::from(10u64), F::from(4u64)]))]
+            },
+            &[],
+        );
+    }
+
+    batch_test_field!(
+        Bn254,
+        [
+            test_expr_arithmetic,
+            test_expr_constraints,
+            test_expr_is_zero_transformed,
+            test_expr_gadget
+        ],
+        [test_expr_unsatisfied]
+    );
+}
@@ -0,0 +1,246 @@
+//! Allocation of structured values in circuit, and gadgets which are circuit
+//! components with native counterparts.
+
+use ark_ec::{models::TEModelParameters, twisted_edwards_extended::GroupAffine};
+use ark_ff::{Field, PrimeField};
+
+use super::*;
+use crate::error::Error;
+
+/// How a native value is allocated in circuit.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AllocMode {
+    /// Fixed by the circuit, which needs no gate where possible.
+    Constant,
+    /// Private witness of the prover.
+    Witness,
+    /// Witness which is set as public input.
+    Input,
+}
+
+/// Types in circuit allocated from a native value of type `V`, which are
+/// constrained to be well-formed, e.g. bits to be booleans.
+pub trait AllocVar<V: ?Sized, F: Field>: Sized {
+    /// Allocates `value` in `mode`, which fails if `value` doesn't have the
+    /// shape of `Self`.
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &V,
+        mode: AllocMode,
+    ) -> Result<Self, Error>;
+
+    ///
+    fn new_constant(cs: &mut ConstraintSystem<F>, value: &V) -> Result<Self, Error> {
+        Self::new_variable(cs, value, AllocMode::Constant)
+    }
+
+    ///
+    fn new_witness(cs: &mut ConstraintSystem<F>, value: &V) -> Result<Self, Error> {
+        Self::new_variable(cs, value, AllocMode::Witness)
+    }
+
+    ///
+    fn new_input(cs: &mut ConstraintSystem<F>, value: &V) -> Result<Self, Error> {
+        Self::new_variable(cs, value, AllocMode::Input)
+    }
+
+    /// Allocates `value` in `mode`, which is only needed with a witness or
+    /// for constants, so that setup needs no value. Without a witness, the
+    /// default value only gives the shape.
+    fn new_optional(
+        cs: &mut ConstraintSystem<F>,
+        value: Option<&V>,
+        mode: AllocMode,
+    ) -> Result<Self, Error>
+    where
+        V: Default + Sized,
+    {
+        match value {
+            Some(value) => Self::new_variable(cs, value, mode),
+            None if mode != AllocMode::Constant && !cs.has_witness() => {
+                Self::new_variable(cs, &V::default(), mode)
+            }
+            None => Err(Error::MissingWitness),
+        }
+    }
+}
+
+/// Component of a circuit, whose output in circuit matches the output of its
+/// native counterpart on the same inputs.
+pub trait Gadget<F: Field> {
+    /// Input of the native computation.
+    type NativeInput: ?Sized;
+    /// Output of the native computation.
+    type NativeOutput;
+    /// Input in circuit, usually allocated from a [`Gadget::NativeInput`].
+    type Input: ?Sized;
+    /// Output in circuit.
+    type Output;
+
+    /// Computes the output natively.
+    fn native(&self, input: &Self::NativeInput) -> Self::NativeOutput;
+
+    /// Computes the output in circuit.
+    fn synthesize(&self, cs: &mut ConstraintSystem<F>, input: &Self::Input) -> Self::Output;
+}
+
+impl<F: Field> AllocVar<F, F> for LTVariable<F> {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &F,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        let x = match mode {
+            AllocMode::Constant => LTVariable::constant(*value),
+            AllocMode::Witness => cs.assign_variable(*value).into(),
+            AllocMode::Input => {
+                let x = cs.assign_variable(*value).into();
+                cs.set_variable_public(&x);
+                x
+            }
+        };
+
+        Ok(x)
+    }
+}
+
+impl<F: Field> AllocVar<bool, F> for Boolean {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &bool,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        if mode == AllocMode::Constant {
+            return Ok(cs.constant_boolean(*value));
+        }
+
+        let x = cs.assign_variable(F::from(*value));
+        let bit = cs.boolean_gate(x);
+        if mode == AllocMode::Input {
+            cs.set_variable_public(&bit.into());
+        }
+
+        Ok(bit)
+    }
+}
+
+impl<F: PrimeField> AllocVar<u8, F> for Byte {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &u8,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        if mode == AllocMode::Constant {
+            return Ok(cs.constant_byte(*value));
+        }
+
+        let x = cs.assign_variable(F::from(*value));
+        if mode == AllocMode::Input {
+            cs.set_variable_public(&x.into());
+        }
+
+        Ok(cs.byte_gate(x))
+    }
+}
+
+impl<F: PrimeField, const BITS: usize> AllocVar<u64, F> for UInt<BITS> {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &u64,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        if mode == AllocMode::Constant {
+            return Ok(Self::constant(cs, *value));
+        }
+
+        let x = LTVariable::new_variable(cs, &F::from(*value), mode)?;
+        Ok(Self::from_variable(cs, &x))
+    }
+}
+
+impl<P: TEModelParameters> AllocVar<GroupAffine<P>, P::BaseField> for AffinePoint<P> {
+    fn new_variable(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        value: &GroupAffine<P>,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        if mode == AllocMode::Constant {
+            return Ok(Self::constant(value));
+        }
+
+        let point = Self::new(cs, value);
+        if mode == AllocMode::Input {
+            cs.set_variable_public(&point.x());
+            cs.set_variable_public(&point.y());
+        }
+
+        Ok(point)
+    }
+}
+
+impl<V, F: Field, T: AllocVar<V, F>> AllocVar<[V], F> for Vec<T> {
+    fn new_variable(
+        cs: &mut ConstraintSystem<F>,
+        value: &[V],
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        value
+            .iter()
+            .map(|v| T::new_variable(cs, v, mode))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::batch_test_field;
+    use super::*;
+
+    fn test_alloc_modes<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| {
+                let x = LTVariable::new_constant(cs, &F::from(3u64)).unwrap();
+                let bits = Vec::<Boolean>::new_constant(cs, &[true, false, true]).unwrap();
+                assert_eq!(cs.composer.size(), 1);
+
+                let y = LTVariable::new_witness(cs, &F::from(5u64)).unwrap();
+                let bits_var = Vec::<Boolean>::new_witness(cs, &[false, true]).unwrap();
+                let z = UInt8::new_input(cs, &200).unwrap();
+                assert_eq!(bits_var.len(), 2);
+                // 2 booleans, then 1 public input with the range check of 8 bits
+                assert_eq!(cs.composer.size(), 20);
+
+                let bit = Boolean::new_input(cs, &true).unwrap();
+                let sum = cs.add_gate(&x, &y);
+
+                vec![
+                    (x, F::from(3u64)),
+                    (bits[0].into(), F::one()),
+                    (bits[1].into(), F::zero()),
+                    (sum.into(), F::from(8u64)),
+                    (z.to_variable(cs).into(), F::from(200u64)),
+                    (bit.into(), F::one()),
+                ]
+            },
+            &[F::from(200u64), F::one()],
+        );
+    }
+
+    fn test_alloc_optional<F: PrimeField>() {
+        let mut cs = ConstraintSystem::<F>::new(true, Default::default());
+        assert!(UInt8::new_optional(&mut cs, None, AllocMode::Input).is_ok());
+        assert!(Boolean::new_optional(&mut cs, None, AllocMode::Constant).is_err());
+
+        let mut cs = ConstraintSystem::<F>::new(false, Default::default());
+        assert!(Boolean::new_optional(&mut cs, Some(&true), AllocMode::Witness).is_ok());
+        assert!(Boolean::new_optional(&mut cs, None, AllocMode::Witness).is_err());
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_alloc_modes, test_alloc_optional],
+        []
+    );
+}
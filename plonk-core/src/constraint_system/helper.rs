@@ -7,8 +7,91 @@
 use ark_ff::Field;
 use itertools::izip;
 
+use crate::{lookup::LookupTable, plonkup::Circuit};
+
 use super::*;
 
+/// Compares the gate structure of `actual` against `expected` gate by gate,
+/// i.e. the selectors, the lookup rows, the wire variables and the public
+/// input positions, and panics at the first diverging gate.
+///
+/// Both must come from the same circuit, e.g. synthesized without and with a
+/// witness, where a divergence means that the control flow of the circuit
+/// depends on the witness.
+pub fn check_structure<F: Field>(expected: &SetupComposer<F>, actual: &SetupComposer<F>) {
+    let n = expected.n.min(actual.n);
+    let expected_wires = expected.perm.gate_wires(n);
+    let actual_wires = actual.perm.gate_wires(n);
+
+    let divergence = (0..n)
+        .find_map(|i| {
+            let selectors = [
+                ("q_m", expected.q_m[i], actual.q_m[i]),
+                ("q_l", expected.q_l[i], actual.q_l[i]),
+                ("q_r", expected.q_r[i], actual.q_r[i]),
+                ("q_o", expected.q_o[i], actual.q_o[i]),
+                ("q_c", expected.q_c[i], actual.q_c[i]),
+                ("q_lookup", expected.q_lookup[i], actual.q_lookup[i]),
+            ];
+            let reason = if let Some((name, _, _)) = selectors.iter().find(|(_, x, y)| x != y) {
+                format!("selector {} differs", name)
+            } else if expected_wires[i] != actual_wires[i] {
+                format!("wires {:?} differ from {:?}", actual_wires[i], expected_wires[i])
+            } else if expected.pp.contains(i) != actual.pp.contains(i) {
+                "public input position differs".to_string()
+            } else {
+                return None;
+            };
+
+            Some((i, reason))
+        })
+        .or_else(|| {
+            (expected.n != actual.n).then(|| {
+                (n, format!("circuit size {} differs from {}", actual.n, expected.n))
+            })
+        });
+
+    if let Some((i, reason)) = divergence {
+        #[cfg(feature = "trace")]
+        {
+            for (composer, name) in [(expected, "expected"), (actual, "actual")] {
+                if let Some(backtrace) = composer.backtrace.get(i) {
+                    let mut backtrace = backtrace.clone();
+                    backtrace.resolve();
+                    println!("{} gate at {}:\n{:?}", name, i, backtrace);
+                }
+            }
+        }
+        let path = actual.namespaces.gate_path(i).unwrap_or_default();
+        panic!("gate at {:?} in {} diverges: {}", i, path, reason);
+    }
+}
+
+/// Synthesizes `shape` as in setup and `circuit` with its witness, checks
+/// that both have the same gate structure and that the gates hold for the
+/// witness and `pub_inputs`.
+pub fn check_circuit<F, C>(shape: C, circuit: C, pub_inputs: &[F], lookup_table: LookupTable<F>)
+where
+    F: Field,
+    C: Circuit<F>,
+{
+    let mut setup = ConstraintSystem::new(true, lookup_table.clone());
+    shape
+        .synthesize(&mut setup)
+        .unwrap_or_else(|e| panic!("setup synthesis failed: {e}"));
+    let setup: SetupComposer<F> = setup.composer.into();
+
+    let mut cs = ConstraintSystem::new_with_witness(lookup_table);
+    circuit
+        .synthesize(&mut cs)
+        .unwrap_or_else(|e| panic!("proving synthesis failed: {e}"));
+    let actual = cs.composer.setup.expect("gate structure is recorded");
+    let proving = cs.composer.proving.expect("witness is recorded");
+
+    check_structure(&setup, &actual);
+    check_arith_gate(&actual, &proving, pub_inputs);
+}
+
 ///
 pub fn check_arith_gate<F: Field>(
     setup: &SetupComposer<F>,
@@ -59,7 +142,8 @@ pub fn check_arith_gate<F: Field>(
                 backtrace.resolve();
                 println!("{:?}", backtrace);
             }
-            panic!("arithmetic gate at {:?} is not satisfied", i);
+            let path = setup.namespaces.gate_path(i).unwrap_or_default();
+            panic!("arithmetic gate at {:?} in {} is not satisfied", i, path);
         }
     }
 }
@@ -71,14 +155,31 @@ where
     I: IntoIterator<Item = (LTVariable<F>, F)>,
     P: Fn(&mut ConstraintSystem<F>) -> I,
 {
-    let mut setup = ConstraintSystem::new(true, Default::default());
-    let mut proving = ConstraintSystem::new(false, Default::default());
+    test_gate_constraints_with_table(process, pub_inputs, Default::default())
+}
 
+/// Same as [`test_gate_constraints`], for gates that look up values in
+/// `lookup_table`.
+pub fn test_gate_constraints_with_table<F, I, P>(
+    process: P,
+    pub_inputs: &[F],
+    lookup_table: LookupTable<F>,
+)
+where
+    F: Field,
+    I: IntoIterator<Item = (LTVariable<F>, F)>,
+    P: Fn(&mut ConstraintSystem<F>) -> I,
+{
+    // setup sees no witness, the gates must not depend on it
+    let mut setup = ConstraintSystem::new(true, lookup_table.clone());
     process(&mut setup);
     let setup: SetupComposer<F> = setup.composer.into();
 
-    let var_map = process(&mut proving);
-    let proving: ProvingComposer<F> = proving.composer.into();
+    let mut cs = ConstraintSystem::new_with_witness(lookup_table);
+    let var_map = process(&mut cs);
+    let actual = cs.composer.setup.expect("gate structure is recorded");
+    let proving = cs.composer.proving.expect("witness is recorded");
+    check_structure(&setup, &actual);
     for (lt_var, expect) in var_map {
         let actual = proving.var_map.value_of_lt_var(&lt_var);
         if actual != expect {
@@ -95,8 +196,99 @@ where
     }
 
     check_arith_gate(
-        &setup,
+        &actual,
         &proving,
         pub_inputs,
     )
 }
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::{batch_test_field, error::Error};
+    use super::*;
+
+    /// Computes `a * b` and makes `a` public if `branch` is set, or zero
+    /// otherwise by a selection.
+    #[derive(Debug, Default)]
+    struct SelectCircuit {
+        a: u64,
+        b: u64,
+        branch: bool,
+    }
+
+    impl<F: Field> Circuit<F> for SelectCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            cs.mul_gate(&a.into(), &b.into());
+
+            let branch = cs.assign_variable(self.branch.into());
+            let branch = cs.boolean_gate(branch);
+            let x = cs.conditional_select_zero(branch, &a.into());
+            cs.set_variable_public(&x.into());
+
+            Ok(())
+        }
+    }
+
+    /// Same as [`SelectCircuit`], but branches on the witness.
+    #[derive(Debug, Default)]
+    struct BranchCircuit {
+        a: u64,
+        b: u64,
+        branch: bool,
+    }
+
+    impl<F: Field> Circuit<F> for BranchCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            cs.mul_gate(&a.into(), &b.into());
+
+            if self.branch {
+                cs.set_variable_public(&a.into());
+            } else {
+                cs.set_variable_public(&LTVariable::zero());
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Checks `a` against a constant which is taken from the witness.
+    #[derive(Debug, Default)]
+    struct ConstantCircuit {
+        a: u64,
+    }
+
+    impl<F: Field> Circuit<F> for ConstantCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            cs.equal_constrain(&a.into(), &LTVariable::constant(self.a.into()));
+
+            Ok(())
+        }
+    }
+
+    fn test_consistent_circuit<F: Field>() {
+        let circuit = SelectCircuit { a: 3, b: 5, branch: true };
+        check_circuit(Default::default(), circuit, &[F::from(3u64)], Default::default());
+    }
+
+    fn test_witness_dependent_circuit<F: Field>() {
+        let circuit = BranchCircuit { a: 3, b: 5, branch: true };
+        check_circuit(Default::default(), circuit, &[F::from(3u64)], Default::default());
+    }
+
+    fn test_witness_dependent_constant<F: Field>() {
+        check_circuit(Default::default(), ConstantCircuit { a: 3 }, &[] as &[F], Default::default());
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_consistent_circuit],
+        [test_witness_dependent_circuit, test_witness_dependent_constant]
+    );
+}
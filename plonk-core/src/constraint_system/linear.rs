@@ -0,0 +1,342 @@
+//! Linear combinations of any number of variables, which are compiled into
+//! the fewest 3-wire gates when materialized or enforced.
+
+use core::{
+    iter::Sum,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+use ark_ff::Field;
+use indexmap::IndexMap;
+
+use super::*;
+
+/// `sum(coeff_i * var_i) + constant`, where each variable occurs once.
+#[derive(derivative::Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), Default(bound = ""), PartialEq(bound = ""), Eq(bound = ""))]
+pub struct LinearCombination<F: Field> {
+    terms: IndexMap<Variable, F>,
+    constant: F,
+}
+
+impl<F: Field> LinearCombination<F> {
+    ///
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    ///
+    pub fn constant(value: F) -> Self {
+        Self {
+            terms: IndexMap::new(),
+            constant: value,
+        }
+    }
+
+    /// Returns the variables with their coefficients, in the order they are
+    /// added first.
+    pub fn terms(&self) -> impl Iterator<Item = (Variable, F)> + '_ {
+        self.terms.iter().map(|(&var, &coeff)| (var, coeff))
+    }
+
+    ///
+    pub fn constant_term(&self) -> F {
+        self.constant
+    }
+
+    /// Adds `coeff * var`, merging it into the term of `var` if any.
+    pub fn add_term(&mut self, var: Variable, coeff: F) {
+        if var == Variable::Zero || coeff.is_zero() {
+            return;
+        }
+
+        let sum = *self.terms.entry(var).or_insert_with(F::zero) + coeff;
+        if sum.is_zero() {
+            self.terms.shift_remove(&var);
+        } else {
+            self.terms.insert(var, sum);
+        }
+    }
+}
+
+impl<F: Field> From<Variable> for LinearCombination<F> {
+    fn from(var: Variable) -> Self {
+        LTVariable::from(var).into()
+    }
+}
+
+impl<F: Field> From<LTVariable<F>> for LinearCombination<F> {
+    fn from(x: LTVariable<F>) -> Self {
+        let mut lc = Self::constant(x.offset);
+        lc.add_term(x.var, x.coeff);
+        lc
+    }
+}
+
+impl<F: Field> From<&LTVariable<F>> for LinearCombination<F> {
+    fn from(x: &LTVariable<F>) -> Self {
+        (*x).into()
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> AddAssign<T> for LinearCombination<F> {
+    fn add_assign(&mut self, other: T) {
+        let other = other.into();
+        for (var, coeff) in other.terms {
+            self.add_term(var, coeff);
+        }
+        self.constant += other.constant;
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> SubAssign<T> for LinearCombination<F> {
+    fn sub_assign(&mut self, other: T) {
+        *self += -other.into();
+    }
+}
+
+impl<F: Field> MulAssign<F> for LinearCombination<F> {
+    fn mul_assign(&mut self, scalar: F) {
+        if scalar.is_zero() {
+            *self = Self::zero();
+            return;
+        }
+        self.terms.values_mut().for_each(|coeff| *coeff *= scalar);
+        self.constant *= scalar;
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> Add<T> for LinearCombination<F> {
+    type Output = Self;
+
+    fn add(mut self, other: T) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> Sub<T> for LinearCombination<F> {
+    type Output = Self;
+
+    fn sub(mut self, other: T) -> Self {
+        self -= other;
+        self
+    }
+}
+
+impl<F: Field> Mul<F> for LinearCombination<F> {
+    type Output = Self;
+
+    fn mul(mut self, scalar: F) -> Self {
+        self *= scalar;
+        self
+    }
+}
+
+impl<F: Field> Neg for LinearCombination<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -F::one()
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> Add<T> for LTVariable<F> {
+    type Output = LinearCombination<F>;
+
+    fn add(self, other: T) -> LinearCombination<F> {
+        LinearCombination::from(self) + other
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> Sub<T> for LTVariable<F> {
+    type Output = LinearCombination<F>;
+
+    fn sub(self, other: T) -> LinearCombination<F> {
+        LinearCombination::from(self) - other
+    }
+}
+
+impl<F: Field, T: Into<LinearCombination<F>>> Sum<T> for LinearCombination<F> {
+    fn sum<I: Iterator<Item = T>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Returns the terms of `lc` as transforms without offset, with constant
+    /// variables folded into the constant.
+    fn lc_terms(&self, lc: &LinearCombination<F>) -> (Vec<LTVariable<F>>, F) {
+        let mut constant = lc.constant;
+        let mut terms = Vec::with_capacity(lc.terms.len());
+        for (var, coeff) in lc.terms() {
+            match self.constant_value(&var.into()) {
+                Some(value) => constant += coeff * value,
+                None => terms.push(var.linear_transform(coeff, F::zero())),
+            }
+        }
+
+        (terms, constant)
+    }
+
+    /// Sums up `terms` into a single variable by gates of two terms each,
+    /// until `len` terms are left.
+    fn reduce_lc_terms(&mut self, mut terms: Vec<LTVariable<F>>, len: usize) -> Vec<LTVariable<F>> {
+        while terms.len() > len {
+            let y = terms.pop().expect("more terms than len");
+            let x = terms.pop().expect("more terms than len");
+            terms.push(self.add_gate(&x, &y).into());
+        }
+
+        terms
+    }
+
+    /// Returns `lc` as a single transformed variable, which takes one gate
+    /// less than the number of its non-constant terms.
+    pub fn materialize(&mut self, lc: &LinearCombination<F>) -> LTVariable<F> {
+        let (terms, constant) = self.lc_terms(lc);
+        let terms = self.reduce_lc_terms(terms, 1);
+
+        match terms.first() {
+            Some(x) => x.linear_transform(F::one(), constant),
+            None => LTVariable::constant(constant),
+        }
+    }
+
+    /// Constrains `lc` to be zero. The last gate takes up to three terms
+    /// with the constant, so that up to three terms need a single gate.
+    pub fn enforce_lc(&mut self, lc: &LinearCombination<F>) {
+        let (terms, constant) = self.lc_terms(lc);
+        if terms.is_empty() && constant.is_zero() {
+            return;
+        }
+        let mut terms = self.reduce_lc_terms(terms, 3);
+        terms.resize(3, LTVariable::zero());
+
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_right(F::one())
+            .with_out(F::one())
+            .with_constant(constant)
+            .by_left_lt(&terms[0])
+            .by_right_lt(&terms[1])
+            .by_out_lt(&terms[2]);
+
+        self.arith_constrain(terms[0].var, terms[1].var, terms[2].var, sels, None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_std::test_rng;
+    use ark_bn254::Bn254;
+
+    use crate::batch_test_field;
+    use super::*;
+
+    fn test_lc_operators<F: Field>() {
+        let x = Variable::Var(0);
+        let y = Variable::Var(1);
+        let two = F::from(2u64);
+        let three = F::from(3u64);
+
+        let lc = LinearCombination::from(x) * two + y.linear_transform(three, F::one()) - x;
+        assert_eq!(lc.terms().collect_vec(), [(x, F::one()), (y, three)]);
+        assert_eq!(lc.constant_term(), F::one());
+
+        let lc = lc - LinearCombination::from(x) + LTVariable::constant(-F::one());
+        assert_eq!(lc, LinearCombination::from(y) * three);
+        assert_eq!(-lc.clone() + lc, LinearCombination::zero());
+
+        let sum: LinearCombination<F> = [x, y, x].into_iter().sum();
+        assert_eq!(sum.terms().collect_vec(), [(x, two), (y, F::one())]);
+    }
+
+    fn test_materialize<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let rng = &mut test_rng();
+                let values = (0..5).map(|_| F::rand(rng)).collect_vec();
+                let coeffs = (0..5).map(|_| F::rand(rng)).collect_vec();
+                let constant = F::rand(rng);
+                let vars = values.iter().map(|&value| cs.assign_variable(value)).collect_vec();
+
+                let lc = vars
+                    .iter()
+                    .zip(coeffs.iter())
+                    .map(|(var, &coeff)| var.linear_transform(coeff, F::zero()))
+                    .sum::<LinearCombination<F>>()
+                    + LTVariable::constant(constant);
+                let x = cs.materialize(&lc);
+                assert_eq!(cs.composer.size(), 4);
+
+                // a single term or a constant needs no gate
+                let y = cs.materialize(&(LinearCombination::from(vars[0]) * coeffs[0]));
+                let z = cs.materialize(&LinearCombination::constant(constant));
+                assert_eq!(cs.composer.size(), 4);
+
+                let expected = values
+                    .iter()
+                    .zip(coeffs.iter())
+                    .map(|(&value, &coeff)| value * coeff)
+                    .sum::<F>()
+                    + constant;
+
+                vec![(x, expected), (y, values[0] * coeffs[0]), (z, constant)]
+            },
+            &[],
+        );
+    }
+
+    fn test_enforce_lc<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let rng = &mut test_rng();
+                let a = F::rand(rng);
+                let b = F::rand(rng);
+                let x = cs.assign_variable(a);
+                let y = cs.assign_variable(b);
+                let z = cs.assign_variable(a + b);
+                let c = cs.constant_var(F::from(5u64));
+
+                // x + y - z + c - 5 = 0 in a single gate
+                let lc = LinearCombination::from(x) + y - z + c - LTVariable::constant(F::from(5u64));
+                cs.enforce_lc(&lc);
+                assert_eq!(cs.composer.size(), 2);
+
+                // x + y + 2z + w - 3 * (a + b) - d = 0 in two gates
+                let d = F::rand(rng);
+                let w = cs.assign_variable(d);
+                let lc = LinearCombination::from(x)
+                    + y
+                    + LinearCombination::from(z) * F::from(2u64)
+                    + w
+                    - LTVariable::constant(F::from(3u64) * (a + b) + d);
+                cs.enforce_lc(&lc);
+                assert_eq!(cs.composer.size(), 4);
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    fn test_enforce_unsatisfied_lc<F: Field>() {
+        test_gate_constraints(
+            |cs| {
+                let x = cs.assign_variable(F::one());
+                let y = cs.assign_variable(F::one());
+                let lc = LinearCombination::from(x) + y + x;
+                cs.enforce_lc(&(lc - LTVariable::constant(F::from(2u64))));
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_lc_operators, test_materialize, test_enforce_lc],
+        [test_enforce_unsatisfied_lc]
+    );
+}
@@ -0,0 +1,454 @@
+//! A mock prover which checks a circuit and its witness against every
+//! constraint the prover relies on, without committing to any polynomial.
+//!
+//! Unlike [`check_circuit`], the checks do not stop at the first failure, all
+//! of them are collected as [`Failure`]s, so that a broken circuit can be
+//! inspected as a whole.
+
+use core::fmt;
+use ark_ff::Field;
+
+use crate::{
+    error::Error,
+    lookup::LookupTable,
+    permutation::WireData,
+    plonkup::Circuit,
+};
+
+use super::*;
+
+/// Wire column of a gate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Column {
+    ///
+    Left,
+    ///
+    Right,
+    ///
+    Output,
+}
+
+/// A constraint which is not satisfied by the witness.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Failure<F: Field> {
+    /// The numbers of gates in setup and proving differ.
+    CircuitSize {
+        ///
+        setup: usize,
+        ///
+        proving: usize,
+    },
+    /// The gate equation evaluates to `value` instead of zero.
+    Gate {
+        ///
+        gate: usize,
+        ///
+        value: F,
+    },
+    /// The output wire of a lookup gate holds `value`, which is not in the
+    /// lookup table.
+    Lookup {
+        ///
+        gate: usize,
+        ///
+        value: F,
+    },
+    /// The number of public inputs differs from the public positions in
+    /// setup.
+    PublicInputCount {
+        ///
+        expected: usize,
+        ///
+        actual: usize,
+    },
+    /// The gate takes a public input in only one of setup and proving.
+    PublicInputPosition {
+        ///
+        gate: usize,
+    },
+    /// The public input of the gate differs from the one given.
+    PublicInputValue {
+        ///
+        gate: usize,
+        ///
+        expected: F,
+        ///
+        actual: F,
+    },
+    /// A wire mapped to `var` holds a different value than the first wire of
+    /// `var`.
+    Permutation {
+        ///
+        var: Variable,
+        ///
+        gate: usize,
+        ///
+        column: Column,
+        ///
+        expected: F,
+        ///
+        actual: F,
+    },
+}
+
+impl<F: Field> fmt::Display for Failure<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CircuitSize { setup, proving } => {
+                write!(f, "circuit size {} in proving differs from {} in setup", proving, setup)
+            }
+            Self::Gate { gate, value } => {
+                write!(f, "arithmetic gate at {} evaluates to {} instead of zero", gate, value)
+            }
+            Self::Lookup { gate, value } => {
+                write!(f, "lookup gate at {} outputs {} which is not in table", gate, value)
+            }
+            Self::PublicInputCount { expected, actual } => {
+                write!(f, "{} public inputs are given but {} are expected", actual, expected)
+            }
+            Self::PublicInputPosition { gate } => {
+                write!(f, "public input position at {} differs between setup and proving", gate)
+            }
+            Self::PublicInputValue { gate, expected, actual } => {
+                write!(f, "public input at {} is {} instead of {}", gate, actual, expected)
+            }
+            Self::Permutation { var, gate, column, expected, actual } => {
+                write!(
+                    f,
+                    "{:?} wire at {} holds {} instead of {} of {:?}",
+                    column, gate, actual, expected, var,
+                )
+            }
+        }
+    }
+}
+
+/// Synthesizes a circuit as both setup and proving do, and checks the
+/// arithmetic gates, the lookup rows, the public inputs and the copy
+/// constraints of the permutation against the witness.
+#[derive(derivative::Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct MockProver<F: Field> {
+    setup: SetupComposer<F>,
+    proving: ProvingComposer<F>,
+    lookup_table: LookupTable<F>,
+    pub_inputs: Vec<F>,
+}
+
+impl<F: Field> MockProver<F> {
+    /// Synthesizes `shape` for the gate structure and `circuit` for the
+    /// witness, both optimized if [`Circuit::OPTIMIZE`]. Lookups are not
+    /// checked during synthesis but by [`MockProver::verify`].
+    pub fn run<C, I>(
+        shape: C,
+        circuit: C,
+        lookup_table: I,
+        pub_inputs: &[F],
+    ) -> Result<Self, Error>
+    where
+        C: Circuit<F>,
+        I: Into<LookupTable<F>>,
+    {
+        let lookup_table = lookup_table.into();
+
+        let mut cs = ConstraintSystem::new(true, lookup_table.clone());
+        shape.synthesize(&mut cs)?;
+        if C::OPTIMIZE {
+            cs.optimize();
+        }
+        let setup = cs.composer.into();
+
+        // the witness is rewired along with the gate structure
+        let mut cs = if C::OPTIMIZE {
+            ConstraintSystem::new_with_witness(lookup_table)
+        } else {
+            ConstraintSystem::new(false, lookup_table)
+        };
+        cs.eager_lookup = false;
+        circuit.synthesize(&mut cs)?;
+        if C::OPTIMIZE {
+            cs.optimize();
+        }
+        let proving = cs.composer.into();
+
+        Ok(Self {
+            setup,
+            proving,
+            lookup_table: cs.lookup_table,
+            pub_inputs: pub_inputs.to_vec(),
+        })
+    }
+
+    /// Returns all failures of the witness, in the order of circuit size,
+    /// gates, lookups, public inputs and permutation.
+    pub fn verify(&self) -> Result<(), Vec<Failure<F>>> {
+        let mut failures = Vec::new();
+        let n = self.setup.n.min(self.proving.n);
+        if self.setup.n != self.proving.n {
+            failures.push(Failure::CircuitSize { setup: self.setup.n, proving: self.proving.n });
+        }
+
+        let pi = self.proving.pi.as_evals(self.proving.n);
+        for (i, &pi) in pi.iter().enumerate().take(n) {
+            let a = self.wire_value(i, Column::Left);
+            let b = self.wire_value(i, Column::Right);
+            let c = self.wire_value(i, Column::Output);
+            let value = (self.setup.q_m[i] * a * b)
+                + (self.setup.q_l[i] * a)
+                + (self.setup.q_r[i] * b)
+                + (self.setup.q_o[i] * c)
+                + pi
+                + self.setup.q_c[i];
+            if !value.is_zero() {
+                failures.push(Failure::Gate { gate: i, value });
+            }
+        }
+
+        for i in 0..n {
+            if !self.setup.q_lookup[i].is_zero() {
+                let value = self.wire_value(i, Column::Output);
+                if !self.lookup_table.0.contains(&value) {
+                    failures.push(Failure::Lookup { gate: i, value });
+                }
+            }
+        }
+
+        self.verify_public_inputs(&mut failures);
+        self.verify_permutation(n, &mut failures);
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    fn verify_public_inputs(&self, failures: &mut Vec<Failure<F>>) {
+        if self.setup.pp.size() != self.pub_inputs.len() {
+            failures.push(Failure::PublicInputCount {
+                expected: self.setup.pp.size(),
+                actual: self.pub_inputs.len(),
+            });
+        }
+
+        let positions = self.setup.pp
+            .get_pos()
+            .chain(self.proving.pi.get_pos())
+            .copied()
+            .sorted()
+            .dedup();
+        for gate in positions {
+            if self.setup.pp.contains(gate) != self.proving.pi.get(gate).is_some() {
+                failures.push(Failure::PublicInputPosition { gate });
+            }
+        }
+
+        // the verifier places the given inputs at the positions in setup
+        for (&gate, &expected) in self.setup.pp.get_pos().zip(self.pub_inputs.iter()) {
+            let actual = self.proving.pi.get(gate).copied().unwrap_or_else(F::zero);
+            if actual != expected {
+                failures.push(Failure::PublicInputValue { gate, expected, actual });
+            }
+        }
+    }
+
+    fn verify_permutation(&self, n: usize, failures: &mut Vec<Failure<F>>) {
+        for (var, wire_datas) in self.setup.perm.variable_wires() {
+            let mut wires = wire_datas
+                .iter()
+                .map(|wire_data| match *wire_data {
+                    WireData::Left(i) => (i, Column::Left),
+                    WireData::Right(i) => (i, Column::Right),
+                    WireData::Output(i) => (i, Column::Output),
+                })
+                .filter(|(i, _)| *i < n);
+
+            if let Some((i, column)) = wires.next() {
+                let expected = self.wire_value(i, column);
+                for (gate, column) in wires {
+                    let actual = self.wire_value(gate, column);
+                    if actual != expected {
+                        failures.push(Failure::Permutation { var, gate, column, expected, actual });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the witness value on the wire of the gate at `i`.
+    fn wire_value(&self, i: usize, column: Column) -> F {
+        let var = match column {
+            Column::Left => self.proving.w_l[i],
+            Column::Right => self.proving.w_r[i],
+            Column::Output => self.proving.w_o[i],
+        };
+
+        self.proving.var_map.value_of_var(var)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::batch_test_field;
+    use super::*;
+
+    /// Computes `c = a + b` with `c` in table and `a * b` public.
+    #[derive(Debug, Default)]
+    struct LookupCircuit {
+        a: u64,
+        b: u64,
+    }
+
+    impl<F: Field> Circuit<F> for LookupCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            let c = cs.add_gate(&a.into(), &b.into());
+            cs.lookup_constrain(&c.into());
+            let d = cs.mul_gate(&a.into(), &b.into());
+            cs.set_variable_public(&d.into());
+
+            Ok(())
+        }
+    }
+
+    /// Constrains `a * b = c` for any witness `c`.
+    #[derive(Debug, Default)]
+    struct MulCircuit {
+        a: u64,
+        b: u64,
+        c: u64,
+    }
+
+    impl<F: Field> Circuit<F> for MulCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            let c = cs.assign_variable(self.c.into());
+            let sels = Selectors::new().with_mul(F::one()).with_out(-F::one());
+            cs.arith_constrain(a, b, c, sels, None);
+
+            Ok(())
+        }
+    }
+
+    /// Squares `a` in setup, but multiplies `a` by `b` if `distinct`.
+    #[derive(Debug, Default)]
+    struct CopyCircuit {
+        a: u64,
+        b: u64,
+        distinct: bool,
+    }
+
+    impl<F: Field> Circuit<F> for CopyCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            let y = if self.distinct { b } else { a };
+            cs.mul_gate(&a.into(), &y.into());
+
+            Ok(())
+        }
+    }
+
+    /// Constrains the inverse of each input, whose number is the shape.
+    #[derive(Debug)]
+    struct InverseCircuit {
+        inputs: Vec<Option<u64>>,
+    }
+
+    impl<F: Field> Circuit<F> for InverseCircuit {
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            for input in self.inputs {
+                let a = cs.assign_witness(|| input.map(F::from))?;
+                let a_inv = cs.div_gate(&LTVariable::constant(F::one()), &a.into());
+                let is_zero = cs.is_zero_with_output(&a_inv.into());
+                cs.equal_constrain(&is_zero.into(), &LTVariable::zero());
+            }
+
+            Ok(())
+        }
+    }
+
+    fn table<F: Field>() -> LookupTable<F> {
+        (0..16u64).map(F::from).collect::<Vec<_>>().into()
+    }
+
+    fn test_satisfied_circuit<F: Field>() {
+        let circuit = LookupCircuit { a: 3, b: 5 };
+        let prover = MockProver::run(Default::default(), circuit, table(), &[F::from(15u64)]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    fn test_lookup_failure<F: Field>() {
+        let circuit = LookupCircuit { a: 9, b: 7 };
+        let prover = MockProver::run(Default::default(), circuit, table(), &[F::from(63u64)]).unwrap();
+        assert_eq!(prover.verify(), Err(vec![Failure::Lookup { gate: 1, value: F::from(16u64) }]));
+    }
+
+    fn test_public_input_failure<F: Field>() {
+        let circuit = LookupCircuit { a: 3, b: 5 };
+        let prover = MockProver::run(Default::default(), circuit, table(), &[F::from(16u64)]).unwrap();
+        assert_eq!(
+            prover.verify(),
+            Err(vec![Failure::PublicInputValue {
+                gate: 3,
+                expected: F::from(16u64),
+                actual: F::from(15u64),
+            }]),
+        );
+
+        let prover = MockProver::<F>::run(Default::default(), LookupCircuit { a: 3, b: 5 }, table(), &[]).unwrap();
+        assert_eq!(
+            prover.verify(),
+            Err(vec![Failure::PublicInputCount { expected: 1, actual: 0 }]),
+        );
+    }
+
+    fn test_gate_failure<F: Field>() {
+        let circuit = MulCircuit { a: 3, b: 5, c: 16 };
+        let prover = MockProver::run(Default::default(), circuit, Vec::new(), &[]).unwrap();
+        assert_eq!(prover.verify(), Err(vec![Failure::Gate { gate: 0, value: -F::one() }]));
+    }
+
+    fn test_permutation_failure<F: Field>() {
+        let circuit = CopyCircuit { a: 3, b: 5, distinct: true };
+        let prover = MockProver::<F>::run(Default::default(), circuit, Vec::new(), &[]).unwrap();
+        assert_eq!(
+            prover.verify(),
+            Err(vec![Failure::Permutation {
+                var: Variable::Var(0),
+                gate: 0,
+                column: Column::Right,
+                expected: F::from(3u64),
+                actual: F::from(5u64),
+            }]),
+        );
+    }
+
+    fn test_lazy_witness<F: Field>() {
+        let shape = || InverseCircuit { inputs: vec![None; 2] };
+        let circuit = InverseCircuit { inputs: vec![Some(3), Some(4)] };
+        let prover = MockProver::<F>::run(shape(), circuit, Vec::new(), &[]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = InverseCircuit { inputs: vec![Some(3), None] };
+        let result = MockProver::<F>::run(shape(), circuit, Vec::new(), &[]);
+        assert!(matches!(result, Err(Error::MissingWitness)));
+    }
+
+    batch_test_field!(
+        Bn254,
+        [
+            test_satisfied_circuit,
+            test_lazy_witness,
+            test_lookup_failure,
+            test_public_input_failure,
+            test_gate_failure,
+            test_permutation_failure
+        ],
+        []
+    );
+}
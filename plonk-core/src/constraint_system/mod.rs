@@ -10,21 +10,44 @@
 
 mod arithmetic;
 mod boolean;
+mod byte;
+mod comparison;
 mod composer;
+mod constant;
+mod ecc;
+mod expr;
+mod gadget;
+mod nonnative;
 mod variable;
 mod pi;
 mod helper;
+mod linear;
+mod mock;
+mod namespace;
+mod optimizer;
+mod uint;
 
 pub use boolean::*;
+pub use byte::*;
 pub use composer::*;
+pub use constant::*;
+pub use ecc::*;
+pub use expr::*;
+pub use gadget::*;
+pub use nonnative::*;
 pub use variable::*;
 pub use helper::*;
+pub use linear::*;
+pub use mock::*;
+pub use namespace::*;
+pub use optimizer::*;
 pub use pi::*;
+pub use uint::*;
 
 use ark_ff::Field;
 use itertools::Itertools;
 
-use crate::lookup::LookupTable;
+use crate::{error::Error, lookup::LookupTable};
 
 /// The ConstraintSystem is the circuit-builder tool that the `plonk` repository
 /// provides to create, stored and transformed circuit descriptions
@@ -58,18 +81,28 @@ pub struct ConstraintSystem<F: Field> {
     pub composer: Composer<F>,
     ///
     pub lookup_table: LookupTable<F>,
+    /// Whether lookups are checked against the table during synthesis.
+    eager_lookup: bool,
+    /// Constant variables shared by gadgets.
+    constants: ConstantCache<F>,
 }
 
 impl<F: Field> ConstraintSystem<F> {
-    ///
+    /// Creates a constraint system which records the gate structure if
+    /// `setup`, or the wires and values otherwise.
     pub fn new(setup: bool, lookup_table: LookupTable<F>) -> Self {
         let composer = if setup {
-            Composer::Setup(SetupComposer::new())
+            Composer::new(Some(SetupComposer::new()), None)
         } else {
-            Composer::Proving(ProvingComposer::new())
+            Composer::new(None, Some(ProvingComposer::new()))
         };
 
-        Self { composer, lookup_table }
+        Self {
+            composer,
+            lookup_table,
+            eager_lookup: true,
+            constants: ConstantCache::default(),
+        }
     }
 
     ///
@@ -80,16 +113,36 @@ impl<F: Field> ConstraintSystem<F> {
         lookup_table: LookupTable<F>,
     ) -> Self {
         let composer = if setup {
-            Composer::Setup(
-                SetupComposer::with_capacity(constraint_size, variable_size)
+            Composer::new(
+                Some(SetupComposer::with_capacity(constraint_size, variable_size)),
+                None,
             )
         } else {
-            Composer::Proving(
-                ProvingComposer::with_capacity(constraint_size, variable_size)
+            Composer::new(
+                None,
+                Some(ProvingComposer::with_capacity(constraint_size, variable_size)),
             )
         };
 
-        Self { composer, lookup_table }
+        Self {
+            composer,
+            lookup_table,
+            eager_lookup: true,
+            constants: ConstantCache::default(),
+        }
+    }
+
+    /// Creates a constraint system which records both the gate structure and
+    /// the witness, so that a single synthesis can be checked gate by gate.
+    pub fn new_with_witness(lookup_table: LookupTable<F>) -> Self {
+        let composer = Composer::new(Some(SetupComposer::new()), Some(ProvingComposer::new()));
+
+        Self {
+            composer,
+            lookup_table,
+            eager_lookup: true,
+            constants: ConstantCache::default(),
+        }
     }
 
     /// Returns the length of the circuit that can accomodate the lookup table.
@@ -104,14 +157,29 @@ impl<F: Field> ConstraintSystem<F> {
 
     ///
     pub fn assign_variable(&mut self, value: F) -> Variable {
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                composer.perm.new_variable()
-            }
-            Composer::Proving(composer) => {
-                composer.var_map.assign_variable(value)
-            }
-        }
+        self.composer.new_variable(value)
+    }
+
+    /// Returns whether the witness is synthesized, i.e. witness values are
+    /// read rather than ignored.
+    pub fn has_witness(&self) -> bool {
+        self.composer.proving.is_some()
+    }
+
+    /// Assigns the value given by `witness`, which is only evaluated with a
+    /// witness, so that setup needs no value. It fails if `witness` gives no
+    /// value when proving.
+    pub fn assign_witness<W>(&mut self, witness: W) -> Result<Variable, Error>
+    where
+        W: FnOnce() -> Option<F>,
+    {
+        let value = if self.has_witness() {
+            witness().ok_or(Error::MissingWitness)?
+        } else {
+            F::zero()
+        };
+
+        Ok(self.assign_variable(value))
     }
 
     ///
@@ -123,13 +191,28 @@ impl<F: Field> ConstraintSystem<F> {
         sels: Selectors<F>,
         pi: Option<F>,
     ) {
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                composer.gate_constrain(w_l, w_r, w_o, sels, pi.is_some());
-            }
-            Composer::Proving(composer) => {
-                composer.input_wires(w_l, w_r, w_o, pi);
-            }
+        for var in [w_l, w_r, w_o] {
+            self.emit_pending_constant(var);
+        }
+
+        self.composer.gate_constrain(w_l, w_r, w_o, sels, pi);
+    }
+
+    /// Returns the value of `var`, or zero without a witness.
+    pub fn value_of_var(&self, var: Variable) -> F {
+        self.composer.value_of_var(var)
+    }
+
+    /// Returns the value of `x`, or zero without a witness.
+    pub fn value_of_lt_var(&self, x: &LTVariable<F>) -> F {
+        self.composer.value_of_lt_var(x)
+    }
+
+    /// Confirms that `value` is in the lookup table, unless there is no
+    /// witness or the check is left to the [`MockProver`].
+    fn check_lookup(&self, value: &F) {
+        if self.eager_lookup && self.composer.has_witness() {
+            self.lookup_table.contains(value);
         }
     }
 }
@@ -138,24 +221,16 @@ impl<F: Field> ConstraintSystem<F> {
 
     /// Constrain a value in the lookup table.
     pub fn lookup_constrain(&mut self, x: &LTVariable<F>) {
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let w_o = composer.perm.new_variable();
-                let sels = Selectors::new()
-                    .with_left(F::one())
-                    .with_out(-F::one())
-                    .with_lookup()
-                    .by_left_lt(x);
-                composer.gate_constrain(x.var, Variable::Zero, w_o, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let out = composer.var_map.value_of_lt_var(x);
-                // confirm that the value is in the lookup table
-                self.lookup_table.contains(&out);
-                let w_o = composer.var_map.assign_variable(out);
-                composer.input_wires(x.var, Variable::Zero, w_o, None);
-            }
-        }
+        let out = self.value_of_lt_var(x);
+        self.check_lookup(&out);
+        let w_o = self.assign_variable(out);
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_out(-F::one())
+            .with_lookup()
+            .by_left_lt(x);
+
+        self.arith_constrain(x.var, Variable::Zero, w_o, sels, None);
     }
 
     /// Add a constraint into the circuit description that states that two
@@ -170,72 +245,42 @@ impl<F: Field> ConstraintSystem<F> {
         self.arith_constrain(x.var, y.var, Variable::Zero, sels, None);
     }
 
-    ///
+    /// Recomposes little-endian bits of any length into a variable whose
+    /// value is `sum(2^i * b_i)`, with one gate per bit after the first.
     pub fn bits_le_constrain(&mut self, bits: &[Boolean]) -> Variable {
-        // We restrict the length of the bits to be a power of two, so that we
-        // can use a recursive construction.
-        assert!(bits.len().is_power_of_two(), "bits length must be a power of two");
+        let mut bits = bits.iter();
+        let mut acc = match bits.next() {
+            Some(bit) => bit.0,
+            None => return Variable::Zero,
+        };
 
-        let mut vars = bits.iter().map(|bit| bit.0).collect_vec();
-        while vars.len() > 1 {
-            vars = vars
-                .chunks(2)
-                .map(|chunk| {
-                    match &mut self.composer {
-                        Composer::Setup(composer) => {
-                            let new_var = composer.perm.new_variable();
-                            let sels = Selectors::new()
-                                .with_left(F::one())
-                                .with_right(F::from(2u64))
-                                .with_out(-F::one());
-
-                            composer.gate_constrain(chunk[0], chunk[1], new_var, sels, false);
-
-                            new_var
-                        }
-                        Composer::Proving(composer) => {
-                            let left = composer.var_map.value_of_var(chunk[0]);
-                            let right = composer.var_map.value_of_var(chunk[1]);
-                            let new_val = left + right * F::from(2u64);
-                            let new_var = composer.var_map.assign_variable(new_val);
-
-                            composer.input_wires(chunk[0], chunk[1], new_var, None);
-
-                            new_var
-                        }
-                    }
-                })
-                .collect_vec();
+        // acc_i = acc_{i-1} + 2^i * b_i
+        let mut power = F::one();
+        for bit in bits {
+            power.double_in_place();
+            let acc_value = self.value_of_var(acc);
+            let bit_value = self.value_of_var(bit.0);
+            let new_acc = self.assign_variable(acc_value + power * bit_value);
+            let sels = Selectors::new()
+                .with_left(F::one())
+                .with_right(power)
+                .with_out(-F::one());
+
+            self.arith_constrain(acc, bit.0, new_acc, sels, None);
+            acc = new_acc;
         }
 
-        vars[0]
+        acc
     }
 
     /// x = public input
     pub fn set_variable_public(&mut self, x: &LTVariable<F>) {
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                let sels = Selectors::new()
-                    .with_out(-F::one())
-                    .by_out_lt(x);
-
-                composer.gate_constrain(
-                    Variable::Zero,
-                    Variable::Zero,
-                    x.var,
-                    sels,
-                    true,
-                );
-            }
-            Composer::Proving(composer) => {
-                composer.input_wires(
-                    Variable::Zero,
-                    Variable::Zero,
-                    x.var,
-                    Some(composer.var_map.value_of_lt_var(x)),
-                );
-            }
-        }
+        let sels = Selectors::new()
+            .with_out(-F::one())
+            .by_out_lt(x);
+        let pi = self.value_of_lt_var(x);
+
+        self.arith_constrain(Variable::Zero, Variable::Zero, x.var, sels, Some(pi));
     }
 
     /// A gate which outputs a variable whose value is 1 if
@@ -245,38 +290,26 @@ impl<F: Field> ConstraintSystem<F> {
         // x * y + z - 1 = 0
         // x * z = 0
         // where y is auxiliary and z is the boolean (x == 0).
-        let (y, z): (Variable, Variable);
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                y = composer.perm.new_variable();
-                z = composer.perm.new_variable();
+        let x_value = self.value_of_lt_var(x);
+        let y_value = x_value.inverse().unwrap_or_default();
+        let z_value = if x_value.is_zero() { F::one() } else { F::zero() };
 
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(F::one())
-                    .with_constant(-F::one())
-                    .by_out_lt(x);
+        let y = self.assign_variable(y_value);
+        let z = self.assign_variable(z_value);
 
-                composer.gate_constrain(x.var, y, z, sels, false);
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(F::one())
+            .with_constant(-F::one())
+            .by_left_lt(x);
 
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .by_out_lt(x);
+        self.arith_constrain(x.var, y, z, sels, None);
 
-                composer.gate_constrain(x.var, z, Variable::Zero, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let x_value = composer.var_map.value_of_lt_var(x);
-                let y_value = x_value.inverse().unwrap_or_default();
-                let z_value = if x_value.is_zero() { F::one() } else { F::zero() };
-
-                y = composer.var_map.assign_variable(y_value);
-                z = composer.var_map.assign_variable(z_value);
-                
-                composer.input_wires(x.var, y, z, None);
-                composer.input_wires(x.var, z, Variable::Zero, None);
-            }
-        }
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .by_left_lt(x);
+
+        self.arith_constrain(x.var, z, Variable::Zero, sels, None);
 
         Boolean(z)
     }
@@ -304,56 +337,47 @@ impl<F: Field> ConstraintSystem<F> {
         choice_a: &LTVariable<F>,
         choice_b: &LTVariable<F>,
     ) -> Variable {
-        let (x, y, z): (Variable, Variable, Variable);
+        if let Some(bit_value) = self.constant_value(&bit.0.into()) {
+            let choice = if bit_value.is_one() { choice_a } else { choice_b };
+            if let Some(z) = self.as_variable(choice) {
+                return z;
+            }
+        }
+
         // bit * a - x = 0
         // (1 - bit) * b - y = 0 => b - bit * b - y = 0
         // x + y - z = 0
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                x = composer.perm.new_variable();
-                y = composer.perm.new_variable();
-                z = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one())
-                    .by_right_lt(choice_a);
-
-                composer.gate_constrain(bit.0, choice_a.var, x, sels, false);
-
-                let sels = Selectors::new()
-                    .with_mul(-F::one())
-                    .with_right(F::one())
-                    .with_out(-F::one())
-                    .by_right_lt(choice_b);
-
-                composer.gate_constrain(bit.0, choice_b.var, y, sels, false);
-
-                let sels = Selectors::new()
-                    .with_left(F::one())
-                    .with_right(F::one())
-                    .with_out(-F::one());
-                
-                composer.gate_constrain(x, y, z, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let bit_value = composer.var_map.value_of_var(bit.0);
-                assert!(bit_value.is_one() || bit_value.is_zero());
-                let x_value = composer.var_map.value_of_lt_var(choice_a);
-                let y_value = composer.var_map.value_of_lt_var(choice_b);
-                let x_value = bit_value * x_value;
-                let y_value = (F::one() - bit_value) * y_value;
-                let z_value = x_value + y_value;
-
-                x = composer.var_map.assign_variable(x_value);
-                y = composer.var_map.assign_variable(y_value);
-                z = composer.var_map.assign_variable(z_value);
-
-                composer.input_wires(bit.0, choice_a.var, x, None);
-                composer.input_wires(bit.0, choice_b.var, y, None);
-                composer.input_wires(x, y, z, None);
-            }
-        }
+        let bit_value = self.value_of_var(bit.0);
+        assert!(bit_value.is_one() || bit_value.is_zero());
+        let x_value = bit_value * self.value_of_lt_var(choice_a);
+        let y_value = (F::one() - bit_value) * self.value_of_lt_var(choice_b);
+        let z_value = x_value + y_value;
+
+        let x = self.assign_variable(x_value);
+        let y = self.assign_variable(y_value);
+        let z = self.assign_variable(z_value);
+
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one())
+            .by_right_lt(choice_a);
+
+        self.arith_constrain(bit.0, choice_a.var, x, sels, None);
+
+        let sels = Selectors::new()
+            .with_mul(-F::one())
+            .with_right(F::one())
+            .with_out(-F::one())
+            .by_right_lt(choice_b);
+
+        self.arith_constrain(bit.0, choice_b.var, y, sels, None);
+
+        let sels = Selectors::new()
+            .with_left(F::one())
+            .with_right(F::one())
+            .with_out(-F::one());
+
+        self.arith_constrain(x, y, z, sels, None);
 
         z
     }
@@ -372,33 +396,29 @@ impl<F: Field> ConstraintSystem<F> {
         bit: Boolean,
         value: &LTVariable<F>,
     ) -> Variable {
+        if let Some(bit_value) = self.constant_value(&bit.0.into()) {
+            let choice = if bit_value.is_one() { *value } else { LTVariable::zero() };
+            if let Some(out) = self.as_variable(&choice) {
+                return out;
+            }
+        }
+
         // bit * value - out = 0
-        let out: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                out = composer.perm.new_variable();
+        let bit_value = self.value_of_var(bit.0);
+        assert!(bit_value.is_one() || bit_value.is_zero());
+        let out_value = if bit_value.is_zero() {
+            F::zero()
+        } else {
+            self.value_of_lt_var(value)
+        };
 
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_out(-F::one())
-                    .by_right_lt(value);
+        let out = self.assign_variable(out_value);
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_out(-F::one())
+            .by_right_lt(value);
 
-                composer.gate_constrain(bit.0, value.var, out, sels, false);
-            }
-            Composer::Proving(composer) => {
-                let bit_value = composer.var_map.value_of_var(bit.0);
-                assert!(bit_value.is_one() || bit_value.is_zero());
-                let out_value = if bit_value.is_zero() {
-                    F::zero()
-                } else {
-                    composer.var_map.value_of_lt_var(value)
-                };
-
-                out = composer.var_map.assign_variable(out_value);
-                
-                composer.input_wires(bit.0, value.var, out, None);
-            }
-        }
+        self.arith_constrain(bit.0, value.var, out, sels, None);
 
         out
     }
@@ -417,39 +437,73 @@ impl<F: Field> ConstraintSystem<F> {
         bit: Boolean,
         value: &LTVariable<F>,
     ) -> Variable {
-        // bit * value - bit - out + 1 = 0
-        let out: Variable;
-        match &mut self.composer {
-            Composer::Setup(composer) => {
-                out = composer.perm.new_variable();
-
-                let sels = Selectors::new()
-                    .with_mul(F::one())
-                    .with_left(-F::one())
-                    .with_out(-F::one())
-                    .with_constant(F::one())
-                    .by_right_lt(value);
-
-                composer.gate_constrain(bit.0, value.var, out, sels, false);
+        if let Some(bit_value) = self.constant_value(&bit.0.into()) {
+            let choice = if bit_value.is_one() { *value } else { LTVariable::constant(F::one()) };
+            if let Some(out) = self.as_variable(&choice) {
+                return out;
             }
-            Composer::Proving(composer) => {
-                let bit_value = composer.var_map.value_of_var(bit.0);
-                assert!(bit_value.is_one() || bit_value.is_zero());
-        
-                let out_value = if bit_value.is_zero() {
-                    F::one()
-                } else {
-                    composer.var_map.value_of_lt_var(value)
-                };
+        }
 
-                out = composer.var_map.assign_variable(out_value);
+        // bit * value - bit - out + 1 = 0
+        let bit_value = self.value_of_var(bit.0);
+        assert!(bit_value.is_one() || bit_value.is_zero());
+        let out_value = if bit_value.is_zero() {
+            F::one()
+        } else {
+            self.value_of_lt_var(value)
+        };
 
-                composer.input_wires(bit.0, value.var, out, None)
-            }
-        }
+        let out = self.assign_variable(out_value);
+        let sels = Selectors::new()
+            .with_mul(F::one())
+            .with_left(-F::one())
+            .with_out(-F::one())
+            .with_constant(F::one())
+            .by_right_lt(value);
+
+        self.arith_constrain(bit.0, value.var, out, sels, None);
 
         out
     }
+
+    /// Selects one of `2^m` choices by an `m`-bit index given in little-endian
+    /// order, i.e. returns `choices[sum(bits[i] * 2^i)]`.
+    ///
+    /// The selection is done layer by layer with
+    /// [`ConstraintSystem::conditional_select`], and pairs of identical
+    /// choices are passed through without emitting any gate.
+    ///
+    /// # Note
+    /// The `bits` used as input should have previously been constrained to be
+    /// either 1 or 0 using a bool constrain. See:
+    /// [`ConstraintSystem::boolean_gate`].
+    pub fn multiplexer(
+        &mut self,
+        bits: &[Boolean],
+        choices: &[LTVariable<F>],
+    ) -> LTVariable<F> {
+        assert_eq!(
+            choices.len(),
+            1 << bits.len(),
+            "number of choices must be 2^(number of bits)",
+        );
+
+        let mut choices = choices.to_vec();
+        for &bit in bits {
+            choices = choices
+                .chunks(2)
+                .map(|pair| {
+                    if pair[0] == pair[1] {
+                        pair[0]
+                    } else {
+                        self.conditional_select(bit, &pair[1], &pair[0]).into()
+                    }
+                })
+                .collect_vec();
+        }
+
+        choices[0]
+    }
 }
 
 #[cfg(test)]
@@ -475,10 +529,64 @@ mod test {
         )
     }
 
+    fn test_multiplexer<F: Field>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let choice_values = (0..8).map(|_| F::rand(rng)).collect::<Vec<_>>();
+                let choices = choice_values
+                    .iter()
+                    .map(|value| cs.assign_variable(*value).into())
+                    .collect::<Vec<_>>();
+
+                (0..8)
+                    .map(|index| {
+                        let bits = (0..3)
+                            .map(|i| {
+                                let bit = cs.assign_variable(F::from((index >> i) & 1u64));
+                                cs.boolean_gate(bit)
+                            })
+                            .collect::<Vec<_>>();
+                        let out = cs.multiplexer(&bits, &choices);
+
+                        (out, choice_values[index as usize])
+                    })
+                    .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_multiplexer_with_identical_choices<F: Field>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let a_value = F::rand(rng);
+                let b_value = F::rand(rng);
+                let a = cs.assign_variable(a_value).into();
+                let b = cs.assign_variable(b_value).into();
+                let bit_0 = cs.assign_variable(F::one());
+                let bit_0 = cs.boolean_gate(bit_0);
+                let bit_1 = cs.assign_variable(F::zero());
+                let bit_1 = cs.boolean_gate(bit_1);
+
+                let size = cs.composer.size();
+                let out = cs.multiplexer(&[bit_0, bit_1], &[a, b, a, a]);
+                // the identical pair `(a, a)` is passed through
+                assert_eq!(cs.composer.size(), size + 6);
+
+                vec![(out, b_value)]
+            },
+            &[],
+        )
+    }
+
     batch_test_field!(
         Bn254,
         [
-            test_set_variable_public
+            test_set_variable_public,
+            test_multiplexer,
+            test_multiplexer_with_identical_choices
         ],
         []
     );
@@ -0,0 +1,354 @@
+//! Namespaces which tag gates and variables by the part of the circuit that
+//! creates them, and the cost profile of a circuit per namespace.
+
+use core::{borrow::Borrow, fmt};
+use ark_ff::Field;
+use indexmap::IndexSet;
+
+use super::*;
+
+/// Separator of the names in a namespace path.
+const SEPARATOR: char = '/';
+
+/// Nested namespaces of a circuit, e.g. `/merkle/poseidon`, and the
+/// namespace in which each gate and variable is created.
+#[derive(Clone, Debug)]
+pub struct Namespaces {
+    /// Distinct namespace paths, the root `/` first.
+    paths: IndexSet<String>,
+    /// Parent of each path, the root being its own parent.
+    parents: Vec<usize>,
+    /// Current namespace and its ancestors, without the root.
+    stack: Vec<usize>,
+    /// Namespace of each gate.
+    gates: Vec<usize>,
+    /// Namespace of each variable.
+    vars: Vec<usize>,
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Namespaces {
+    ///
+    pub fn new() -> Self {
+        Self {
+            paths: IndexSet::from_iter([SEPARATOR.to_string()]),
+            parents: vec![0],
+            stack: Vec::new(),
+            gates: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the current namespace.
+    fn current(&self) -> usize {
+        self.stack.last().copied().unwrap_or(0)
+    }
+
+    /// Enters the namespace `name` nested in the current one.
+    pub fn push(&mut self, name: &str) {
+        assert!(!name.contains(SEPARATOR), "namespace {:?} contains '{}'", name, SEPARATOR);
+
+        let parent = self.current();
+        let path = if parent == 0 {
+            format!("{}{}", SEPARATOR, name)
+        } else {
+            format!("{}{}{}", self.paths[parent], SEPARATOR, name)
+        };
+        let (index, inserted) = self.paths.insert_full(path);
+        if inserted {
+            self.parents.push(parent);
+        }
+        self.stack.push(index);
+    }
+
+    /// Leaves the current namespace.
+    pub fn pop(&mut self) {
+        self.stack.pop().expect("no namespace to leave");
+    }
+
+    /// Returns the path of the current namespace.
+    pub fn current_path(&self) -> &str {
+        &self.paths[self.current()]
+    }
+
+    pub(super) fn tag_gate(&mut self) {
+        self.gates.push(self.current());
+    }
+
+    pub(super) fn tag_var(&mut self) {
+        self.vars.push(self.current());
+    }
+
+    /// Keeps the namespaces of the gates at `indices`, in that order.
+    pub(super) fn retain_gates(&mut self, indices: &[usize]) {
+        self.gates = indices.iter().map(|&i| self.gates[i]).collect();
+    }
+
+    /// Returns the namespace path of the gate at `index`.
+    pub fn gate_path(&self, index: usize) -> Option<&str> {
+        self.gates.get(index).map(|&i| self.paths[i].as_str())
+    }
+
+    /// Returns the namespace path of `var`.
+    pub fn var_path(&self, var: Variable) -> Option<&str> {
+        match var {
+            Variable::Zero => None,
+            Variable::Var(index) => self.vars.get(index).map(|&i| self.paths[i].as_str()),
+        }
+    }
+
+    /// Adds one to `counts` at the namespace `i` and at each of its
+    /// ancestors.
+    fn count(&self, mut i: usize, counts: &mut [usize]) {
+        loop {
+            counts[i] += 1;
+            if i == 0 {
+                break;
+            }
+            i = self.parents[i];
+        }
+    }
+}
+
+/// Cost of a namespace, including the namespaces nested in it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NamespaceCost {
+    ///
+    pub path: String,
+    ///
+    pub gates: usize,
+    ///
+    pub lookups: usize,
+    ///
+    pub public_inputs: usize,
+    ///
+    pub variables: usize,
+}
+
+/// Costs of the namespaces of a circuit, in the order they are entered first.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Profile(pub Vec<NamespaceCost>);
+
+impl Profile {
+    /// Returns the cost of the namespace at `path`.
+    pub fn get(&self, path: &str) -> Option<&NamespaceCost> {
+        self.0.iter().find(|cost| cost.path == path)
+    }
+
+    /// Renders the profile as a JSON array of objects.
+    pub fn to_json(&self) -> String {
+        let entries = self.0
+            .iter()
+            .map(|cost| {
+                format!(
+                    "{{\"path\":\"{}\",\"gates\":{},\"lookups\":{},\"public_inputs\":{},\"variables\":{}}}",
+                    escape_json(&cost.path),
+                    cost.gates,
+                    cost.lookups,
+                    cost.public_inputs,
+                    cost.variables,
+                )
+            })
+            .join(",");
+
+        format!("[{}]", entries)
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.0
+            .iter()
+            .map(|cost| cost.path.len())
+            .max()
+            .unwrap_or_default()
+            .max("namespace".len());
+        writeln!(
+            f,
+            "{:<width$} {:>10} {:>10} {:>10} {:>10}",
+            "namespace", "gates", "lookups", "inputs", "variables",
+        )?;
+        for cost in self.0.iter() {
+            writeln!(
+                f,
+                "{:<width$} {:>10} {:>10} {:>10} {:>10}",
+                cost.path, cost.gates, cost.lookups, cost.public_inputs, cost.variables,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl<F: Field> SetupComposer<F> {
+    /// Returns the gates, lookups, public inputs and variables of each
+    /// namespace.
+    pub fn profile(&self) -> Profile {
+        let namespaces = &self.namespaces;
+        let size = namespaces.paths.len();
+        let mut gates = vec![0; size];
+        let mut lookups = vec![0; size];
+        let mut public_inputs = vec![0; size];
+        let mut variables = vec![0; size];
+
+        for (i, &namespace) in namespaces.gates.iter().enumerate() {
+            namespaces.count(namespace, &mut gates);
+            if !self.q_lookup[i].is_zero() {
+                namespaces.count(namespace, &mut lookups);
+            }
+            if self.pp.contains(i) {
+                namespaces.count(namespace, &mut public_inputs);
+            }
+        }
+        for &namespace in namespaces.vars.iter() {
+            namespaces.count(namespace, &mut variables);
+        }
+
+        let costs = namespaces.paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| NamespaceCost {
+                path: path.clone(),
+                gates: gates[i],
+                lookups: lookups[i],
+                public_inputs: public_inputs[i],
+                variables: variables[i],
+            })
+            .collect();
+
+        Profile(costs)
+    }
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Enters the namespace `name` nested in the current one, which tags the
+    /// following gates and variables until [`ConstraintSystem::pop_namespace`].
+    pub fn push_namespace(&mut self, name: &str) {
+        if let Some(composer) = &mut self.composer.setup {
+            composer.namespaces.push(name);
+        }
+    }
+
+    /// Leaves the current namespace.
+    pub fn pop_namespace(&mut self) {
+        if let Some(composer) = &mut self.composer.setup {
+            composer.namespaces.pop();
+        }
+    }
+
+    /// Runs `f` in the namespace `name` nested in the current one.
+    pub fn namespace<R, P>(&mut self, name: &str, f: P) -> R
+    where
+        P: FnOnce(&mut Self) -> R,
+    {
+        self.push_namespace(name);
+        let result = f(self);
+        self.pop_namespace();
+
+        result
+    }
+
+    /// Returns the cost profile of the circuit per namespace.
+    pub fn profile(&self) -> Profile {
+        let composer: &SetupComposer<F> = self.composer.borrow();
+        composer.profile()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+
+    use crate::batch_test_field;
+    use super::*;
+
+    fn synthesize<F: Field>(cs: &mut ConstraintSystem<F>) {
+        let a = cs.assign_variable(F::from(3u64));
+        cs.namespace("hash", |cs| {
+            let b = cs.assign_variable(F::from(4u64));
+            let c = cs.add_gate(&a.into(), &b.into());
+            cs.namespace("round", |cs| {
+                cs.lookup_constrain(&c.into());
+            });
+        });
+        cs.namespace("output", |cs| {
+            cs.set_variable_public(&a.into());
+        });
+    }
+
+    fn test_profile<F: Field>() {
+        let mut cs = ConstraintSystem::new_with_witness((0..8u64).map(F::from).into());
+        synthesize(&mut cs);
+
+        let profile = cs.profile();
+        let paths = profile.0.iter().map(|cost| cost.path.as_str()).collect_vec();
+        assert_eq!(paths, ["/", "/hash", "/hash/round", "/output"]);
+        let costs = profile.0.iter()
+            .map(|cost| (cost.gates, cost.lookups, cost.public_inputs, cost.variables))
+            .collect_vec();
+        assert_eq!(costs, [(3, 1, 1, 4), (2, 1, 0, 3), (1, 1, 0, 1), (1, 0, 1, 0)]);
+
+        assert_eq!(
+            profile.get("/hash/round"),
+            Some(&NamespaceCost {
+                path: "/hash/round".to_string(),
+                gates: 1,
+                lookups: 1,
+                public_inputs: 0,
+                variables: 1,
+            }),
+        );
+        assert!(profile.to_json().starts_with(
+            "[{\"path\":\"/\",\"gates\":3,\"lookups\":1,\"public_inputs\":1,\"variables\":4},"
+        ));
+        assert_eq!(profile.to_string().lines().count(), 5);
+    }
+
+    fn test_namespaces_without_setup<F: Field>() {
+        let mut cs = ConstraintSystem::new(false, (0..8u64).map(F::from).into());
+        synthesize(&mut cs);
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_profile, test_namespaces_without_setup],
+        []
+    );
+
+    #[test]
+    #[should_panic(expected = "arithmetic gate at 0 in /hash/round is not satisfied")]
+    fn test_namespace_in_gate_failure() {
+        test_gate_constraints(
+            |cs: &mut ConstraintSystem<Fr>| {
+                cs.namespace("hash", |cs| {
+                    cs.namespace("round", |cs| {
+                        let a = cs.assign_variable(Fr::from(1u64));
+                        cs.equal_constrain(&a.into(), &LTVariable::zero());
+                    });
+                });
+
+                vec![]
+            },
+            &[],
+        );
+    }
+}
@@ -0,0 +1,592 @@
+//! Non-native Field Arithmetic
+//!
+//! An element of a field `K` other than the circuit field is kept as
+//! little-endian limbs of [`LIMB_BITS`] bits, which are range checked by byte
+//! lookups, so the lookup table of the circuit must contain
+//! [`byte_table`](super::byte_table). Limbs only bound an element below
+//! `2^(LIMB_BITS * num_limbs)`, not below the modulus `p`, so an element is
+//! any representative of its residue class.
+//!
+//! Every operation witnesses the quotient `q` of an integer relation
+//! `E = q * p + r` and checks `E - q * p - r = 0` as in the Chinese remainder
+//! theorem: once modulo the circuit field, where limbs recompose linearly,
+//! and once modulo `2^(LIMB_BITS * t)` by carries over the lowest `t` limb
+//! columns. The moduli are coprime and their product exceeds twice the bound
+//! of the relation, so it holds over the integers.
+
+use ark_ff::{Field, FpParameters, PrimeField};
+use ark_std::marker::PhantomData;
+use derivative::Derivative;
+use itertools::Itertools;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{One, Zero};
+
+use super::{ConstraintSystem, LTVariable, Variable};
+
+/// Bit size of the limbs of non-native elements.
+pub const LIMB_BITS: usize = 64;
+
+/// Converts a signed integer into the circuit field.
+fn to_field<F: PrimeField>(x: &BigInt) -> F {
+    let magnitude = F::from(x.magnitude().clone());
+    match x.sign() {
+        Sign::Minus => -magnitude,
+        _ => magnitude,
+    }
+}
+
+/// Returns the modulus of a prime field.
+fn modulus<F: PrimeField>() -> BigUint {
+    F::Params::MODULUS.into()
+}
+
+/// Term of a limb column, whose value is an integer in `[0, max]`.
+struct Term<F: Field> {
+    var: LTVariable<F>,
+    negative: bool,
+    max: BigUint,
+}
+
+/// Integer relation `sum(2^(LIMB_BITS * k) * c_k) = 0`, where the column
+/// `c_k` sums signed terms.
+struct Relation<F: Field> {
+    columns: Vec<Vec<Term<F>>>,
+}
+
+impl<F: PrimeField> Relation<F> {
+    fn new() -> Self {
+        Self { columns: Vec::new() }
+    }
+
+    fn push(&mut self, column: usize, var: LTVariable<F>, negative: bool, max: BigUint) {
+        if self.columns.len() <= column {
+            self.columns.resize_with(column + 1, Vec::new);
+        }
+        self.columns[column].push(Term { var, negative, max });
+    }
+
+    /// Constrains the relation modulo the circuit field and modulo
+    /// `2^(LIMB_BITS * t)`, where `t` is the least number of columns such
+    /// that the product of both moduli exceeds twice the bound of the
+    /// integer.
+    fn enforce(mut self, cs: &mut ConstraintSystem<F>) {
+        let column_bounds = self
+            .columns
+            .iter()
+            .map(|terms| terms.iter().map(|term| &term.max).sum::<BigUint>())
+            .collect_vec();
+        let bound = column_bounds
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, column_bound| (acc << LIMB_BITS) + column_bound);
+        let field_bound = modulus::<F>();
+        let mut num_carries = 0;
+        while (&field_bound << (LIMB_BITS * num_carries)) <= &bound << 1 {
+            num_carries += 1;
+        }
+
+        // modulo the circuit field, all columns recompose linearly
+        let shift = F::from(BigUint::one() << LIMB_BITS);
+        let mut power = F::one();
+        let mut terms = Vec::new();
+        for column in &self.columns {
+            for term in column {
+                let coeff = if term.negative { -power } else { power };
+                terms.push(term.var.linear_transform(coeff, F::zero()));
+            }
+            power *= shift;
+        }
+        let sum = cs.linear_sum(terms);
+        cs.equal_constrain(&sum, &LTVariable::zero());
+
+        // modulo 2^(LIMB_BITS * t): c_k + carry_{k-1} = 2^LIMB_BITS * carry_k
+        self.columns.resize_with(self.columns.len().max(num_carries), Vec::new);
+        let mut carry = LTVariable::zero();
+        let mut carry_value = BigInt::zero();
+        let mut carry_bound = BigUint::zero();
+        for (k, column) in self.columns.iter().take(num_carries).enumerate() {
+            let column_bound = column_bounds.get(k).cloned().unwrap_or_default() + &carry_bound;
+            assert!(
+                column_bound.bits() + 8 < F::size_in_bits() as u64,
+                "circuit field is too small for the limbs",
+            );
+
+            let column_value = column.iter().fold(carry_value, |acc, term| {
+                let value = BigInt::from(cs.integer_value(&term.var));
+                if term.negative { acc - value } else { acc + value }
+            });
+            carry_value = column_value >> LIMB_BITS;
+            carry_bound = column_bound >> LIMB_BITS;
+
+            // carries are signed, so they are range checked with an offset
+            let num_bytes = (carry_bound.bits() as usize + 1).div_ceil(8);
+            let offset = F::from(BigUint::one() << (8 * num_bytes - 1));
+            let new_carry: LTVariable<F> = cs.assign_variable(to_field(&carry_value)).into();
+            cs.to_bytes_le(&new_carry.linear_transform(F::one(), offset), num_bytes);
+
+            let terms = column
+                .iter()
+                .map(|term| {
+                    let coeff = if term.negative { -F::one() } else { F::one() };
+                    term.var.linear_transform(coeff, F::zero())
+                })
+                .chain([carry, new_carry.linear_transform(-shift, F::zero())])
+                .collect_vec();
+            let sum = cs.linear_sum(terms);
+            cs.equal_constrain(&sum, &LTVariable::zero());
+
+            carry = new_carry;
+        }
+    }
+}
+
+/// Element of the prime field `K` in a circuit over `F`.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct NonNativeFieldVar<K: PrimeField, F: Field> {
+    limbs: Vec<LTVariable<F>>,
+    _k: PhantomData<K>,
+}
+
+impl<K: PrimeField, F: PrimeField> NonNativeFieldVar<K, F> {
+    /// Returns the number of limbs of an element.
+    pub fn num_limbs() -> usize {
+        K::size_in_bits().div_ceil(LIMB_BITS)
+    }
+
+    /// Returns the little-endian limbs.
+    pub fn limbs(&self) -> &[LTVariable<F>] {
+        &self.limbs
+    }
+
+    /// Assigns `value`, whose limbs are range checked.
+    pub fn new(cs: &mut ConstraintSystem<F>, value: &K) -> Self {
+        let limbs = cs.limbs_gate(&(*value).into(), Self::num_limbs());
+
+        Self { limbs, _k: PhantomData }
+    }
+
+    /// Returns an element fixed to `value`, which costs no gate.
+    pub fn constant(value: &K) -> Self {
+        let mut digits = Into::<BigUint>::into(*value).to_u64_digits();
+        digits.resize(Self::num_limbs(), 0);
+        let limbs = digits
+            .into_iter()
+            .map(|digit| LTVariable::constant(F::from(digit)))
+            .collect();
+
+        Self { limbs, _k: PhantomData }
+    }
+
+    /// Returns the integer of the limbs, or zero without a witness.
+    fn value(&self, cs: &ConstraintSystem<F>) -> BigUint {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, limb| (acc << LIMB_BITS) + cs.integer_value(limb))
+    }
+
+    /// Witnesses `q` and `r` such that `E = q * p + r` for the integer
+    /// `E = sum(a * b) + sum(±x) + constant`, constrains the relation and
+    /// returns `r`. Without remainder `r` is zero, which constrains `E` to be
+    /// a multiple of `p`.
+    ///
+    /// `E` must not be negative, otherwise there is no valid quotient and the
+    /// gates fail.
+    fn reduce(
+        cs: &mut ConstraintSystem<F>,
+        products: &[(&Self, &Self)],
+        sums: &[(&Self, bool)],
+        constant: &BigInt,
+        with_remainder: bool,
+    ) -> Self {
+        let num_limbs = Self::num_limbs();
+        let p = modulus::<K>();
+        let limb_max = (BigUint::one() << LIMB_BITS) - 1u32;
+        let element_max = (BigUint::one() << (LIMB_BITS * num_limbs)) - 1u32;
+
+        let value = products.iter().fold(constant.clone(), |acc, (a, b)| {
+            acc + BigInt::from(a.value(cs) * b.value(cs))
+        });
+        let value = sums.iter().fold(value, |acc, (x, negative)| {
+            let x = BigInt::from(x.value(cs));
+            if *negative { acc - x } else { acc + x }
+        });
+        let value = value.to_biguint().unwrap_or_default();
+
+        // the quotient has as many limbs as the bound of E over p needs
+        let num_positive_sums = sums.iter().filter(|(_, negative)| !negative).count();
+        let max_value = element_max.pow(2) * products.len()
+            + &element_max * num_positive_sums
+            + constant.to_biguint().unwrap_or_default();
+        let num_quotient_limbs = ((max_value / &p).bits() as usize).div_ceil(LIMB_BITS);
+        let quotient = cs.limbs_gate(&(&value / &p), num_quotient_limbs);
+        let remainder = if with_remainder {
+            let limbs = cs.limbs_gate(&(&value % &p), num_limbs);
+            Self { limbs, _k: PhantomData }
+        } else {
+            Self::constant(&K::zero())
+        };
+
+        let mut relation = Relation::new();
+        for (a, b) in products {
+            for ((i, x), (j, y)) in a.limbs.iter().enumerate().cartesian_product(b.limbs.iter().enumerate()) {
+                // products with constant limbs are linear
+                let xy = if x.var == Variable::Zero {
+                    y.linear_transform(x.offset, F::zero())
+                } else if y.var == Variable::Zero {
+                    x.linear_transform(y.offset, F::zero())
+                } else {
+                    cs.mul_gate(x, y).into()
+                };
+                relation.push(i + j, xy, false, limb_max.pow(2));
+            }
+        }
+        for (x, negative) in sums {
+            for (i, limb) in x.limbs.iter().enumerate() {
+                relation.push(i, *limb, *negative, limb_max.clone());
+            }
+        }
+        let negative = constant.sign() == Sign::Minus;
+        for (i, digit) in constant.magnitude().to_u64_digits().into_iter().enumerate() {
+            let limb = LTVariable::constant(F::from(digit));
+            relation.push(i, limb, negative, digit.into());
+        }
+        for (i, q) in quotient.iter().enumerate() {
+            for (j, digit) in p.to_u64_digits().into_iter().enumerate() {
+                let qp = q.linear_transform(F::from(digit), F::zero());
+                relation.push(i + j, qp, true, &limb_max * digit);
+            }
+        }
+        for (i, limb) in remainder.limbs.iter().enumerate() {
+            relation.push(i, *limb, true, limb_max.clone());
+        }
+        relation.enforce(cs);
+
+        remainder
+    }
+
+    /// Returns a multiple of `p` which is at least `2^(LIMB_BITS *
+    /// num_limbs)`, so that subtracting an element from it stays positive.
+    fn modulus_multiple() -> BigInt {
+        let p = modulus::<K>();
+        let element_bound = BigUint::one() << (LIMB_BITS * Self::num_limbs());
+        BigInt::from((element_bound + &p - 1u32) / &p * p)
+    }
+
+    /// Calculates `self + other`.
+    pub fn add(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        Self::reduce(cs, &[], &[(self, false), (other, false)], &BigInt::zero(), true)
+    }
+
+    /// Calculates `self - other`.
+    pub fn sub(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let constant = Self::modulus_multiple();
+        Self::reduce(cs, &[], &[(self, false), (other, true)], &constant, true)
+    }
+
+    /// Calculates `self * other`.
+    pub fn mul(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        Self::reduce(cs, &[(self, other)], &[], &BigInt::zero(), true)
+    }
+
+    /// Returns the inverse, which is witnessed and constrained by
+    /// `self * inverse = 1 mod p`. Zero has no inverse and the gates fail.
+    pub fn inverse(&self, cs: &mut ConstraintSystem<F>) -> Self {
+        let value = K::BigInt::try_from(self.value(cs) % modulus::<K>())
+            .ok()
+            .and_then(K::from_repr)
+            .and_then(|x| x.inverse())
+            .unwrap_or_else(K::zero);
+        let inverse = Self::new(cs, &value);
+        Self::reduce(cs, &[(self, &inverse)], &[], &BigInt::from(-1), false);
+
+        inverse
+    }
+
+    /// Constrains `self` and `other` to be equal modulo `p`, which holds for
+    /// different representatives of the same element.
+    pub fn enforce_equal(&self, cs: &mut ConstraintSystem<F>, other: &Self) {
+        let constant = Self::modulus_multiple();
+        Self::reduce(cs, &[], &[(self, false), (other, true)], &constant, false);
+    }
+}
+
+impl<F: PrimeField> ConstraintSystem<F> {
+    /// Returns the value of `x` as an integer, or zero without a witness.
+    fn integer_value(&self, x: &LTVariable<F>) -> BigUint {
+        self.value_of_lt_var(x).into()
+    }
+
+    /// Sums `terms` with one gate per term after the first, constant terms
+    /// are free.
+    fn linear_sum(&mut self, terms: Vec<LTVariable<F>>) -> LTVariable<F> {
+        terms.into_iter().fold(LTVariable::zero(), |acc, term| {
+            if term.var == Variable::Zero {
+                acc.linear_transform(F::one(), term.offset)
+            } else if acc.var == Variable::Zero {
+                term.linear_transform(F::one(), acc.offset)
+            } else {
+                self.add_gate(&acc, &term).into()
+            }
+        })
+    }
+
+    /// Assigns `num_limbs` little-endian limbs of `value`, which are range
+    /// checked. Higher limbs are dropped, so the gates fail if `value` is too
+    /// large.
+    fn limbs_gate(&mut self, value: &BigUint, num_limbs: usize) -> Vec<LTVariable<F>> {
+        let mut digits = value.to_u64_digits();
+        digits.resize(num_limbs, 0);
+        digits
+            .into_iter()
+            .map(|digit| {
+                let limb = self.assign_variable(F::from(digit)).into();
+                self.to_bytes_le(&limb, LIMB_BITS / 8);
+                limb
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::{biginteger::BigInteger320, fields::*, UniformRand};
+    use ark_std::test_rng;
+    use ark_bn254::Bn254;
+    use ark_bls12_381::Bls12_381;
+
+    use crate::{batch_test_field, constraint_system::{byte_table, test_gate_constraints_with_table}};
+    use super::*;
+
+    /// Base field of secp256k1. The modulus takes all 256 bits, so it is
+    /// backed by five words to leave room for the carries of addition.
+    type Fq = Fp320<FqParameters>;
+
+    struct FqParameters;
+
+    impl Fp320Parameters for FqParameters {}
+
+    impl FftParameters for FqParameters {
+        type BigInt = BigInteger320;
+
+        const TWO_ADICITY: u32 = 1;
+
+        #[rustfmt::skip]
+        const TWO_ADIC_ROOT_OF_UNITY: BigInteger320 = BigInteger320([
+            0xfffffffefffffc2f,
+            0xfffffffefffffc2e,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0x0000000000000000,
+        ]);
+    }
+
+    impl FpParameters for FqParameters {
+        /// MODULUS = 2^256 - 2^32 - 977
+        #[rustfmt::skip]
+        const MODULUS: BigInteger320 = BigInteger320([
+            0xfffffffefffffc2f,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0x0000000000000000,
+        ]);
+
+        const MODULUS_BITS: u32 = 256;
+
+        const CAPACITY: u32 = Self::MODULUS_BITS - 1;
+
+        const REPR_SHAVE_BITS: u32 = 64;
+
+        #[rustfmt::skip]
+        const R: BigInteger320 = BigInteger320([
+            0x0000000000000000,
+            0x00000001000003d1,
+            0x0000000000000000,
+            0x0000000000000000,
+            0x0000000000000000,
+        ]);
+
+        #[rustfmt::skip]
+        const R2: BigInteger320 = BigInteger320([
+            0x0000000000000000,
+            0x0000000000000000,
+            0x000007a2000e90a1,
+            0x0000000000000001,
+            0x0000000000000000,
+        ]);
+
+        const INV: u64 = 15580212934572586289;
+
+        // GENERATOR = 3
+        #[rustfmt::skip]
+        const GENERATOR: BigInteger320 = BigInteger320([
+            0x0000000000000000,
+            0x0000000300000b73,
+            0x0000000000000000,
+            0x0000000000000000,
+            0x0000000000000000,
+        ]);
+
+        #[rustfmt::skip]
+        const MODULUS_MINUS_ONE_DIV_TWO: BigInteger320 = BigInteger320([
+            0xffffffff7ffffe17,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0x7fffffffffffffff,
+            0x0000000000000000,
+        ]);
+
+        #[rustfmt::skip]
+        const T: BigInteger320 = BigInteger320([
+            0xffffffff7ffffe17,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0x7fffffffffffffff,
+            0x0000000000000000,
+        ]);
+
+        #[rustfmt::skip]
+        const T_MINUS_ONE_DIV_TWO: BigInteger320 = BigInteger320([
+            0xffffffffbfffff0b,
+            0xffffffffffffffff,
+            0xffffffffffffffff,
+            0x3fffffffffffffff,
+            0x0000000000000000,
+        ]);
+    }
+
+    /// Pairs the limbs of `x` with those of the canonical `expected`.
+    fn limbs_of<F: PrimeField>(
+        x: &NonNativeFieldVar<Fq, F>,
+        expected: Fq,
+    ) -> Vec<(LTVariable<F>, F)> {
+        let expected = NonNativeFieldVar::<Fq, F>::constant(&expected);
+        x.limbs()
+            .iter()
+            .zip(expected.limbs())
+            .map(|(limb, expected)| (*limb, expected.offset))
+            .collect()
+    }
+
+    #[test]
+    fn test_secp256k1_base_field() {
+        let p: BigUint = FqParameters::MODULUS.into();
+        let expected = (BigUint::one() << 256) - (BigUint::one() << 32) - 977u32;
+        assert_eq!(p, expected);
+
+        let rng = &mut test_rng();
+        let x = Fq::rand(rng);
+        let y = Fq::rand(rng);
+        let (x_int, y_int): (BigUint, BigUint) = (x.into(), y.into());
+        assert_eq!(Into::<BigUint>::into(x * y), x_int * y_int % &p);
+        assert_eq!(x * x.inverse().unwrap(), Fq::one());
+    }
+
+    fn test_nonnative_arithmetic<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let x = Fq::rand(rng);
+                let y = Fq::rand(rng);
+                // the largest element makes every carry as large as it gets
+                let z = -Fq::one();
+                let c = Fq::rand(rng);
+
+                let x_var = NonNativeFieldVar::<Fq, F>::new(cs, &x);
+                let y_var = NonNativeFieldVar::new(cs, &y);
+                let z_var = NonNativeFieldVar::new(cs, &z);
+                let c_var = NonNativeFieldVar::constant(&c);
+
+                [
+                    (x_var.add(cs, &y_var), x + y),
+                    (z_var.add(cs, &z_var), z + z),
+                    (x_var.sub(cs, &y_var), x - y),
+                    (y_var.sub(cs, &z_var), y - z),
+                    (x_var.mul(cs, &y_var), x * y),
+                    (z_var.mul(cs, &z_var), z * z),
+                    (x_var.mul(cs, &c_var), x * c),
+                    (x_var.inverse(cs), x.inverse().unwrap()),
+                    (c_var.inverse(cs), c.inverse().unwrap()),
+                ]
+                .iter()
+                .flat_map(|(var, expected)| limbs_of(var, *expected))
+                .collect()
+            },
+            &[],
+            byte_table(),
+        )
+    }
+
+    fn test_nonnative_equality<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let x = Fq::rand(rng);
+                let y = Fq::rand(rng);
+
+                // (x + y) * (x - y) = x^2 - y^2
+                let x_var = NonNativeFieldVar::<Fq, F>::new(cs, &x);
+                let y_var = NonNativeFieldVar::new(cs, &y);
+                let sum = x_var.add(cs, &y_var);
+                let difference = x_var.sub(cs, &y_var);
+                let lhs = sum.mul(cs, &difference);
+                let x_square = x_var.mul(cs, &x_var);
+                let y_square = y_var.mul(cs, &y_var);
+                let rhs = x_square.sub(cs, &y_square);
+                lhs.enforce_equal(cs, &rhs);
+
+                // x * x^-1 = 1
+                let x_inverse = x_var.inverse(cs);
+                let one = x_var.mul(cs, &x_inverse);
+                one.enforce_equal(cs, &NonNativeFieldVar::constant(&Fq::one()));
+
+                limbs_of(&one, Fq::one())
+            },
+            &[],
+            byte_table(),
+        )
+    }
+
+    fn test_nonnative_inequality<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| {
+                let rng = &mut test_rng();
+                let x = Fq::rand(rng);
+                let x_var = NonNativeFieldVar::<Fq, F>::new(cs, &x);
+                let y_var = NonNativeFieldVar::new(cs, &(x + Fq::one()));
+                x_var.enforce_equal(cs, &y_var);
+
+                vec![]
+            },
+            &[] as &[F],
+            byte_table(),
+        )
+    }
+
+    fn test_nonnative_zero_inverse<F: PrimeField>() {
+        test_gate_constraints_with_table(
+            |cs| {
+                let x_var = NonNativeFieldVar::<Fq, F>::new(cs, &Fq::zero());
+                x_var.inverse(cs);
+
+                vec![]
+            },
+            &[] as &[F],
+            byte_table(),
+        )
+    }
+
+    batch_test_field!(
+        Bn254,
+        [test_nonnative_arithmetic, test_nonnative_equality],
+        [test_nonnative_inequality, test_nonnative_zero_inverse]
+    );
+
+    batch_test_field!(
+        Bls12_381,
+        [test_nonnative_arithmetic, test_nonnative_equality],
+        [test_nonnative_inequality, test_nonnative_zero_inverse]
+    );
+}
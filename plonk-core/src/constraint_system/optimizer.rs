@@ -0,0 +1,484 @@
+//! Gate level optimization of a synthesized circuit, run before the selectors
+//! are preprocessed into polynomials.
+//!
+//! The optimizer only looks at the gate structure, so the same circuit is
+//! optimized the same way in setup and in proving.
+
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+use ark_ff::Field;
+
+use super::*;
+
+/// Gate counts before and after [`ConstraintSystem::optimize`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OptimizationReport {
+    ///
+    pub before: usize,
+    ///
+    pub after: usize,
+    /// Gates removed as their output is never used.
+    pub dead: usize,
+    /// Linear gates merged into the gate using their output.
+    pub merged: usize,
+    /// Gates removed as duplicates of other gates.
+    pub deduplicated: usize,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gates: {} -> {} ({} dead, {} merged, {} deduplicated)",
+            self.before, self.after, self.dead, self.merged, self.deduplicated,
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Gate<F: Field> {
+    q_m: F,
+    q_l: F,
+    q_r: F,
+    q_o: F,
+    q_c: F,
+    q_lookup: F,
+    wires: [Variable; 3],
+    /// Index of the gate before optimization, which keeps its public input
+    /// and namespace.
+    index: usize,
+    pi: bool,
+}
+
+impl<F: Field> Gate<F> {
+    fn selectors(&self) -> [F; 6] {
+        [self.q_m, self.q_l, self.q_r, self.q_o, self.q_c, self.q_lookup]
+    }
+
+    /// Returns the output variable if it is determined by the inputs.
+    fn output(&self) -> Option<Variable> {
+        let [w_l, w_r, w_o] = self.wires;
+        (w_o != Variable::Zero && !self.q_o.is_zero() && w_o != w_l && w_o != w_r).then_some(w_o)
+    }
+
+    fn is_linear(&self) -> bool {
+        self.q_m.is_zero() && self.q_lookup.is_zero()
+    }
+
+    fn is_trivial(&self) -> bool {
+        !self.pi && self.selectors().iter().all(|q| q.is_zero())
+    }
+
+    /// Returns the linear terms of the gate, one for each variable.
+    fn linear_terms(&self) -> Vec<(Variable, F)> {
+        let mut terms: Vec<(Variable, F)> = Vec::with_capacity(3);
+        for (var, coeff) in self.wires.into_iter().zip([self.q_l, self.q_r, self.q_o]) {
+            if var == Variable::Zero || coeff.is_zero() {
+                continue;
+            }
+            match terms.iter_mut().find(|(x, _)| *x == var) {
+                Some((_, sum)) => *sum += coeff,
+                None => terms.push((var, coeff)),
+            }
+        }
+        terms.retain(|(_, coeff)| !coeff.is_zero());
+
+        terms
+    }
+
+    /// Replaces `t` in `self` by its solution from the linear gate `other`
+    /// whose output is `t`, if the result fits in a single gate.
+    fn substitute(&self, t: Variable, other: &Self) -> Option<Self> {
+        let [w_l, w_r, _] = self.wires;
+        if !self.q_lookup.is_zero() || (!self.q_m.is_zero() && (w_l == t || w_r == t)) {
+            return None;
+        }
+
+        let mut wires = self.wires;
+        let mut coeffs = [self.q_l, self.q_r, self.q_o];
+        let mut k = F::zero();
+        for (var, coeff) in wires.iter_mut().zip(coeffs.iter_mut()).filter(|(x, _)| **x == t) {
+            k += *coeff;
+            *var = Variable::Zero;
+            *coeff = F::zero();
+        }
+
+        // t = -(sum(c_i * x_i) + q_c) / q_o by the other gate, whose terms
+        // join the same variables or take the free wires, but the product
+        let scale = -k * other.q_o.inverse()?;
+        let fixed = if self.q_m.is_zero() { 0 } else { 2 };
+        for (var, coeff) in other.linear_terms().into_iter().filter(|(x, _)| *x != t) {
+            let slot = wires
+                .iter()
+                .position(|x| *x == var)
+                .or_else(|| (fixed..3).find(|&i| wires[i] == Variable::Zero))?;
+            wires[slot] = var;
+            coeffs[slot] += scale * coeff;
+        }
+        for slot in fixed..3 {
+            if coeffs[slot].is_zero() {
+                wires[slot] = Variable::Zero;
+            }
+        }
+
+        Some(Self {
+            q_m: self.q_m,
+            q_l: coeffs[0],
+            q_r: coeffs[1],
+            q_o: coeffs[2],
+            q_c: self.q_c + scale * other.q_c,
+            q_lookup: F::zero(),
+            wires,
+            index: self.index,
+            pi: self.pi,
+        })
+    }
+}
+
+/// Returns the gates at which each variable is wired, once per wire.
+fn variable_uses<F: Field>(gates: &[Gate<F>]) -> HashMap<Variable, Vec<usize>> {
+    let mut uses: HashMap<Variable, Vec<usize>> = HashMap::new();
+    for (i, gate) in gates.iter().enumerate() {
+        for var in gate.wires.into_iter().filter(|x| *x != Variable::Zero) {
+            uses.entry(var).or_default().push(i);
+        }
+    }
+
+    uses
+}
+
+/// Removes trivial gates and gates whose output is used nowhere else, until
+/// there are none.
+///
+/// Removing a gate drops a use of each of its wires. Only the gates whose
+/// output is left with a single use can become dead, so they are revisited
+/// from a worklist rather than scanning all gates again.
+fn remove_dead_gates<F: Field>(gates: &mut Vec<Gate<F>>) -> usize {
+    let mut counts: HashMap<Variable, usize> = HashMap::new();
+    let mut producers: HashMap<Variable, Vec<usize>> = HashMap::new();
+    for (i, gate) in gates.iter().enumerate() {
+        for var in gate.wires.into_iter().filter(|x| *x != Variable::Zero) {
+            *counts.entry(var).or_default() += 1;
+        }
+        if let Some(var) = gate.output() {
+            producers.entry(var).or_default().push(i);
+        }
+    }
+
+    let mut removed = vec![false; gates.len()];
+    let mut worklist = (0..gates.len()).rev().collect::<Vec<_>>();
+    while let Some(i) = worklist.pop() {
+        let gate = &gates[i];
+        let dead = !gate.pi
+            && gate.q_lookup.is_zero()
+            && gate.output().is_some_and(|var| counts[&var] == 1);
+        if removed[i] || !(dead || gate.is_trivial()) {
+            continue;
+        }
+
+        removed[i] = true;
+        for var in gate.wires.into_iter().filter(|x| *x != Variable::Zero) {
+            let count = counts.get_mut(&var).expect("wires are counted");
+            *count -= 1;
+            if *count == 1 {
+                worklist.extend(producers.get(&var).into_iter().flatten());
+            }
+        }
+    }
+
+    let mut removed_iter = removed.iter();
+    gates.retain(|_| !removed_iter.next().expect("one flag per gate"));
+
+    removed.into_iter().filter(|x| *x).count()
+}
+
+/// Removes duplicated gates. If two gates compute their outputs from the
+/// same inputs alike, the second output is replaced by the first one.
+fn deduplicate_gates<F: Field>(gates: &mut Vec<Gate<F>>) -> usize {
+    fn resolve(renames: &HashMap<Variable, Variable>, mut var: Variable) -> Variable {
+        while let Some(&x) = renames.get(&var) {
+            var = x;
+        }
+        var
+    }
+
+    let before = gates.len();
+    let mut renames = HashMap::new();
+    let mut identical = HashSet::new();
+    let mut outputs = HashMap::new();
+    let mut deduplicated = Vec::with_capacity(before);
+    for mut gate in gates.drain(..) {
+        gate.wires = gate.wires.map(|var| resolve(&renames, var));
+        if gate.pi {
+            deduplicated.push(gate);
+            continue;
+        }
+        if !identical.insert((gate.selectors(), gate.wires)) {
+            continue;
+        }
+        if let Some(var) = gate.output() {
+            let key = (gate.selectors(), gate.wires[0], gate.wires[1]);
+            if let Some(&x) = outputs.get(&key) {
+                // the first output may be renamed already, which must not
+                // rename it back
+                let x = resolve(&renames, x);
+                if x != var {
+                    renames.insert(var, x);
+                }
+                continue;
+            }
+            outputs.insert(key, var);
+        }
+        deduplicated.push(gate);
+    }
+
+    for gate in deduplicated.iter_mut() {
+        gate.wires = gate.wires.map(|var| resolve(&renames, var));
+    }
+    *gates = deduplicated;
+
+    before - gates.len()
+}
+
+/// Merges each linear gate into the only other gate using its output.
+fn merge_linear_gates<F: Field>(gates: &mut Vec<Gate<F>>) -> usize {
+    let uses = variable_uses(gates);
+    let mut touched = vec![false; gates.len()];
+    let mut merged = vec![false; gates.len()];
+    for i in 0..gates.len() {
+        if touched[i] || gates[i].pi || !gates[i].is_linear() {
+            continue;
+        }
+        let t = match gates[i].output() {
+            Some(t) => t,
+            None => continue,
+        };
+        let j = match *uses[&t].as_slice() {
+            [x, y] if x == i && !touched[y] => y,
+            [x, y] if y == i && !touched[x] => x,
+            _ => continue,
+        };
+        if let Some(gate) = gates[j].substitute(t, &gates[i]) {
+            gates[j] = gate;
+            touched[i] = true;
+            touched[j] = true;
+            merged[i] = true;
+        }
+    }
+
+    let mut merged = merged.into_iter();
+    gates.retain(|_| !merged.next().unwrap_or_default());
+
+    touched.iter().filter(|x| **x).count() / 2
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Optimizes the synthesized gates: removes gates whose outputs are never
+    /// used and which take no public input, merges chained linear gates and
+    /// removes duplicated gates. The witness, if any, is rewired alike.
+    pub fn optimize(&mut self) -> OptimizationReport {
+        let setup = self.composer.setup
+            .as_mut()
+            .expect("constraint system records no gate structure");
+        let wires = setup.perm.gate_wires(setup.n);
+        let mut gates = wires
+            .into_iter()
+            .enumerate()
+            .map(|(i, wires)| Gate {
+                q_m: setup.q_m[i],
+                q_l: setup.q_l[i],
+                q_r: setup.q_r[i],
+                q_o: setup.q_o[i],
+                q_c: setup.q_c[i],
+                q_lookup: setup.q_lookup[i],
+                wires,
+                index: i,
+                pi: setup.pp.contains(i),
+            })
+            .collect_vec();
+
+        let mut report = OptimizationReport {
+            before: gates.len(),
+            ..Default::default()
+        };
+        loop {
+            let len = gates.len();
+            report.dead += remove_dead_gates(&mut gates);
+            report.deduplicated += deduplicate_gates(&mut gates);
+            report.merged += merge_linear_gates(&mut gates);
+            if gates.len() == len {
+                break;
+            }
+        }
+        report.after = gates.len();
+
+        let wires = gates.iter().map(|gate| gate.wires).collect_vec();
+        let indices = gates.iter().map(|gate| gate.index).collect_vec();
+        setup.n = gates.len();
+        setup.q_m = gates.iter().map(|gate| gate.q_m).collect();
+        setup.q_l = gates.iter().map(|gate| gate.q_l).collect();
+        setup.q_r = gates.iter().map(|gate| gate.q_r).collect();
+        setup.q_o = gates.iter().map(|gate| gate.q_o).collect();
+        setup.q_c = gates.iter().map(|gate| gate.q_c).collect();
+        setup.q_lookup = gates.iter().map(|gate| gate.q_lookup).collect();
+        setup.perm.remap(&wires);
+        setup.pp = PublicPositions::new();
+        gates
+            .iter()
+            .positions(|gate| gate.pi)
+            .for_each(|i| setup.pp.add_input(i));
+        setup.namespaces.retain_gates(&indices);
+        #[cfg(feature = "trace")]
+        {
+            setup.backtrace = indices.iter().map(|&i| setup.backtrace[i].clone()).collect();
+        }
+
+        if let Some(proving) = &mut self.composer.proving {
+            let mut pi = PublicInputs::new();
+            for (i, gate) in gates.iter().enumerate().filter(|(_, gate)| gate.pi) {
+                let value = proving.pi.get(gate.index).copied().unwrap_or_default();
+                pi.add_input(i, value);
+            }
+            proving.n = gates.len();
+            proving.w_l = wires.iter().map(|wire| wire[0]).collect();
+            proving.w_r = wires.iter().map(|wire| wire[1]).collect();
+            proving.w_o = wires.iter().map(|wire| wire[2]).collect();
+            proving.pi = pi;
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+
+    use crate::{batch_test_field, error::Error, plonkup::Circuit};
+    use super::*;
+
+    /// Makes `(a + b)^2 + 2c + 1` public with `a` and `c` in table, with a
+    /// repeated sum, a repeated lookup, a chain of linear gates and an unused
+    /// sum.
+    #[derive(Debug, Default)]
+    struct RedundantCircuit {
+        a: u64,
+        b: u64,
+        c: u64,
+        d: u64,
+    }
+
+    impl<F: Field> Circuit<F> for RedundantCircuit {
+        const OPTIMIZE: bool = true;
+
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            let a = cs.assign_variable(self.a.into());
+            let b = cs.assign_variable(self.b.into());
+            let c = cs.assign_variable(self.c.into());
+            let d = cs.assign_variable(self.d.into());
+            let x = cs.add_gate(&a.into(), &b.into());
+            let y = cs.add_gate(&a.into(), &b.into());
+            let p = cs.mul_gate(&x.into(), &y.into());
+            let t = cs.linear_transform_gate(
+                &c.into(),
+                &LTVariable::zero(),
+                F::from(2u64),
+                F::zero(),
+                F::one(),
+            );
+            let q = cs.add_gate(&p.into(), &t.into());
+            cs.lookup_constrain(&a.into());
+            cs.lookup_constrain(&a.into());
+            cs.lookup_constrain(&c.into());
+            cs.set_variable_public(&q.into());
+            cs.add_gate(&c.into(), &d.into());
+
+            Ok(())
+        }
+    }
+
+    fn test_optimize<F: Field>() {
+        let circuit = RedundantCircuit { a: 1, b: 2, c: 3, d: 4 };
+        let mut cs = ConstraintSystem::new_with_witness((0..8u64).map(F::from).into());
+        circuit.synthesize(&mut cs).unwrap();
+        let report = cs.optimize();
+        assert_eq!(
+            report,
+            OptimizationReport { before: 10, after: 5, dead: 1, merged: 2, deduplicated: 2 },
+        );
+        assert_eq!(report.to_string(), "gates: 10 -> 5 (1 dead, 2 merged, 2 deduplicated)");
+
+        let setup = cs.composer.setup.expect("gate structure is recorded");
+        let proving = cs.composer.proving.expect("witness is recorded");
+        check_arith_gate(&setup, &proving, &[F::from(16u64)]);
+        assert_eq!(setup.profile().get("/").map(|cost| cost.gates), Some(5));
+    }
+
+    fn test_remove_dead_chain<F: Field>() {
+        let mut cs = ConstraintSystem::<F>::new_with_witness(Default::default());
+        let a = cs.assign_variable(F::from(2u64));
+        let b = cs.assign_variable(F::from(3u64));
+        cs.set_variable_public(&a.into());
+        // each product only becomes dead once the next one is removed
+        let x = cs.mul_gate(&a.into(), &b.into());
+        let y = cs.mul_gate(&x.into(), &b.into());
+        cs.mul_gate(&y.into(), &b.into());
+
+        let report = cs.optimize();
+        assert_eq!(report.dead, 3);
+        assert_eq!(report.after, 1);
+    }
+
+    fn test_deduplicate_crossed_outputs<F: Field>() {
+        let mut cs = ConstraintSystem::<F>::new_with_witness(Default::default());
+        let a = cs.assign_variable(F::from(1u64));
+        let b = cs.assign_variable(F::from(5u64));
+        let c = cs.assign_variable(F::from(2u64));
+        let d = cs.assign_variable(F::from(3u64));
+        let v = cs.assign_variable(F::from(6u64));
+        let x = cs.assign_variable(F::from(6u64));
+        let sum = || Selectors::new().with_left(F::one()).with_right(F::one()).with_out(-F::one());
+        let product = || Selectors::new().with_mul(F::one()).with_out(-F::one());
+        // v is renamed to x by the third gate, the fourth gate must not
+        // rename x back to v
+        cs.arith_constrain(a, b, v, sum(), None);
+        cs.arith_constrain(c, d, x, product(), None);
+        cs.arith_constrain(c, d, v, product(), None);
+        cs.arith_constrain(a, b, x, sum(), None);
+        cs.set_variable_public(&v.into());
+        cs.set_variable_public(&x.into());
+
+        let report = cs.optimize();
+        assert_eq!(report.deduplicated, 2);
+        assert_eq!(report.after, 4);
+
+        let setup = cs.composer.setup.expect("gate structure is recorded");
+        let proving = cs.composer.proving.expect("witness is recorded");
+        check_arith_gate(&setup, &proving, &[F::from(6u64), F::from(6u64)]);
+    }
+
+    fn test_optimized_circuit<F: Field>() {
+        let circuit = RedundantCircuit { a: 1, b: 2, c: 3, d: 4 };
+        let prover = MockProver::run(Default::default(), circuit, (0..8u64).map(F::from), &[F::from(16u64)]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = RedundantCircuit { a: 2, b: 2, c: 1, d: 0 };
+        let prover = MockProver::run(Default::default(), circuit, (0..8u64).map(F::from), &[F::from(19u64)]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = RedundantCircuit { a: 2, b: 2, c: 1, d: 0 };
+        let prover = MockProver::run(Default::default(), circuit, (0..8u64).map(F::from), &[F::from(20u64)]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    batch_test_field!(
+        Bn254,
+        [
+            test_optimize,
+            test_remove_dead_chain,
+            test_deduplicate_crossed_outputs,
+            test_optimized_circuit
+        ],
+        []
+    );
+}
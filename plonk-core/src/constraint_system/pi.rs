@@ -45,6 +45,11 @@ impl PublicPositions {
     pub fn get_pos(&self) -> impl Iterator<Item = &usize> {
         self.0.iter()
     }
+
+    /// Returns whether the gate at `pos` takes a public input.
+    pub fn contains(&self, pos: usize) -> bool {
+        self.0.contains(&pos)
+    }
 }
 
 ///  Public Inputs
@@ -102,6 +107,11 @@ impl<F: Field> PublicInputs<F> {
     pub fn get_vals(&self) -> impl Iterator<Item = &F> {
         self.0.values()
     }
+
+    /// Returns the PI value at `pos`, if any.
+    pub fn get(&self, pos: usize) -> Option<&F> {
+        self.0.get(&pos)
+    }
 }
 
 // #[cfg(test)]
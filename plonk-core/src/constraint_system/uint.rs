@@ -0,0 +1,427 @@
+//! Fixed-width Unsigned Integers
+
+use ark_ff::PrimeField;
+use itertools::Itertools;
+
+use super::{Boolean, ConstraintSystem, LTVariable, Variable};
+
+/// Unsigned integer of `BITS` bits in circuit, stored as its little-endian
+/// [`Boolean`]s, so bitwise operations, shifts and rotations are cheap and
+/// arithmetic recomposes the bits into a [`Variable`] first.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UInt<const BITS: usize> {
+    bits: Vec<Boolean>,
+}
+
+///
+pub type UInt8 = UInt<8>;
+///
+pub type UInt32 = UInt<32>;
+///
+pub type UInt64 = UInt<64>;
+
+impl<const BITS: usize> UInt<BITS> {
+    fn check_width() {
+        assert!(BITS > 0 && BITS <= 64, "width of integer must be in [1, 64]");
+    }
+
+    /// Builds an integer from little-endian bits, which must have been
+    /// constrained as booleans.
+    pub fn from_bits_le(bits: &[Boolean]) -> Self {
+        Self::check_width();
+        assert_eq!(bits.len(), BITS, "invalid bits length");
+
+        Self { bits: bits.to_vec() }
+    }
+
+    /// Builds an integer from big-endian bits, which must have been
+    /// constrained as booleans.
+    pub fn from_bits_be(bits: &[Boolean]) -> Self {
+        Self::check_width();
+        assert_eq!(bits.len(), BITS, "invalid bits length");
+
+        Self { bits: bits.iter().rev().copied().collect() }
+    }
+
+    ///
+    pub fn to_bits_le(&self) -> Vec<Boolean> {
+        self.bits.clone()
+    }
+
+    ///
+    pub fn to_bits_be(&self) -> Vec<Boolean> {
+        self.bits.iter().rev().copied().collect()
+    }
+
+    /// Shifts left by `by` bits, dropping the high bits.
+    pub fn shl(&self, by: usize) -> Self {
+        let by = by.min(BITS);
+        let bits = (0..by)
+            .map(|_| Boolean(Variable::Zero))
+            .chain(self.bits[..BITS - by].iter().copied())
+            .collect();
+
+        Self { bits }
+    }
+
+    /// Shifts right by `by` bits, dropping the low bits.
+    pub fn shr(&self, by: usize) -> Self {
+        let by = by.min(BITS);
+        let bits = self.bits[by..]
+            .iter()
+            .copied()
+            .chain((0..by).map(|_| Boolean(Variable::Zero)))
+            .collect();
+
+        Self { bits }
+    }
+
+    /// Rotates left by `by` bits.
+    pub fn rotl(&self, by: usize) -> Self {
+        let mut bits = self.bits.clone();
+        bits.rotate_right(by % BITS);
+
+        Self { bits }
+    }
+
+    /// Rotates right by `by` bits.
+    pub fn rotr(&self, by: usize) -> Self {
+        let mut bits = self.bits.clone();
+        bits.rotate_left(by % BITS);
+
+        Self { bits }
+    }
+
+    /// Returns the constant `value`, whose bits are fixed by gates.
+    pub fn constant<F: PrimeField>(cs: &mut ConstraintSystem<F>, value: u64) -> Self {
+        Self::check_width();
+
+        let bits = (0..BITS)
+            .map(|i| cs.constant_boolean((value >> i) & 1 == 1))
+            .collect();
+
+        Self { bits }
+    }
+
+    /// Assigns `value`, which is range checked to fit in `BITS` bits.
+    pub fn new<F: PrimeField>(cs: &mut ConstraintSystem<F>, value: u64) -> Self {
+        let x = cs.assign_variable(F::from(value));
+        Self::from_variable(cs, &x.into())
+    }
+
+    /// Decomposes `x`, which is range checked to fit in `BITS` bits.
+    pub fn from_variable<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        x: &LTVariable<F>,
+    ) -> Self {
+        Self::check_width();
+
+        Self { bits: cs.to_bits_le(x, BITS) }
+    }
+
+    /// Recomposes the bits into a variable.
+    pub fn to_variable<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>) -> Variable {
+        cs.bits_le_constrain(&self.bits)
+    }
+
+    /// Constrains both integers to be equal.
+    pub fn enforce_equal<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) {
+        for (x, y) in self.bits.iter().zip(&other.bits) {
+            cs.equal_constrain(&(*x).into(), &(*y).into());
+        }
+    }
+
+    fn bitwise<F, O>(&self, cs: &mut ConstraintSystem<F>, other: &Self, op: O) -> Self
+    where
+        F: PrimeField,
+        O: Fn(&mut ConstraintSystem<F>, Boolean, Boolean) -> Boolean,
+    {
+        let bits = self.bits
+            .iter()
+            .zip(&other.bits)
+            .map(|(x, y)| op(cs, *x, *y))
+            .collect();
+
+        Self { bits }
+    }
+
+    ///
+    pub fn xor<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        self.bitwise(cs, other, ConstraintSystem::xor_gate)
+    }
+
+    ///
+    pub fn and<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        self.bitwise(cs, other, ConstraintSystem::and_gate)
+    }
+
+    ///
+    pub fn or<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        self.bitwise(cs, other, ConstraintSystem::or_gate)
+    }
+
+    /// Calculates `(NOT self) AND other`.
+    pub fn not_and<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        self.bitwise(cs, other, ConstraintSystem::not_and_gate)
+    }
+
+    /// Decomposes `x` into `num_bits` bits and keeps the lowest `BITS`.
+    fn truncate<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        x: &LTVariable<F>,
+        num_bits: usize,
+    ) -> Self {
+        let mut bits = cs.to_bits_le(x, num_bits);
+        bits.truncate(BITS);
+
+        Self { bits }
+    }
+
+    fn sum<F: PrimeField>(cs: &mut ConstraintSystem<F>, operands: &[Self]) -> Variable {
+        let vars = operands
+            .iter()
+            .map(|operand| operand.to_variable(cs))
+            .collect_vec();
+
+        vars.into_iter()
+            .reduce(|acc, var| cs.add_gate(&acc.into(), &var.into()))
+            .unwrap_or(Variable::Zero)
+    }
+
+    /// Adds all operands modulo `2^BITS`.
+    pub fn wrapping_add_many<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        operands: &[Self],
+    ) -> Self {
+        Self::wrapping_add_many_with_constant(cs, operands, 0)
+    }
+
+    /// Adds all operands and a constant modulo `2^BITS`. The constant costs
+    /// no gate.
+    pub fn wrapping_add_many_with_constant<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        operands: &[Self],
+        constant: u64,
+    ) -> Self {
+        assert!(!operands.is_empty(), "no operands to add");
+
+        // the sum of n terms has at most log2(n) more bits
+        let terms = operands.len() + usize::from(constant != 0);
+        let carry_bits = terms.next_power_of_two().trailing_zeros() as usize;
+        let sum = Self::sum(cs, operands).linear_transform(F::one(), F::from(constant));
+
+        Self::truncate(cs, &sum, BITS + carry_bits)
+    }
+
+    /// Adds modulo `2^BITS`.
+    pub fn wrapping_add<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        Self::wrapping_add_many(cs, &[self.clone(), other.clone()])
+    }
+
+    /// Adds and constrains the sum not to overflow.
+    pub fn checked_add<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let sum = Self::sum(cs, &[self.clone(), other.clone()]);
+
+        Self::from_variable(cs, &sum.into())
+    }
+
+    /// Subtracts modulo `2^BITS`.
+    pub fn wrapping_sub<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let x = self.to_variable(cs);
+        let y = other.to_variable(cs);
+        // x - y + 2^BITS is positive and has at most BITS + 1 bits
+        let difference = cs.sub_gate(&x.into(), &y.into());
+        let shifted = difference.linear_transform(F::one(), F::from(2u64).pow([BITS as u64]));
+
+        Self::truncate(cs, &shifted, BITS + 1)
+    }
+
+    /// Subtracts and constrains the difference not to underflow.
+    pub fn checked_sub<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let x = self.to_variable(cs);
+        let y = other.to_variable(cs);
+        let difference = cs.sub_gate(&x.into(), &y.into());
+
+        Self::from_variable(cs, &difference.into())
+    }
+
+    /// Multiplies modulo `2^BITS`.
+    pub fn wrapping_mul<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let x = self.to_variable(cs);
+        let y = other.to_variable(cs);
+        let product = cs.mul_gate(&x.into(), &y.into());
+
+        Self::truncate(cs, &product.into(), 2 * BITS)
+    }
+
+    /// Multiplies and constrains the product not to overflow.
+    pub fn checked_mul<F: PrimeField>(&self, cs: &mut ConstraintSystem<F>, other: &Self) -> Self {
+        let x = self.to_variable(cs);
+        let y = other.to_variable(cs);
+        let product = cs.mul_gate(&x.into(), &y.into());
+
+        Self::from_variable(cs, &product.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::PrimeField;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::Bn254;
+    use ark_bls12_381::Bls12_381;
+
+    use crate::{batch_test_field, constraint_system::test_gate_constraints};
+    use super::*;
+
+    fn test_bitwise<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let x_value = rng.gen::<u32>();
+                let y_value = rng.gen::<u32>();
+                let x = UInt32::new(cs, x_value as u64);
+                let y = UInt32::new(cs, y_value as u64);
+                let c = UInt32::constant(cs, 0xdeadbeef);
+
+                [
+                    (x.xor(cs, &y), x_value ^ y_value),
+                    (x.and(cs, &y), x_value & y_value),
+                    (x.or(cs, &y), x_value | y_value),
+                    (x.not_and(cs, &y), !x_value & y_value),
+                    (x.xor(cs, &c), x_value ^ 0xdeadbeef),
+                    (x.shl(5), x_value << 5),
+                    (x.shr(7), x_value >> 7),
+                    (x.shr(40), 0),
+                    (x.rotl(9), x_value.rotate_left(9)),
+                    (x.rotr(13), x_value.rotate_right(13)),
+                ]
+                .into_iter()
+                .map(|(z, expected)| (z.to_variable(cs).into(), F::from(expected)))
+                .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_bits_conversion<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = UInt8::new(cs, 0b1000_0110);
+                let bits = x.to_bits_le();
+                assert_eq!(UInt8::from_bits_le(&bits), x);
+                assert_eq!(UInt8::from_bits_be(&x.to_bits_be()), x);
+                let reversed = UInt8::from_bits_be(&bits);
+
+                vec![
+                    (bits[1].into(), F::one()),
+                    (bits[0].into(), F::zero()),
+                    (reversed.to_variable(cs).into(), F::from(0b0110_0001u64)),
+                ]
+            },
+            &[],
+        )
+    }
+
+    fn test_arithmetic<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let rng = &mut test_rng();
+                let x_value = rng.gen::<u64>();
+                let y_value = rng.gen::<u64>();
+                let z_value = rng.gen::<u64>();
+                let x = UInt64::new(cs, x_value);
+                let y = UInt64::new(cs, y_value);
+                let z = UInt64::new(cs, z_value);
+                let small = UInt64::new(cs, (x_value >> 40).min(y_value >> 40));
+
+                [
+                    (x.wrapping_add(cs, &y), x_value.wrapping_add(y_value)),
+                    (
+                        UInt64::wrapping_add_many(cs, &[x.clone(), y.clone(), z]),
+                        x_value.wrapping_add(y_value).wrapping_add(z_value),
+                    ),
+                    (
+                        UInt64::wrapping_add_many_with_constant(cs, &[x.clone(), y.clone()], u64::MAX),
+                        x_value.wrapping_add(y_value).wrapping_add(u64::MAX),
+                    ),
+                    (x.wrapping_sub(cs, &y), x_value.wrapping_sub(y_value)),
+                    (y.wrapping_sub(cs, &x), y_value.wrapping_sub(x_value)),
+                    (x.wrapping_mul(cs, &y), x_value.wrapping_mul(y_value)),
+                    (small.checked_add(cs, &small), (x_value >> 40).min(y_value >> 40) * 2),
+                    (x.checked_sub(cs, &small), x_value - (x_value >> 40).min(y_value >> 40)),
+                    (small.checked_mul(cs, &small), (x_value >> 40).min(y_value >> 40).pow(2)),
+                ]
+                .into_iter()
+                .map(|(z, expected)| (z.to_variable(cs).into(), F::from(expected)))
+                .collect()
+            },
+            &[],
+        )
+    }
+
+    fn test_new_out_of_range<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                UInt8::new(cs, 256);
+
+                vec![]
+            },
+            &[] as &[F],
+        )
+    }
+
+    fn test_checked_add_overflow<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = UInt8::new(cs, 200);
+                let y = UInt8::new(cs, 100);
+                x.checked_add(cs, &y);
+
+                vec![]
+            },
+            &[] as &[F],
+        )
+    }
+
+    fn test_checked_sub_underflow<F: PrimeField>() {
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let x = UInt32::new(cs, 1);
+                let y = UInt32::new(cs, 2);
+                x.checked_sub(cs, &y);
+
+                vec![]
+            },
+            &[] as &[F],
+        )
+    }
+
+    batch_test_field!(
+        Bn254,
+        [
+            test_bitwise,
+            test_bits_conversion,
+            test_arithmetic
+        ],
+        [
+            test_new_out_of_range,
+            test_checked_add_overflow,
+            test_checked_sub_underflow
+        ]
+    );
+
+    batch_test_field!(
+        Bls12_381,
+        [
+            test_bitwise,
+            test_bits_conversion,
+            test_arithmetic
+        ],
+        [
+            test_new_out_of_range,
+            test_checked_add_overflow,
+            test_checked_sub_underflow
+        ]
+    );
+}
@@ -74,13 +74,10 @@ impl<F: Field> LTVariable<F> {
 
     ///
     pub fn linear_transform(&self, coeff: F, offset: F) -> Self {
-        let coeff = self.coeff * coeff;
-        let offset = self.offset * coeff + offset;
-        
         Self {
             var: self.var,
-            coeff,
-            offset,
+            coeff: self.coeff * coeff,
+            offset: self.offset * coeff + offset,
         }
     }
 }
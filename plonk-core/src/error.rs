@@ -61,6 +61,17 @@ pub enum Error {
     // Plonkup circuit errors
     /// Element is not found in lookup table.
     ElementNotIndexed,
+    /// A witness value is not given when proving.
+    MissingWitness,
+    /// The gate structure synthesized when proving differs from the one of
+    /// the prover key.
+    StructureMismatch,
+    /// The shape parameters of a circuit are invalid, e.g. a circuit
+    /// spending no inputs.
+    InvalidCircuitShape,
+    /// A value to allocate in circuit doesn't have the shape of its type,
+    /// e.g. an authentication path of another height.
+    InvalidValueShape,
 }
 
 impl From<ark_poly_commit::error::Error> for Error {
@@ -124,6 +135,12 @@ impl std::fmt::Display for Error {
             Self::ElementNotIndexed => {
                 write!(f, "element not found in lookup table")
             }
+            Self::MissingWitness => write!(f, "witness value is missing"),
+            Self::StructureMismatch => {
+                write!(f, "gate structure differs from the compiled circuit")
+            }
+            Self::InvalidCircuitShape => write!(f, "circuit shape is invalid"),
+            Self::InvalidValueShape => write!(f, "value shape is invalid"),
         }
     }
 }
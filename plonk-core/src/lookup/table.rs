@@ -52,7 +52,7 @@ impl<F: Field> LookupTable<F> {
     /// Takes in a table, which is a vector of slices containing all elements,
     /// turns them into multiset for c and extends the length to `n`, 
     pub(crate) fn into_multiset(self, n: usize) -> MultiSet<F> {
-        let mut t = MultiSet::from_iter(self.0.into_iter());
+        let mut t = MultiSet::from_iter(self.0);
         assert!(t.len() < n, "table size is equal or larger than n");
 
         t.pad_with_zero(n);
@@ -23,7 +23,7 @@ use crate::{constraint_system::Variable, util::poly_from_evals};
 /// Left(1) signifies that this wire belongs to the first gate and is the left
 /// wire
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum WireData {
+pub(crate) enum WireData {
     /// Left Wire of n'th gate
     Left(usize),
     /// Right Wire of n'th gate
@@ -97,7 +97,47 @@ impl Permutation {
         };
         self.0[i].push(wire_data);
     }
-    
+
+    /// Replaces the wires of all gates by `wires`, the left, right and output
+    /// [`Variable`]s of each gate.
+    pub(crate) fn remap(&mut self, wires: &[[Variable; 3]]) {
+        self.0.iter_mut().for_each(|wire_datas| wire_datas.clear());
+        for (i, &[w_l, w_r, w_o]) in wires.iter().enumerate() {
+            self.add_variables_to_map(w_l, w_r, w_o, i);
+        }
+    }
+
+    /// Returns each mapped [`Variable`] with the wires it occupies.
+    pub(crate) fn variable_wires(&self) -> impl Iterator<Item = (Variable, &[WireData])> {
+        // index 1 is reserved and never mapped
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(i, wire_datas)| {
+                let var = if i == 0 { Variable::Zero } else { Variable::Var(i - 2) };
+                (var, wire_datas.as_slice())
+            })
+    }
+
+    /// Returns the left, right and output [`Variable`]s of each of the first
+    /// `n` gates, as mapped by [`Permutation::add_variables_to_map`].
+    pub(crate) fn gate_wires(&self, n: usize) -> Vec<[Variable; 3]> {
+        let mut wires = vec![[Variable::Zero; 3]; n];
+        for (var, wire_datas) in self.variable_wires() {
+            for wire_data in wire_datas {
+                match *wire_data {
+                    WireData::Left(i) if i < n => wires[i][0] = var,
+                    WireData::Right(i) if i < n => wires[i][1] = var,
+                    WireData::Output(i) if i < n => wires[i][2] = var,
+                    _ => {}
+                }
+            }
+        }
+
+        wires
+    }
+
     /// Performs shift by one permutation and computes `sigma1`, `sigma2` and
     /// `sigma3` permutations from the variable maps.
     fn compute_sigma_permutations(
@@ -6,7 +6,7 @@
 
 //! Tools & traits for PLONK circuits
 
-use std::{marker::PhantomData, rc::Rc};
+use std::{borrow::Borrow, marker::PhantomData, rc::Rc};
 use ark_ff::{FftField, Field};
 use ark_poly::EvaluationDomain;
 use rand_core::{CryptoRng, RngCore};
@@ -14,7 +14,7 @@ use rand_core::{CryptoRng, RngCore};
 use crate::{
     commitment::HomomorphicCommitment,
     error::{to_pc_error, Error},
-    constraint_system::ConstraintSystem,
+    constraint_system::{ConstraintSystem, SetupComposer},
     proof_system::{
         Proof,
         ProverKey, ExtendedProverKey, VerifierKey,
@@ -29,147 +29,71 @@ use crate::{
 /// the capabilities of automatically being able to generate, and verify proofs
 /// as well as compile the circuit.
 ///
+/// Witness values are only read when proving, see
+/// [`ConstraintSystem::assign_witness`], so the circuit given to
+/// [`ZKTPlonkup::compile`] carries its shape parameters alone.
+///
 /// # Example
 ///
-/// ```rust,no_run
-/// use ark_bls12_381::{Bls12_381, Fr as BlsScalar};
-/// use ark_ec::PairingEngine;
-/// use ark_ec::models::twisted_edwards_extended::GroupAffine;
-/// use ark_ec::{TEModelParameters, AffineCurve, ProjectiveCurve};
-/// use ark_ed_on_bls12_381::{
-///     EdwardsAffine as JubJubAffine, EdwardsParameters as JubJubParameters,
-///     EdwardsProjective as JubJubProjective, Fr as JubJubScalar,
+/// ```rust
+/// use std::rc::Rc;
+/// use ark_bls12_381::{Bls12_381, Fr};
+/// use ark_ff::Field;
+/// use ark_poly::GeneralEvaluationDomain;
+/// use ark_poly_commit::PolynomialCommitment;
+/// use ark_std::test_rng;
+/// use plonk_core::{
+///     commitment::KZG10,
+///     constraint_system::ConstraintSystem,
+///     error::Error,
+///     plonkup::{Circuit, ZKTPlonkup},
+///     transcript::MerlinTranscript,
 /// };
-/// use ark_ff::{FftField, PrimeField, BigInteger, ToConstraintField};
-/// use plonk_core::circuit::{Circuit, verify_proof};
-/// use plonk_core::constraint_system::ConstraintSystem;
-/// use plonk_core::error::{to_pc_error,Error};
-/// use ark_poly::polynomial::univariate::DensePolynomial;
-/// use ark_poly_commit::{PolynomialCommitment, sonic_pc::SonicKZG10};
-/// use plonk_core::prelude::*;
-/// use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-/// use num_traits::{Zero, One};
-/// use rand_core::OsRng;
-///
-/// fn main() -> Result<(), Error> {
-/// // Implements a circuit that checks:
-/// // 1) a + b = c where C is a PI
-/// // 2) a <= 2^6
-/// // 3) b <= 2^5
-/// // 4) a * b = d where D is a PI
-/// // 5) JubJub::GENERATOR * e(JubJubScalar) = f where F is a PI
-/// #[derive(derivative::Derivative)]
-///    #[derivative(Debug(bound = ""), Default(bound = ""))]
-/// pub struct TestCircuit<F, P>
-/// where
-///     F: PrimeField,
-///     P: TEModelParameters<BaseField = F>,
-/// {
-///        a: F,
-///        b: F,
-///        c: F,
-///        d: F,
-///        e: P::ScalarField,
-///        f: GroupAffine<P>,
-///    }
-///
-/// impl<F, P> Circuit<F, P> for TestCircuit<F, P>
-/// where
-///     F: PrimeField,
-///     P: TEModelParameters<BaseField = F>,
-///    {
-///        const CIRCUIT_ID: [u8; 32] = [0xff; 32];
 ///
-///        fn gadget(
-///            &mut self,
-///            composer: &mut ConstraintSystem<F, P>,
-///        ) -> Result<(), Error> {
-///            let a = composer.add_input(self.a);
-///            let b = composer.add_input(self.b);
-///            let zero = composer.zero_var();
-///
-///            // Make first constraint a + b = c (as public input)
-///            composer.arithmetic_gate(|gate| {
-///                gate.witness(a, b, Some(zero))
-///                    .add(F::one(), F::one())
-///                    .pi(-self.c)
-///            });
-///
-///            // Check that a and b are in range
-///            composer.range_gate(a, 1 << 6);
-///            composer.range_gate(b, 1 << 5);
-///            // Make second constraint a * b = d
-///            composer.arithmetic_gate(|gate| {
-///                gate.witness(a, b, Some(zero)).mul(F::one()).pi(-self.d)
-///            });
-///            let e = composer
-///                .add_input(from_embedded_curve_scalar::<F, P>(self.e));
-///            let (x, y) = P::AFFINE_GENERATOR_COEFFS;
-///            let generator = GroupAffine::new(x, y);
-///            let scalar_mul_result =
-///                composer.fixed_base_scalar_mul(e, generator);
-///            // Apply the constrain
-///            composer.assert_equal_public_point(scalar_mul_result, self.f);
-///            Ok(())
-///        }
+/// // Checks that x^3 + x + 5 = y, where y is a public input and x is only
+/// // known to the prover and looked up in the table.
+/// #[derive(Default)]
+/// struct CubicCircuit {
+///     x: Option<u64>,
+/// }
 ///
-///        fn padded_circuit_size(&self) -> usize {
-///            1 << 11
-///        }
-///    }
+/// impl<F: Field> Circuit<F> for CubicCircuit {
+///     fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+///         let x = cs.assign_witness(|| self.x.map(F::from))?;
+///         cs.lookup_constrain(&x.into());
 ///
-/// // Generate CRS
-/// type PC = SonicKZG10::<Bls12_381,DensePolynomial<BlsScalar>>;
-/// let pp = PC::setup(
-///     1 << 10, None, &mut OsRng
-///  )?;
+///         let x_square = cs.square_gate(&x.into());
+///         let x_cube = cs.mul_gate(&x_square.into(), &x.into());
+///         let y = cs.add_gate(&x_cube.into(), &x.linear_transform(F::one(), F::from(5u64)));
+///         cs.set_variable_public(&y.into());
 ///
-/// let mut circuit = TestCircuit::<BlsScalar, JubJubParameters>::default();
-/// // Compile the circuit
-/// let (pk_p, (vk, _pi_pos)) = circuit.compile::<PC>(&pp)?;
+///         Ok(())
+///     }
+/// }
 ///
-/// let (x, y) = JubJubParameters::AFFINE_GENERATOR_COEFFS;
-/// let generator: GroupAffine<JubJubParameters> = GroupAffine::new(x, y);
-/// let point_f_pi: GroupAffine<JubJubParameters> = AffineCurve::mul(
-///     &generator,
-///     JubJubScalar::from(2u64).into_repr(),
-/// )
-/// .into_affine();
-/// // Prover POV
-/// let (proof, pi) = {
-///     let mut circuit: TestCircuit<BlsScalar, JubJubParameters> = TestCircuit {
-///         a: BlsScalar::from(20u64),
-///         b: BlsScalar::from(5u64),
-///         c: BlsScalar::from(25u64),
-///         d: BlsScalar::from(100u64),
-///         e: JubJubScalar::from(2u64),
-///         f: point_f_pi,
-///     };
-///     circuit.gen_proof::<PC>(&pp, pk_p, b"Test")
-/// }?;
+/// type PC = KZG10<Bls12_381>;
+/// type Plonkup = ZKTPlonkup<Fr, GeneralEvaluationDomain<Fr>, PC, MerlinTranscript, CubicCircuit>;
 ///
-/// let verifier_data = VerifierData::new(vk, pi);
-/// // Test serialisation for verifier_data
-/// let mut verifier_data_bytes = Vec::new();
-/// verifier_data.serialize(&mut verifier_data_bytes).unwrap();
+/// fn main() -> Result<(), Error> {
+///     let rng = &mut test_rng();
+///     let pp = PC::setup(1 << 10, None, rng).expect("setup failed");
+///     let table = vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
 ///
-/// let deserialized_verifier_data: VerifierData<BlsScalar, PC> =
-///     VerifierData::deserialize(verifier_data_bytes.as_slice()).unwrap();
+///     // the circuit without witness is enough to compile
+///     let (ck, cvk, pk, epk, vk) = Plonkup::compile(true, &pp, table.clone(), CubicCircuit::default())?;
 ///
-/// assert!(deserialized_verifier_data == verifier_data);
+///     let circuit = CubicCircuit { x: Some(3) };
+///     let proof = Plonkup::prove(&ck, &pk, epk.map(Rc::new), &vk, table, circuit, rng)?;
 ///
-/// // Verifier POV
-/// verify_proof::<BlsScalar, JubJubParameters, PC>(
-///     &pp,
-///     verifier_data.key,
-///     &proof,
-///     &verifier_data.pi,
-///     b"Test",
-/// )
+///     Plonkup::verify(&cvk, &vk, &proof, &[Fr::from(35u64)])
 /// }
 /// ```
-///
-pub trait Circuit<F: Field>: Default {    
+pub trait Circuit<F: Field> {
+    /// Whether the gates are optimized before preprocessing, see
+    /// [`ConstraintSystem::optimize`]. The prover synthesizes the gate
+    /// structure along with the witness to optimize it the same way.
+    const OPTIMIZE: bool = false;
+
     /// Implementation used to fill the composer.
     fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error>;
 }
@@ -205,6 +129,7 @@ where
         extend: bool,
         pp: &PC::UniversalParams,
         table: I,
+        circuit: C,
     ) -> Result<
         (
             PC::CommitterKey,
@@ -217,8 +142,10 @@ where
     > {
         let mut cs = ConstraintSystem::new(true, table.into());
         // Generate circuit constraint
-        let circuit = C::default();
         circuit.synthesize(&mut cs)?;
+        if C::OPTIMIZE {
+            cs.optimize();
+        }
 
         let (ck, cvk) = PC::trim(
             pp,
@@ -234,7 +161,15 @@ where
         Ok((ck, cvk, pk, epk, vk))
     }
 
+    /// Proves `circuit`.
     ///
+    /// Only the wires and values are recorded, unless the circuit is
+    /// optimized, which needs the gate structure to optimize the witness
+    /// alike. The structure costs about as much memory as the witness again,
+    /// so it is checked against the one of the circuit given to
+    /// [`ZKTPlonkup::compile`] only if it is recorded anyway or with debug
+    /// assertions. The check fails with [`Error::StructureMismatch`], e.g. if
+    /// the gates depend on the witness.
     pub fn prove<I: Into<LookupTable<F>>, R: CryptoRng + RngCore>(
         ck: &PC::CommitterKey,
         pk: &ProverKey<F>,
@@ -244,9 +179,23 @@ where
         circuit: C,
         rng: &mut R,
     ) -> Result<Proof<F, D, PC>, Error> {
-        let mut cs = ConstraintSystem::new(false, table.into());
+        let check_structure = C::OPTIMIZE || cfg!(debug_assertions);
+        let mut cs = if check_structure {
+            ConstraintSystem::new_with_witness(table.into())
+        } else {
+            ConstraintSystem::new(false, table.into())
+        };
         // Generate circuit constraint
         circuit.synthesize(&mut cs)?;
+        if C::OPTIMIZE {
+            cs.optimize();
+        }
+        if check_structure {
+            let setup: &SetupComposer<F> = cs.composer.borrow();
+            if setup.digest() != pk.structure_digest {
+                return Err(Error::StructureMismatch);
+            }
+        }
 
         let transcript = &mut T::new("ZKT Plonkup");
         vk.seed_transcript(transcript);
@@ -321,15 +270,49 @@ mod test {
         }
     }
 
-    type ZKTPlonkupInstance<F, PC> = ZKTPlonkup<
+    /// Same as [`TestCircuit`], optimized before preprocessing.
+    #[derive(Debug, Default)]
+    pub struct OptimizedTestCircuit(TestCircuit);
+
+    impl<F: Field> Circuit<F> for OptimizedTestCircuit {
+        const OPTIMIZE: bool = true;
+
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            self.0.synthesize(cs)
+        }
+    }
+
+    /// Same as [`TestCircuit`], with one more gate if `e` is set.
+    #[derive(Debug, Default)]
+    pub struct BranchingTestCircuit<const OPTIMIZE: bool>(TestCircuit);
+
+    impl<F: Field, const OPTIMIZE: bool> Circuit<F> for BranchingTestCircuit<OPTIMIZE> {
+        const OPTIMIZE: bool = OPTIMIZE;
+
+        fn synthesize(self, cs: &mut ConstraintSystem<F>) -> Result<(), Error> {
+            if self.0.e {
+                let e = cs.assign_variable(F::one());
+                cs.boolean_gate(e);
+            }
+
+            self.0.synthesize(cs)
+        }
+    }
+
+    type ZKTPlonkupInstance<F, PC, C> = ZKTPlonkup<
         F,
         GeneralEvaluationDomain<F>,
         PC,
         MerlinTranscript,
-        TestCircuit,
+        C,
     >;
 
-    fn test_full<F: PrimeField, PC: HomomorphicCommitment<F>>() {
+    fn prove_and_verify<F, PC, C>(circuit: C)
+    where
+        F: PrimeField,
+        PC: HomomorphicCommitment<F>,
+        C: Circuit<F> + Default,
+    {
         let table = [F::from(1u64), F::from(5u64), F::from(7u64)];
         let rng = &mut test_rng();
         // setup
@@ -342,60 +325,99 @@ mod test {
             pk,
             epk,
             vk,
-        ) = ZKTPlonkupInstance::<F, PC>::compile(true, &pp, table.clone())
+        ) = ZKTPlonkupInstance::<F, PC, C>::compile(true, &pp, table, C::default())
             .unwrap_or_else(|e| panic!("compile failed: {e}"));
 
         // prove
-        let circuit = TestCircuit {
-            a: 2,
-            b: 3,
-            c: 5,
-            d: 10,
-            e: true,
-        };
-        let epk = epk.map(|epk| Rc::new(epk));
+        let epk = epk.map(Rc::new);
         let proof =
-            ZKTPlonkupInstance::<F, PC>::prove(&ck, &pk, epk, &vk, table, circuit, rng)
+            ZKTPlonkupInstance::<F, PC, C>::prove(&ck, &pk, epk, &vk, table, circuit, rng)
                 .unwrap_or_else(|e| panic!("prove failed: {e}"));
 
         // verify
-        ZKTPlonkupInstance::<F, PC>::verify(&cvk, &vk, &proof, &[10u64.into(), 2u64.into()])
+        ZKTPlonkupInstance::<F, PC, C>::verify(&cvk, &vk, &proof, &[10u64.into(), 2u64.into()])
             .unwrap_or_else(|e| panic!("verify failed: {e}"));
     }
 
+    const TEST_CIRCUIT: TestCircuit = TestCircuit {
+        a: 2,
+        b: 3,
+        c: 5,
+        d: 10,
+        e: true,
+    };
+
+    fn test_full<F: PrimeField, PC: HomomorphicCommitment<F>>() {
+        prove_and_verify::<F, PC, _>(TEST_CIRCUIT);
+    }
+
+    fn test_full_optimized<F: PrimeField, PC: HomomorphicCommitment<F>>() {
+        prove_and_verify::<F, PC, _>(OptimizedTestCircuit(TEST_CIRCUIT));
+    }
+
+    fn structure_mismatch<F, PC, const OPTIMIZE: bool>() -> bool
+    where
+        F: PrimeField,
+        PC: HomomorphicCommitment<F>,
+    {
+        type Instance<F, PC, const OPTIMIZE: bool> =
+            ZKTPlonkupInstance<F, PC, BranchingTestCircuit<OPTIMIZE>>;
+
+        let table = [F::from(1u64), F::from(5u64), F::from(7u64)];
+        let rng = &mut test_rng();
+        let pp =
+            PC::setup(1 << 10, None, rng)
+                .unwrap_or_else(|e| panic!("setup failed: {e}"));
+        let (ck, _, pk, epk, vk) =
+            Instance::<F, PC, OPTIMIZE>::compile(true, &pp, table, BranchingTestCircuit::default())
+                .unwrap_or_else(|e| panic!("compile failed: {e}"));
+
+        let circuit = BranchingTestCircuit(TEST_CIRCUIT);
+        let result =
+            Instance::<F, PC, OPTIMIZE>::prove(&ck, &pk, epk.map(Rc::new), &vk, table, circuit, rng);
+        matches!(result, Err(Error::StructureMismatch))
+    }
+
+    fn test_structure_mismatch<F: PrimeField, PC: HomomorphicCommitment<F>>() {
+        // the structure of optimized circuits is always checked, of others
+        // only with debug assertions
+        assert!(structure_mismatch::<F, PC, true>());
+        assert_eq!(structure_mismatch::<F, PC, false>(), cfg!(debug_assertions));
+    }
+
     batch_test_kzg!(
         Bn254,
-        [test_full],
+        [test_full, test_full_optimized, test_structure_mismatch],
         []
     );
 
     batch_test_kzg!(
         Bls12_377,
-        [test_full],
+        [test_full, test_full_optimized],
         []
     );
 
     batch_test_kzg!(
         Bls12_381,
-        [test_full],
+        [test_full, test_full_optimized],
         []
     );
 
     batch_test_ipa!(
         Bn254,
-        [test_full],
+        [test_full, test_full_optimized],
         []
     );
 
     batch_test_ipa!(
         Bls12_377,
-        [test_full],
+        [test_full, test_full_optimized],
         []
     );
 
     batch_test_ipa!(
         Bls12_381,
-        [test_full],
+        [test_full, test_full_optimized],
         []
     );
 }
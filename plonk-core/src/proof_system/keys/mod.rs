@@ -36,6 +36,10 @@ pub struct ProverKey<F: Field> {
 
     /// Lookup selector
     pub lookup: lookup::ProverKey<F>,
+
+    /// Digest of the gate structure, see
+    /// [`SetupComposer::digest`](crate::constraint_system::SetupComposer::digest).
+    pub structure_digest: Vec<u8>,
 }
 
 impl<F: Field> ProverKey<F> {
@@ -53,6 +57,7 @@ impl<F: Field> ProverKey<F> {
         sigma3: LabeledPolynomial<F, DensePolynomial<F>>,
         q_lookup: LabeledPolynomial<F, DensePolynomial<F>>,
         q_table: LabeledPolynomial<F, DensePolynomial<F>>,
+        structure_digest: Vec<u8>,
     ) -> Self {
         Self {
             arith: arithmetic::ProverKey {
@@ -71,6 +76,7 @@ impl<F: Field> ProverKey<F> {
                 q_lookup,
                 q_table,
             },
+            structure_digest,
         }
     }
 
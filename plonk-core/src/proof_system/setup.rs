@@ -66,6 +66,7 @@ where
     assert_eq!(domain.size(), n);
 
     let mut composer: SetupComposer<F> = cs.composer.into();
+    let structure_digest = composer.digest();
     // Pad composer
     composer.pad_to(n);
 
@@ -147,6 +148,7 @@ where
         labeled_sigma3_poly,
         labeled_q_lookup_poly,
         labeled_q_table_poly,
+        structure_digest,
     );
 
     let epk = if extend {
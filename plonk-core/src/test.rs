@@ -44,7 +44,7 @@ macro_rules! batch_test_kzg {
                 #[test]
                 #[allow(non_snake_case)]
                 fn [< $test_set _with_kzg_on_ $engine>]() {
-                    $test_set::<<$engine as ark_ec::PairingEngine>::Fr, crate::commitment::KZG10<$engine>>()
+                    $test_set::<<$engine as ark_ec::PairingEngine>::Fr, $crate::commitment::KZG10<$engine>>()
                 }
             )*
             $(
@@ -52,7 +52,7 @@ macro_rules! batch_test_kzg {
                 #[should_panic]
                 #[allow(non_snake_case)]
                 fn [< $test_panic_set _with_kzg_on_ $engine>]() {
-                    $test_panic_set::<<$engine as ark_ec::PairingEngine>::Fr, crate::commitment::KZG10<$engine>>()
+                    $test_panic_set::<<$engine as ark_ec::PairingEngine>::Fr, $crate::commitment::KZG10<$engine>>()
                 }
             )*
         }
@@ -70,7 +70,7 @@ macro_rules! batch_test_ipa {
                 fn [< $test_set _with_ipa_on_ $engine>]() {
                     $test_set::<
                         <$engine as ark_ec::PairingEngine>::Fr,
-                        crate::commitment::IPA<<$engine as ark_ec::PairingEngine>::G1Affine, blake2::Blake2s>
+                        $crate::commitment::IPA<<$engine as ark_ec::PairingEngine>::G1Affine, blake2::Blake2s>
                     >()
                 }
             )*
@@ -81,7 +81,7 @@ macro_rules! batch_test_ipa {
                 fn [< $test_panic_set _with_ipa_on_ $engine>]() {
                     $test_panic_set::<
                         <$engine as ark_ec::PairingEngine>::Fr,
-                        crate::commitment::IPA<<$engine as ark_ec::PairingEngine>::G1Affine, blake2::Blake2s>
+                        $crate::commitment::IPA<<$engine as ark_ec::PairingEngine>::G1Affine, blake2::Blake2s>
                     >()
                 }
             )*
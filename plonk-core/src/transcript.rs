@@ -101,7 +101,7 @@ where
 
     fn challenge_scalar(&mut self, label: &'static str) -> F {
         let num_bytes = (F::size_in_bits() + 7) / 8 - 1;
-        let mut bytes = vec![0u8; num_bytes as usize];
+        let mut bytes = vec![0u8; num_bytes];
         self.0.challenge_bytes(label.as_bytes(), &mut bytes);
 
         F::from_random_bytes(&bytes).unwrap()
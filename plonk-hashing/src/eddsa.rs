@@ -0,0 +1,348 @@
+//! EdDSA over a twisted Edwards curve embedded in the circuit field, e.g.
+//! Jubjub over the scalar field of BLS12-381, whose challenge is hashed by a
+//! [`FieldHasher`] such as Poseidon.
+//!
+//! A signature of message `m` by public key `A = a * B` is `(R, S)` with
+//! `S < ℓ`, the order of `B`, and `h * S * B = h * (R + c * A)` for the
+//! cofactor `h` and `c = H(R.x, R.y, A.x, A.y, m)`. Multiplying by the
+//! cofactor clears small order components of `R` and `A`, so neither needs a
+//! subgroup check. The native signer and verifier share the challenge with
+//! the gadget, so test vectors and witnesses are generated by the same code.
+
+use ark_ec::{
+    models::TEModelParameters,
+    twisted_edwards_extended::GroupAffine,
+    AffineCurve,
+    ProjectiveCurve,
+};
+use ark_ff::{BigInteger, One, PrimeField, UniformRand};
+use ark_serialize::CanonicalSerialize;
+use ark_std::rand::Rng;
+use blake2::{Blake2b, Digest};
+use derivative::Derivative;
+use plonk_core::{
+    constraint_system::{AffinePoint, AllocMode, AllocVar, Boolean, ConstraintSystem, LTVariable},
+    error::Error,
+};
+
+use crate::hasher::FieldHasher;
+
+///
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct SecretKey<P: TEModelParameters>(P::ScalarField);
+
+///
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct PublicKey<P: TEModelParameters>(pub GroupAffine<P>);
+
+///
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""), PartialEq(bound = ""))]
+pub struct Signature<P: TEModelParameters> {
+    pub r: GroupAffine<P>,
+    pub s: P::ScalarField,
+}
+
+/// Returns the challenge `H(R.x, R.y, A.x, A.y, m)`.
+fn challenge<P, H>(
+    hasher: &H,
+    r: &GroupAffine<P>,
+    public_key: &PublicKey<P>,
+    message: &P::BaseField,
+) -> P::BaseField
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+    H: FieldHasher<(), P::BaseField>,
+{
+    hasher.hash(&mut (), &[r.x, r.y, public_key.0.x, public_key.0.y, *message])
+}
+
+/// Reduces an element of the base field into the scalar field.
+fn to_scalar<P>(x: &P::BaseField) -> P::ScalarField
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    P::ScalarField::from_le_bytes_mod_order(&x.into_repr().to_bytes_le())
+}
+
+impl<P> SecretKey<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    ///
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self(P::ScalarField::rand(rng))
+    }
+
+    ///
+    pub fn public_key(&self) -> PublicKey<P> {
+        PublicKey(GroupAffine::prime_subgroup_generator().mul(self.0).into_affine())
+    }
+
+    /// Signs `message`. The nonce is derived from the secret key and the
+    /// message, so signing is deterministic.
+    pub fn sign<H: FieldHasher<(), P::BaseField>>(
+        &self,
+        hasher: &H,
+        message: &P::BaseField,
+    ) -> Signature<P> {
+        let mut bytes = Vec::new();
+        self.0.serialize(&mut bytes).expect("serialization to vec never fails");
+        message.serialize(&mut bytes).expect("serialization to vec never fails");
+        let nonce = P::ScalarField::from_le_bytes_mod_order(&Blake2b::digest(&bytes));
+
+        let r = GroupAffine::prime_subgroup_generator().mul(nonce).into_affine();
+        let c = challenge(hasher, &r, &self.public_key(), message);
+        let s = nonce + to_scalar::<P>(&c) * self.0;
+
+        Signature { r, s }
+    }
+}
+
+impl<P> PublicKey<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    /// Verifies the signature of `message`. Both sides are multiplied by the
+    /// cofactor, so the challenge can be reduced for any public key on the
+    /// curve, as it is not in circuit.
+    pub fn verify<H: FieldHasher<(), P::BaseField>>(
+        &self,
+        hasher: &H,
+        message: &P::BaseField,
+        signature: &Signature<P>,
+    ) -> bool {
+        if !self.0.is_on_curve() || !signature.r.is_on_curve() {
+            return false;
+        }
+
+        let c = challenge(hasher, &signature.r, self, message);
+        let lhs = GroupAffine::prime_subgroup_generator().mul(signature.s);
+        let rhs = self.0.mul(to_scalar::<P>(&c)).add_mixed(&signature.r);
+
+        lhs.into_affine().mul_by_cofactor() == rhs.into_affine().mul_by_cofactor()
+    }
+}
+
+/// Signature in circuit.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct SignatureVar<P: TEModelParameters> {
+    r: AffinePoint<P>,
+    s_bits: Vec<Boolean>,
+}
+
+impl<P> AllocVar<Signature<P>, P::BaseField> for SignatureVar<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    /// Allocates `signature`, `R` is constrained to be on the curve and `S` to
+    /// be a canonical scalar.
+    fn new_variable(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        signature: &Signature<P>,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        let r = AffinePoint::new_variable(cs, &signature.r, mode)?;
+        // the scalar field is smaller than the base field, nothing is reduced
+        let s = P::BaseField::from_le_bytes_mod_order(&signature.s.into_repr().to_bytes_le());
+        let s = LTVariable::new_variable(cs, &s, mode)?;
+
+        Ok(Self::from_parts(cs, r, &s))
+    }
+}
+
+impl<P> SignatureVar<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    /// Decomposes `s` into bits, which are constrained to be less than the
+    /// order `ℓ` of the scalar field. Otherwise `S + ℓ` would be another valid
+    /// signature whenever it fits in the bit size of the scalar field.
+    fn from_parts(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        r: AffinePoint<P>,
+        s: &LTVariable<P::BaseField>,
+    ) -> Self {
+        let s_bits = cs.to_bits_le(s, P::ScalarField::size_in_bits());
+        let order_minus_one = (-P::ScalarField::one()).into_repr().to_bits_le();
+        cs.enforce_bits_le_at_most(&s_bits, &order_minus_one);
+
+        Self { r, s_bits }
+    }
+
+    /// Constrains the signature of `message` by `public_key` to be valid,
+    /// where `hasher` must compute the same challenge as the native one.
+    ///
+    /// The challenge is not reduced in circuit, which matches the native
+    /// verifier because both sides are multiplied by the cofactor.
+    pub fn verify<H>(
+        &self,
+        cs: &mut ConstraintSystem<P::BaseField>,
+        hasher: &H,
+        public_key: &AffinePoint<P>,
+        message: &LTVariable<P::BaseField>,
+    )
+    where
+        H: FieldHasher<ConstraintSystem<P::BaseField>, LTVariable<P::BaseField>>,
+    {
+        let c = hasher.hash(
+            cs,
+            &[self.r.x(), self.r.y(), public_key.x(), public_key.y(), *message],
+        );
+        let c_bits = cs.to_bits_le_strict(&c);
+
+        let generator = GroupAffine::prime_subgroup_generator();
+        let lhs = AffinePoint::fixed_base_scalar_mul(cs, &generator, &self.s_bits);
+        let c_a = public_key.scalar_mul(cs, &c_bits);
+        let rhs = self.r.add(cs, &c_a);
+
+        let lhs = lhs.mul_by_cofactor(cs);
+        let rhs = rhs.mul_by_cofactor(cs);
+        lhs.enforce_equal(cs, &rhs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ed_on_bls12_381::{EdwardsParameters, Fq, Fr, FrParameters};
+    use ark_ff::{FpParameters, Zero};
+    use ark_std::test_rng;
+    use plonk_core::constraint_system::test_gate_constraints;
+
+    use crate::hasher::poseidon::*;
+    use super::*;
+
+    const WIDTH: usize = 6;
+
+    fn native_hasher() -> PoseidonRef<(), NativeSpecRef<Fq>, WIDTH> {
+        PoseidonRef::new(PoseidonConstants::preset::<WIDTH>())
+    }
+
+    fn plonk_hasher() -> PoseidonRef<ConstraintSystem<Fq>, PlonkSpecRef, WIDTH> {
+        PoseidonRef::new(PoseidonConstants::preset::<WIDTH>())
+    }
+
+    #[test]
+    fn test_native_signature() {
+        let rng = &mut test_rng();
+        let hasher = native_hasher();
+        let secret_key = SecretKey::<EdwardsParameters>::new(rng);
+        let public_key = secret_key.public_key();
+        let message = Fq::rand(rng);
+
+        let signature = secret_key.sign(&hasher, &message);
+        assert_eq!(signature, secret_key.sign(&hasher, &message));
+        assert!(public_key.verify(&hasher, &message, &signature));
+        assert!(!public_key.verify(&hasher, &(message + Fq::from(1u64)), &signature));
+
+        let other_key = SecretKey::<EdwardsParameters>::new(rng).public_key();
+        assert!(!other_key.verify(&hasher, &message, &signature));
+    }
+
+    fn test_signature_gadget(valid: bool) {
+        let rng = &mut test_rng();
+        let secret_key = SecretKey::<EdwardsParameters>::new(rng);
+        let public_key = secret_key.public_key();
+        let message = Fq::rand(rng);
+        let signature = secret_key.sign(&native_hasher(), &message);
+        let signed_message = if valid { message } else { message + Fq::from(1u64) };
+
+        test_gate_constraints(
+            |cs| {
+                let public_key_var = AffinePoint::new_witness(cs, &public_key.0).unwrap();
+                let message_var = LTVariable::new_witness(cs, &signed_message).unwrap();
+                let signature_var = SignatureVar::new_witness(cs, &signature).unwrap();
+                cs.namespace("verify", |cs| {
+                    signature_var.verify(cs, &plonk_hasher(), &public_key_var, &message_var)
+                });
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_public_key_with_torsion() {
+        let rng = &mut test_rng();
+        let secret_key = SecretKey::<EdwardsParameters>::new(rng);
+        // (0, -1) has order 2
+        let torsion = GroupAffine::new(Fq::zero(), -Fq::one());
+        let public_key = PublicKey(secret_key.public_key().0 + torsion);
+        let message = Fq::rand(rng);
+
+        // signs with the challenge of the public key with torsion
+        let nonce = Fr::rand(rng);
+        let r = GroupAffine::prime_subgroup_generator().mul(nonce).into_affine();
+        let c = challenge(&native_hasher(), &r, &public_key, &message);
+        let signature = Signature { r, s: nonce + to_scalar::<EdwardsParameters>(&c) * secret_key.0 };
+        assert!(public_key.verify(&native_hasher(), &message, &signature));
+
+        test_gate_constraints(
+            |cs| {
+                let public_key_var = AffinePoint::new_witness(cs, &public_key.0).unwrap();
+                let message_var = LTVariable::new_witness(cs, &message).unwrap();
+                let signature_var = SignatureVar::new_witness(cs, &signature).unwrap();
+                signature_var.verify(cs, &plonk_hasher(), &public_key_var, &message_var);
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "in /signature is not satisfied")]
+    fn test_non_canonical_signature_gadget() {
+        let rng = &mut test_rng();
+        let secret_key = SecretKey::<EdwardsParameters>::new(rng);
+        let public_key = secret_key.public_key();
+        let order = Fq::from_le_bytes_mod_order(&FrParameters::MODULUS.to_bytes_le());
+
+        // S + ℓ verifies as S does, find a message where it fits in the bits
+        let (message, signature, s) = (0u64..)
+            .map(|i| {
+                let message = Fq::from(i);
+                let signature = secret_key.sign(&native_hasher(), &message);
+                let s = Fq::from_le_bytes_mod_order(&signature.s.into_repr().to_bytes_le());
+                (message, signature, s + order)
+            })
+            .find(|(_, _, s)| s.into_repr().num_bits() as usize <= Fr::size_in_bits())
+            .unwrap();
+
+        test_gate_constraints(
+            |cs| {
+                let public_key_var = AffinePoint::new_witness(cs, &public_key.0).unwrap();
+                let message_var = LTVariable::new_witness(cs, &message).unwrap();
+                let r = AffinePoint::new_witness(cs, &signature.r).unwrap();
+                let s = LTVariable::new_witness(cs, &s).unwrap();
+                let signature_var =
+                    cs.namespace("signature", |cs| SignatureVar::from_parts(cs, r, &s));
+                signature_var.verify(cs, &plonk_hasher(), &public_key_var, &message_var);
+
+                vec![]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_valid_signature_gadget() {
+        test_signature_gadget(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "in /verify is not satisfied")]
+    fn test_invalid_signature_gadget() {
+        test_signature_gadget(false);
+    }
+}
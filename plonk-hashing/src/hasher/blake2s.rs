@@ -0,0 +1,207 @@
+//! BLAKE2s-256 in circuit, as specified by RFC 7693, without key or salt.
+//!
+//! Words are [`UInt32`]s so rotations are free, and the parameter block, the
+//! counters and the finalization flag are constants since the input length is
+//! part of the circuit structure.
+
+use ark_ff::PrimeField;
+use itertools::Itertools;
+use plonk_core::constraint_system::{ConstraintSystem, UInt32, UInt8};
+
+use super::sha256::IV;
+
+/// Message word permutations of the rounds.
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Block size in bytes.
+pub const BLOCK_SIZE: usize = 64;
+
+/// Digest size in bytes.
+pub const DIGEST_SIZE: usize = 32;
+
+/// Little-endian word of 4 bytes.
+fn word_from_bytes(bytes: &[UInt8]) -> UInt32 {
+    let bits = bytes.iter().flat_map(|byte| byte.to_bits_le()).collect_vec();
+
+    UInt32::from_bits_le(&bits)
+}
+
+/// Little-endian bytes of a word.
+fn word_to_bytes(word: &UInt32) -> Vec<UInt8> {
+    word.to_bits_le()
+        .chunks(8)
+        .map(UInt8::from_bits_le)
+        .collect()
+}
+
+/// The mixing function G over `v[a], v[b], v[c], v[d]` with words `x` and `y`.
+#[allow(clippy::too_many_arguments)]
+fn mix<F: PrimeField>(
+    cs: &mut ConstraintSystem<F>,
+    v: &mut [UInt32],
+    a: usize,
+    b: usize,
+    c: usize,
+    d: usize,
+    x: &UInt32,
+    y: &UInt32,
+) {
+    v[a] = UInt32::wrapping_add_many(cs, &[v[a].clone(), v[b].clone(), x.clone()]);
+    v[d] = v[d].xor(cs, &v[a]).rotr(16);
+    v[c] = v[c].wrapping_add(cs, &v[d]);
+    v[b] = v[b].xor(cs, &v[c]).rotr(12);
+    v[a] = UInt32::wrapping_add_many(cs, &[v[a].clone(), v[b].clone(), y.clone()]);
+    v[d] = v[d].xor(cs, &v[a]).rotr(8);
+    v[c] = v[c].wrapping_add(cs, &v[d]);
+    v[b] = v[b].xor(cs, &v[c]).rotr(7);
+}
+
+/// Compresses a block of 16 words into the state of 8 words. `counter` is
+/// the number of input bytes so far, including this block, and `last` is
+/// set for the final block.
+pub fn blake2s_compression<F: PrimeField>(
+    cs: &mut ConstraintSystem<F>,
+    state: &[UInt32],
+    block: &[UInt32],
+    counter: u64,
+    last: bool,
+) -> Vec<UInt32> {
+    assert_eq!(state.len(), 8, "invalid state length");
+    assert_eq!(block.len(), 16, "invalid block length");
+
+    let mut iv = IV;
+    iv[4] ^= counter as u32;
+    iv[5] ^= (counter >> 32) as u32;
+    if last {
+        iv[6] = !iv[6];
+    }
+    let mut v = state
+        .iter()
+        .cloned()
+        .chain(iv.iter().map(|&word| UInt32::constant(cs, word as u64)))
+        .collect_vec();
+
+    for s in SIGMA {
+        mix(cs, &mut v, 0, 4, 8, 12, &block[s[0]], &block[s[1]]);
+        mix(cs, &mut v, 1, 5, 9, 13, &block[s[2]], &block[s[3]]);
+        mix(cs, &mut v, 2, 6, 10, 14, &block[s[4]], &block[s[5]]);
+        mix(cs, &mut v, 3, 7, 11, 15, &block[s[6]], &block[s[7]]);
+        mix(cs, &mut v, 0, 5, 10, 15, &block[s[8]], &block[s[9]]);
+        mix(cs, &mut v, 1, 6, 11, 12, &block[s[10]], &block[s[11]]);
+        mix(cs, &mut v, 2, 7, 8, 13, &block[s[12]], &block[s[13]]);
+        mix(cs, &mut v, 3, 4, 9, 14, &block[s[14]], &block[s[15]]);
+    }
+
+    state
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let t = h.xor(cs, &v[i]);
+            t.xor(cs, &v[i + 8])
+        })
+        .collect()
+}
+
+/// Returns the BLAKE2s-256 digest of `input` bytes with the 8 bytes of
+/// `personalization`, as used by the native `Blake2s::with_params`.
+pub fn blake2s_personalized<F: PrimeField>(
+    cs: &mut ConstraintSystem<F>,
+    input: &[UInt8],
+    personalization: &[u8; 8],
+) -> Vec<UInt8> {
+    // parameter block: digest length, no key, fanout and depth of 1
+    let mut h = IV;
+    h[0] ^= 0x0101_0000 ^ DIGEST_SIZE as u32;
+    h[6] ^= u32::from_le_bytes(personalization[..4].try_into().unwrap());
+    h[7] ^= u32::from_le_bytes(personalization[4..].try_into().unwrap());
+    let mut state = h
+        .iter()
+        .map(|&word| UInt32::constant(cs, word as u64))
+        .collect_vec();
+
+    // the last block is padded with zeros, an empty input is a block of zeros
+    let num_blocks = input.len().div_ceil(BLOCK_SIZE).max(1);
+    let zero = UInt8::constant(cs, 0);
+    let message = input
+        .iter()
+        .cloned()
+        .chain(core::iter::repeat(zero).take(num_blocks * BLOCK_SIZE - input.len()))
+        .collect_vec();
+    for (i, block) in message.chunks(BLOCK_SIZE).enumerate() {
+        let last = i + 1 == num_blocks;
+        let counter = if last { input.len() } else { (i + 1) * BLOCK_SIZE };
+        let words = block.chunks(4).map(word_from_bytes).collect_vec();
+        state = blake2s_compression(cs, &state, &words, counter as u64, last);
+    }
+
+    state.iter().flat_map(word_to_bytes).collect()
+}
+
+/// Returns the BLAKE2s-256 digest of `input` bytes.
+pub fn blake2s<F: PrimeField>(cs: &mut ConstraintSystem<F>, input: &[UInt8]) -> Vec<UInt8> {
+    blake2s_personalized(cs, input, &[0; 8])
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, rand::RngCore};
+    use blake2::{Blake2s, Digest};
+    use plonk_core::constraint_system::test_gate_constraints;
+
+    use super::*;
+
+    fn test_blake2s(input: &[u8], personalization: &[u8; 8]) {
+        let expected = Blake2s::with_params(&[], &[], personalization)
+            .chain(input)
+            .finalize();
+        test_gate_constraints(
+            |cs| -> Vec<_> {
+                let input_vars = input
+                    .iter()
+                    .map(|&byte| UInt8::new(cs, byte as u64))
+                    .collect_vec();
+                let digest = blake2s_personalized(cs, &input_vars, personalization);
+                assert_eq!(digest.len(), DIGEST_SIZE);
+
+                digest
+                    .iter()
+                    .zip(expected.iter())
+                    .map(|(byte, &value)| (byte.to_variable(cs).into(), Fr::from(value)))
+                    .collect()
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_blake2s_abc() {
+        test_blake2s(b"abc", &[0; 8]);
+    }
+
+    #[test]
+    fn test_blake2s_block_boundaries() {
+        let rng = &mut test_rng();
+        for len in [0, 64, 65] {
+            let mut input = vec![0u8; len];
+            rng.fill_bytes(&mut input);
+            test_blake2s(&input, &[0; 8]);
+        }
+    }
+
+    #[test]
+    fn test_blake2s_personalized() {
+        test_blake2s(b"identifier", b"ZKT_Id__");
+    }
+}
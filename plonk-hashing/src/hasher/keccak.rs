@@ -0,0 +1,212 @@
+//! Keccak-256 in circuit, as used by Ethereum (the original Keccak padding,
+//! not the one of SHA-3).
+//!
+//! The state is kept as [`Byte`]s, so XOR and AND are byte lookups and
+//! rotations by whole bytes are free. The lookup table of the circuit must
+//! contain [`byte_table`](plonk_core::constraint_system::byte_table).
+
+use ark_ff::PrimeField;
+use itertools::Itertools;
+use plonk_core::constraint_system::{Byte, ConstraintSystem};
+
+/// Rate of Keccak-256 in bytes.
+pub const RATE: usize = 136;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// Rotation offsets of the lane at `x + 5 * y`.
+const ROTATIONS: [usize; 25] = [
+    0, 1, 62, 28, 27,
+    36, 44, 6, 55, 20,
+    3, 10, 43, 25, 39,
+    41, 45, 15, 21, 8,
+    18, 2, 61, 56, 14,
+];
+
+type Lane = Vec<Byte>;
+
+fn xor_lanes<F: PrimeField>(cs: &mut ConstraintSystem<F>, x: &Lane, y: &Lane) -> Lane {
+    x.iter().zip(y).map(|(&x, &y)| cs.xor_byte_gate(x, y)).collect()
+}
+
+fn round<F: PrimeField>(cs: &mut ConstraintSystem<F>, a: Vec<Lane>, rc: u64) -> Vec<Lane> {
+    // theta
+    let c = (0..5)
+        .map(|x| {
+            (1..5).fold(a[x].clone(), |acc, y| xor_lanes(cs, &acc, &a[x + 5 * y]))
+        })
+        .collect_vec();
+    let d = (0..5)
+        .map(|x| {
+            let rotated = cs.rotl_bytes_le(&c[(x + 1) % 5], 1);
+            xor_lanes(cs, &c[(x + 4) % 5], &rotated)
+        })
+        .collect_vec();
+    let a = a
+        .iter()
+        .enumerate()
+        .map(|(i, lane)| xor_lanes(cs, lane, &d[i % 5]))
+        .collect_vec();
+
+    // rho and pi: B[y, 2x + 3y] = rot(A[x, y])
+    let mut b = vec![Vec::new(); 25];
+    for (i, lane) in a.iter().enumerate() {
+        let (x, y) = (i % 5, i / 5);
+        b[y + 5 * ((2 * x + 3 * y) % 5)] = cs.rotl_bytes_le(lane, ROTATIONS[i]);
+    }
+
+    // chi: A[x, y] = B[x, y] XOR ((NOT B[x + 1, y]) AND B[x + 2, y])
+    let mut a = (0..25)
+        .map(|i| {
+            let (x, y) = (i % 5, i / 5);
+            let b1 = &b[(x + 1) % 5 + 5 * y];
+            let b2 = &b[(x + 2) % 5 + 5 * y];
+            b[i]
+                .iter()
+                .zip(b1.iter().zip(b2))
+                .map(|(&b0, (&b1, &b2))| {
+                    let t = cs.not_and_byte_gate(b1, b2);
+                    cs.xor_byte_gate(b0, t)
+                })
+                .collect_vec()
+        })
+        .collect_vec();
+
+    // iota
+    a[0] = a[0]
+        .iter()
+        .zip(rc.to_le_bytes())
+        .map(|(&byte, value)| cs.xor_constant_byte_gate(byte, value))
+        .collect();
+
+    a
+}
+
+/// Applies Keccak-f[1600] to the state of 200 bytes, whose lanes are
+/// little-endian and ordered by `x + 5 * y`.
+pub fn keccak_f1600<F: PrimeField>(cs: &mut ConstraintSystem<F>, state: &[Byte]) -> Vec<Byte> {
+    assert_eq!(state.len(), 200, "invalid state length");
+
+    let mut lanes = state.chunks(8).map(|lane| lane.to_vec()).collect_vec();
+    for rc in ROUND_CONSTANTS {
+        lanes = round(cs, lanes, rc);
+    }
+
+    lanes.concat()
+}
+
+/// Returns the Keccak-256 digest of `input` bytes, which are padded in
+/// circuit. The length of the input is part of the circuit structure.
+pub fn keccak256<F: PrimeField>(cs: &mut ConstraintSystem<F>, input: &[Byte]) -> Vec<Byte> {
+    // padding: 0x01, zeros, then 0x80 up to a multiple of the rate
+    let mut padding = vec![0u8; RATE - input.len() % RATE];
+    padding[0] |= 0x01;
+    *padding.last_mut().unwrap() |= 0x80;
+    let padding = padding
+        .into_iter()
+        .map(|byte| cs.constant_byte(byte))
+        .collect_vec();
+    let message = input.iter().copied().chain(padding).collect_vec();
+    debug_assert_eq!(message.len() % RATE, 0);
+
+    // the first block is absorbed into the zero state for free
+    let mut blocks = message.chunks(RATE);
+    let zero = cs.constant_byte(0);
+    let first = blocks.next().unwrap();
+    let mut state = first
+        .iter()
+        .copied()
+        .chain(core::iter::repeat(zero).take(200 - RATE))
+        .collect_vec();
+    state = keccak_f1600(cs, &state);
+    for block in blocks {
+        for (byte, &input) in state.iter_mut().zip(block) {
+            *byte = cs.xor_byte_gate(*byte, input);
+        }
+        state = keccak_f1600(cs, &state);
+    }
+
+    state.truncate(32);
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, rand::{Rng, RngCore}};
+    use plonk_core::constraint_system::{byte_table, test_gate_constraints_with_table};
+    use sha3::{Digest, Keccak256};
+
+    use super::*;
+
+    fn new_byte(cs: &mut ConstraintSystem<Fr>, value: u8) -> Byte {
+        let x = cs.assign_variable(Fr::from(value));
+        cs.byte_gate(x)
+    }
+
+    fn test_keccak256(input: &[u8]) {
+        let expected = Keccak256::digest(input);
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let input_vars = input.iter().map(|&byte| new_byte(cs, byte)).collect_vec();
+                let digest = keccak256(cs, &input_vars);
+                assert_eq!(digest.len(), 32);
+
+                digest
+                    .into_iter()
+                    .zip(expected.iter())
+                    .map(|(byte, &value)| (byte.into(), Fr::from(value)))
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        );
+    }
+
+    #[test]
+    fn test_keccak256_abc() {
+        test_keccak256(b"abc");
+    }
+
+    #[test]
+    fn test_keccak256_padding_boundaries() {
+        let rng = &mut test_rng();
+        // 135 bytes take a single padding byte, 136 bytes a full padding block
+        for len in [0, 135, 136] {
+            let mut input = vec![0u8; len];
+            rng.fill_bytes(&mut input);
+            test_keccak256(&input);
+        }
+    }
+
+    #[test]
+    fn test_keccak_f1600() {
+        let rng = &mut test_rng();
+        let mut lanes = [0u64; 25];
+        lanes.iter_mut().for_each(|lane| *lane = rng.gen());
+        let state = lanes.iter().flat_map(|lane| lane.to_le_bytes()).collect_vec();
+        keccak::f1600(&mut lanes);
+
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let state_vars = state.iter().map(|&byte| new_byte(cs, byte)).collect_vec();
+                let output = keccak_f1600(cs, &state_vars);
+
+                output
+                    .into_iter()
+                    .zip(lanes.iter().flat_map(|lane| lane.to_le_bytes()))
+                    .map(|(byte, value)| (byte.into(), Fr::from(value)))
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        );
+    }
+}
@@ -1,15 +1,27 @@
 use core::fmt::Debug;
+use ark_ff::Field;
+use plonk_core::constraint_system::{Gadget, LTVariable};
 
+pub mod blake2s;
+pub mod keccak;
+pub mod pedersen;
 pub mod poseidon;
+pub mod sha256;
 
+/// Hasher of field elements.
+///
+/// Hashing only borrows the hasher, so a single hasher can be shared, e.g.
+/// by the threads building a Merkle tree.
 pub trait FieldHasher<CS, F: Debug + Clone> {
 
-    fn empty_hash() -> F;
+    /// Returns the hash of empty leaves, which depends on the hasher's
+    /// parameters.
+    fn empty_hash(&self) -> F;
 
-    fn hash(&mut self, cs: &mut CS, input: &[F]) -> F;
+    fn hash(&self, cs: &mut CS, input: &[F]) -> F;
 
     fn hash_two(
-        &mut self,
+        &self,
         cs: &mut CS,
         left: &F,
         right: &F,
@@ -17,3 +29,17 @@ pub trait FieldHasher<CS, F: Debug + Clone> {
         self.hash(cs, &[left.clone(), right.clone()])
     }
 }
+
+/// Gadget hashing field elements both natively and in circuit, such as
+/// Poseidon over [`PlonkSpecRef`](poseidon::PlonkSpecRef) and Pedersen.
+pub trait FieldHasherGadget<F: Field>:
+    Gadget<F, NativeInput = [F], NativeOutput = F, Input = [LTVariable<F>], Output = LTVariable<F>>
+{
+}
+
+impl<F, G> FieldHasherGadget<F> for G
+where
+    F: Field,
+    G: Gadget<F, NativeInput = [F], NativeOutput = F, Input = [LTVariable<F>], Output = LTVariable<F>>,
+{
+}
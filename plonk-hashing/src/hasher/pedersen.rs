@@ -0,0 +1,462 @@
+//! Pedersen commitments and hash over a twisted Edwards curve embedded in the
+//! circuit field, e.g. Jubjub over the scalar field of BLS12-381.
+//!
+//! Generators are derived from a domain by hashing to the curve, so nobody
+//! knows their discrete logarithms. The hash is modeled after the Zcash
+//! construction: bits are split into windows of 3, window `j` of a segment
+//! contributes `(m_j + 1) * 2^(4j)` times the generator of the segment, and a
+//! segment has as many windows as fit below half of the order, which keeps
+//! the encoding injective. Unlike Zcash, whose windows encode the signed
+//! values `±(1 + m)`, the encoding is unsigned, so digests differ from the
+//! Zcash ones.
+//!
+//! In circuit, each window looks up its point in a [`PointTable`] of the
+//! points of all windows, so the lookup table of the circuit must contain
+//! [`PedersenHash::lookup_table`].
+
+use ark_ec::{
+    models::TEModelParameters,
+    twisted_edwards_extended::GroupAffine,
+    AffineCurve,
+    ProjectiveCurve,
+};
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use blake2::{Blake2s, Digest};
+use derivative::Derivative;
+use itertools::Itertools;
+use plonk_core::{
+    constraint_system::{AffinePoint, Boolean, ConstraintSystem, Gadget, LTVariable, PointTable},
+    lookup::LookupTable,
+};
+
+use super::FieldHasher;
+
+/// Number of bits of a window of the hash.
+pub const WINDOW_SIZE: usize = 3;
+
+/// Hashes `domain` and `index` to a point of the prime order subgroup, by
+/// trying consecutive counters until the digest is the x-coordinate of a
+/// point.
+pub fn generator<P: TEModelParameters>(domain: &[u8], index: u64) -> GroupAffine<P>
+where
+    P::BaseField: PrimeField,
+{
+    (0u64..)
+        .find_map(|counter| {
+            let digest = Blake2s::new()
+                .chain(domain)
+                .chain(index.to_le_bytes())
+                .chain(counter.to_le_bytes())
+                .finalize();
+            let x = P::BaseField::from_le_bytes_mod_order(&digest);
+            GroupAffine::get_point_from_x(x, true)
+                .map(|point| point.scale_by_cofactor().into_affine())
+                .filter(|point| !point.is_zero())
+        })
+        .unwrap()
+}
+
+/// Pedersen vector commitment `sum(v_i * G_i) + r * H`.
+///
+/// Values and randomness are scalars of the prime order subgroup. In circuit,
+/// they are elements of the base field whose bits are constrained below the
+/// order, otherwise `v` and `v + ℓ` would open the same commitment.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct PedersenCommitment<P: TEModelParameters> {
+    generators: Vec<GroupAffine<P>>,
+    blinding_generator: GroupAffine<P>,
+}
+
+impl<P> PedersenCommitment<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    /// Derives the generators of vectors of `size` values from `domain`.
+    pub fn new(domain: &[u8], size: usize) -> Self {
+        Self {
+            generators: (1..=size as u64).map(|i| generator(domain, i)).collect(),
+            blinding_generator: generator(domain, 0),
+        }
+    }
+
+    ///
+    pub fn size(&self) -> usize {
+        self.generators.len()
+    }
+
+    ///
+    pub fn commit(&self, values: &[P::ScalarField], randomness: &P::ScalarField) -> GroupAffine<P> {
+        assert_eq!(values.len(), self.size(), "invalid number of values");
+
+        values
+            .iter()
+            .zip(&self.generators)
+            .fold(
+                self.blinding_generator.mul(*randomness),
+                |acc, (value, generator)| acc + generator.mul(*value),
+            )
+            .into_affine()
+    }
+
+    /// Decomposes a scalar into bits, which are constrained below the order.
+    fn scalar_bits(
+        cs: &mut ConstraintSystem<P::BaseField>,
+        x: &LTVariable<P::BaseField>,
+    ) -> Vec<Boolean> {
+        let bits = cs.to_bits_le(x, P::ScalarField::size_in_bits());
+        let order_minus_one = (-P::ScalarField::one()).into_repr().to_bits_le();
+        cs.enforce_bits_le_at_most(&bits, &order_minus_one);
+
+        bits
+    }
+
+    /// Commits in circuit, the values and the randomness must be less than
+    /// the order of the subgroup.
+    pub fn commit_gadget(
+        &self,
+        cs: &mut ConstraintSystem<P::BaseField>,
+        values: &[LTVariable<P::BaseField>],
+        randomness: &LTVariable<P::BaseField>,
+    ) -> AffinePoint<P> {
+        assert_eq!(values.len(), self.size(), "invalid number of values");
+
+        let bits = Self::scalar_bits(cs, randomness);
+        let blinding = AffinePoint::fixed_base_scalar_mul(cs, &self.blinding_generator, &bits);
+        values
+            .iter()
+            .zip(&self.generators)
+            .fold(blinding, |acc, (value, generator)| {
+                let bits = Self::scalar_bits(cs, value);
+                let term = AffinePoint::fixed_base_scalar_mul(cs, generator, &bits);
+                acc.add(cs, &term)
+            })
+    }
+}
+
+/// Windowed Pedersen hash of bit strings of a fixed length.
+///
+/// Inputs are padded with zeros to whole windows, so inputs of different
+/// lengths may collide. As a [`FieldHasher`], it hashes the canonical bits of
+/// the field elements and returns the x-coordinate of the point.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub struct PedersenHash<P: TEModelParameters> {
+    /// Points `(k + 1) * 2^(4j) * G_s` of window `j` of every segment `s`,
+    /// for `k` in `0..8`.
+    table: PointTable<P>,
+    windows_per_segment: usize,
+}
+
+impl<P> PedersenHash<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    /// Derives the generators of inputs of at most `max_bits` bits from
+    /// `domain`, which also tags the lookup table entries.
+    pub fn new(domain: &[u8], max_bits: usize) -> Self {
+        // sum((m_j + 1) * 2^(4j)) must stay below half of the order
+        let windows_per_segment = (P::ScalarField::size_in_bits() - 2) / (WINDOW_SIZE + 1);
+        let num_windows = max_bits.div_ceil(WINDOW_SIZE);
+
+        let windows = (0..num_windows)
+            .chunks(windows_per_segment)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(segment, windows)| {
+                let mut base = generator::<P>(domain, segment as u64).into_projective();
+                windows
+                    .map(|_| {
+                        let mut point = base;
+                        let table = (0..1 << WINDOW_SIZE)
+                            .map(|_| {
+                                let entry = point;
+                                point += &base;
+                                entry
+                            })
+                            .collect_vec();
+                        (0..=WINDOW_SIZE).for_each(|_| {
+                            base.double_in_place();
+                        });
+                        ProjectiveCurve::batch_normalization_into_affine(&table)
+                    })
+                    .collect_vec()
+            })
+            .collect();
+        let tag = P::BaseField::from_le_bytes_mod_order(
+            &Blake2s::new().chain(b"lookup table").chain(domain).finalize(),
+        );
+
+        Self {
+            table: PointTable::new(tag, WINDOW_SIZE, windows),
+            windows_per_segment,
+        }
+    }
+
+    /// Returns the maximum number of input bits.
+    pub fn max_bits(&self) -> usize {
+        self.table.windows().len() * WINDOW_SIZE
+    }
+
+    /// Returns the entries which the lookup table of a circuit hashing in
+    /// circuit must contain, two per point.
+    pub fn lookup_table(&self) -> LookupTable<P::BaseField> {
+        self.table.lookup_table()
+    }
+
+    fn check_len(&self, len: usize) {
+        assert!(len <= self.max_bits(), "input is too long");
+    }
+
+    /// Returns the index of the segment of a window.
+    pub fn segment_of(&self, window: usize) -> usize {
+        window / self.windows_per_segment
+    }
+
+    ///
+    pub fn hash_bits(&self, bits: &[bool]) -> GroupAffine<P> {
+        self.check_len(bits.len());
+
+        bits.chunks(WINDOW_SIZE)
+            .zip(self.table.windows())
+            .fold(GroupAffine::<P>::zero().into_projective(), |acc, (window, table)| {
+                let index = window
+                    .iter()
+                    .rev()
+                    .fold(0, |index, &bit| (index << 1) | usize::from(bit));
+                acc.add_mixed(&table[index])
+            })
+            .into_affine()
+    }
+
+    ///
+    pub fn hash_bits_gadget(
+        &self,
+        cs: &mut ConstraintSystem<P::BaseField>,
+        bits: &[Boolean],
+    ) -> AffinePoint<P> {
+        self.check_len(bits.len());
+
+        let zero = cs.constant_boolean(false);
+        let points = bits
+            .chunks(WINDOW_SIZE)
+            .enumerate()
+            .map(|(i, window)| {
+                let mut window = window.to_vec();
+                window.resize(WINDOW_SIZE, zero);
+                AffinePoint::lookup(cs, &self.table, i, &window)
+            })
+            .collect_vec();
+
+        points
+            .into_iter()
+            .reduce(|acc, point| acc.add(cs, &point))
+            .unwrap_or_else(AffinePoint::identity)
+    }
+}
+
+impl<P> FieldHasher<(), P::BaseField> for PedersenHash<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    fn empty_hash(&self) -> P::BaseField {
+        self.hash(&mut (), &[P::BaseField::zero()])
+    }
+
+    fn hash(&self, _cs: &mut (), input: &[P::BaseField]) -> P::BaseField {
+        let bits = input
+            .iter()
+            .flat_map(|x| {
+                let mut bits = x.into_repr().to_bits_le();
+                bits.truncate(P::BaseField::size_in_bits());
+                bits
+            })
+            .collect_vec();
+
+        self.hash_bits(&bits).x
+    }
+}
+
+impl<P> FieldHasher<ConstraintSystem<P::BaseField>, LTVariable<P::BaseField>> for PedersenHash<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    fn empty_hash(&self) -> LTVariable<P::BaseField> {
+        LTVariable::constant(FieldHasher::<(), _>::empty_hash(self))
+    }
+
+    fn hash(
+        &self,
+        cs: &mut ConstraintSystem<P::BaseField>,
+        input: &[LTVariable<P::BaseField>],
+    ) -> LTVariable<P::BaseField> {
+        let bits = input
+            .iter()
+            .flat_map(|x| cs.to_bits_le_strict(x))
+            .collect_vec();
+
+        self.hash_bits_gadget(cs, &bits).x()
+    }
+}
+
+impl<P> Gadget<P::BaseField> for PedersenHash<P>
+where
+    P: TEModelParameters,
+    P::BaseField: PrimeField,
+{
+    type NativeInput = [P::BaseField];
+    type NativeOutput = P::BaseField;
+    type Input = [LTVariable<P::BaseField>];
+    type Output = LTVariable<P::BaseField>;
+
+    fn native(&self, input: &[P::BaseField]) -> P::BaseField {
+        self.hash(&mut (), input)
+    }
+
+    fn synthesize(
+        &self,
+        cs: &mut ConstraintSystem<P::BaseField>,
+        input: &[LTVariable<P::BaseField>],
+    ) -> LTVariable<P::BaseField> {
+        self.hash(cs, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ed_on_bls12_381::{EdwardsParameters, Fq, Fr, FrParameters};
+    use ark_ff::FpParameters;
+    use ark_std::{test_rng, rand::Rng, UniformRand};
+    use plonk_core::constraint_system::{test_gate_constraints, test_gate_constraints_with_table};
+
+    use super::*;
+
+    const DOMAIN: &[u8] = b"ZKT_Pedersen";
+
+    fn to_base_field(x: &Fr) -> Fq {
+        Fq::from_le_bytes_mod_order(&x.into_repr().to_bytes_le())
+    }
+
+    #[test]
+    fn test_commitment() {
+        let rng = &mut test_rng();
+        let pedersen = PedersenCommitment::<EdwardsParameters>::new(DOMAIN, 3);
+        let values = (0..3).map(|_| Fr::rand(rng)).collect_vec();
+        let randomness = Fr::rand(rng);
+        let commitment = pedersen.commit(&values, &randomness);
+
+        // homomorphic
+        let other_values = (0..3).map(|_| Fr::rand(rng)).collect_vec();
+        let other_randomness = Fr::rand(rng);
+        let sum = values.iter().zip(&other_values).map(|(a, b)| *a + b).collect_vec();
+        assert_eq!(
+            (commitment.into_projective()
+                + pedersen.commit(&other_values, &other_randomness).into_projective())
+            .into_affine(),
+            pedersen.commit(&sum, &(randomness + other_randomness)),
+        );
+
+        test_gate_constraints(
+            |cs| {
+                let value_vars = values
+                    .iter()
+                    .map(|value| cs.assign_variable(to_base_field(value)).into())
+                    .collect_vec();
+                let randomness_var = cs.assign_variable(to_base_field(&randomness));
+                let point = pedersen.commit_gadget(cs, &value_vars, &randomness_var.into());
+
+                [(point.x(), commitment.x), (point.y(), commitment.y)]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_commitment_value_above_order() {
+        let pedersen = PedersenCommitment::<EdwardsParameters>::new(DOMAIN, 1);
+        let order = Fq::from_le_bytes_mod_order(&FrParameters::MODULUS.to_bytes_le());
+        let commitment = pedersen.commit(&[Fr::from(1u64)], &Fr::from(2u64));
+
+        // 1 + ℓ fits in the bits of a scalar and opens the same commitment
+        test_gate_constraints(
+            |cs| {
+                let value_var = cs.assign_variable(Fq::from(1u64) + order).into();
+                let randomness_var = cs.assign_variable(Fq::from(2u64)).into();
+                let point = pedersen.commit_gadget(cs, &[value_var], &randomness_var);
+
+                [(point.x(), commitment.x), (point.y(), commitment.y)]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_hash_bits() {
+        let rng = &mut test_rng();
+        let pedersen = PedersenHash::<EdwardsParameters>::new(DOMAIN, 400);
+        // the last window is partial and the input spans several segments
+        let bits = (0..400).map(|_| rng.gen()).collect_vec();
+        assert!(pedersen.segment_of(bits.len().div_ceil(WINDOW_SIZE) - 1) > 1);
+        let expected = pedersen.hash_bits(&bits);
+        assert_ne!(expected, pedersen.hash_bits(&bits[..399]));
+
+        test_gate_constraints_with_table(
+            |cs| {
+                let bit_vars = bits
+                    .iter()
+                    .map(|&bit| {
+                        let var = cs.assign_variable(Fq::from(bit));
+                        cs.boolean_gate(var)
+                    })
+                    .collect_vec();
+                let point = pedersen.hash_bits_gadget(cs, &bit_vars);
+
+                [(point.x(), expected.x), (point.y(), expected.y)]
+            },
+            &[],
+            pedersen.lookup_table(),
+        );
+    }
+
+    #[test]
+    fn test_field_hasher() {
+        let rng = &mut test_rng();
+        let pedersen = PedersenHash::<EdwardsParameters>::new(DOMAIN, 2 * 255);
+        let left = Fq::rand(rng);
+        let right = Fq::rand(rng);
+        let expected = pedersen.hash_two(&mut (), &left, &right);
+
+        test_gate_constraints_with_table(
+            |cs| {
+                let left_var = cs.assign_variable(left).into();
+                let right_var = cs.assign_variable(right).into();
+
+                [(pedersen.hash_two(cs, &left_var, &right_var), expected)]
+            },
+            &[],
+            pedersen.lookup_table(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "element not found in table")]
+    fn test_hash_with_table_of_other_domain() {
+        let pedersen = PedersenHash::<EdwardsParameters>::new(DOMAIN, 255);
+        let other = PedersenHash::<EdwardsParameters>::new(b"ZKT_Other", 255);
+
+        test_gate_constraints_with_table(
+            |cs| {
+                let x = cs.assign_variable(Fq::from(5u64)).into();
+
+                [(pedersen.hash(cs, &[x]), pedersen.hash(&mut (), &[Fq::from(5u64)]))]
+            },
+            &[],
+            other.lookup_table(),
+        );
+    }
+}
@@ -1,6 +1,14 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
 use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use blake2::{Blake2s, Digest};
 
 use super::{
+    PoseidonError,
     matrix::Matrix,
     mds::{factor_to_sparse_matrixes, MdsMatrices, SparseMatrix},
     preprocessing::compress_round_constants,
@@ -8,7 +16,13 @@ use super::{
     round_numbers::calc_round_numbers,
 };
 
-#[derive(Clone, Debug, PartialEq)]
+type Presets = HashMap<(TypeId, usize), Arc<dyn Any + Send + Sync>>;
+
+/// Constants generated so far by [`PoseidonConstants::preset`], keyed by
+/// field and width.
+static PRESETS: OnceLock<Mutex<Presets>> = OnceLock::new();
+
+#[derive(Clone, Debug, PartialEq, CanonicalDeserialize, CanonicalSerialize)]
 pub struct PoseidonConstants<F: PrimeField> {
     pub mds_matrices: MdsMatrices<F>,
     pub round_constants: Vec<F>,
@@ -77,4 +91,90 @@ impl<F: PrimeField> PoseidonConstants<F> {
             sparse_matrixes,
         }
     }
+
+    /// Returns the constants of specified width, which are generated on the
+    /// first call for each field and width and then shared.
+    pub fn preset<const WIDTH: usize>() -> Arc<Self> {
+        let presets = PRESETS.get_or_init(Default::default);
+        let key = (TypeId::of::<F>(), WIDTH);
+        let cached = presets.lock().unwrap().get(&key).cloned();
+        let constants = match cached {
+            Some(constants) => constants,
+            None => {
+                // Generate without holding the lock, another thread may win
+                // the race, in which case its constants are kept.
+                let constants: Arc<dyn Any + Send + Sync> = Arc::new(Self::generate::<WIDTH>());
+                presets.lock().unwrap().entry(key).or_insert(constants).clone()
+            }
+        };
+
+        constants
+            .downcast()
+            .unwrap_or_else(|_| panic!("preset has invalid type"))
+    }
+
+    /// Returns the Blake2s digest of the serialized constants, which can be
+    /// used to pin a set of constants.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut bytes = Vec::with_capacity(self.serialized_size());
+        self.serialize(&mut bytes).expect("serialization to vec never fails");
+
+        Blake2s::digest(&bytes).into()
+    }
+
+    /// Deserializes constants and checks that they match the pinned `digest`.
+    pub fn deserialize_pinned<R: Read>(
+        reader: R,
+        digest: &[u8; 32],
+    ) -> Result<Self, PoseidonError> {
+        let constants = Self::deserialize(reader)?;
+        if &constants.digest() != digest {
+            return Err(PoseidonError::DigestMismatch);
+        }
+
+        Ok(constants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+
+    use super::*;
+
+    #[test]
+    fn test_serialization() {
+        let constants = PoseidonConstants::<Fr>::generate::<5>();
+        let mut bytes = Vec::new();
+        constants.serialize(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), constants.serialized_size());
+        assert_eq!(PoseidonConstants::deserialize(bytes.as_slice()).unwrap(), constants);
+
+        let digest = constants.digest();
+        assert_eq!(
+            PoseidonConstants::<Fr>::deserialize_pinned(bytes.as_slice(), &digest).unwrap(),
+            constants,
+        );
+        assert_ne!(PoseidonConstants::<Fr>::generate::<3>().digest(), digest);
+
+        // corrupt the domain tag, which precedes the three round numbers
+        let len = bytes.len();
+        bytes[len - 56] ^= 1;
+        assert!(matches!(
+            PoseidonConstants::<Fr>::deserialize_pinned(bytes.as_slice(), &digest),
+            Err(PoseidonError::DigestMismatch),
+        ));
+    }
+
+    #[test]
+    fn test_presets() {
+        let constants = PoseidonConstants::<Fr>::preset::<3>();
+        assert!(Arc::ptr_eq(&constants, &PoseidonConstants::preset::<3>()));
+        assert_eq!(*constants, PoseidonConstants::generate::<3>());
+
+        let other_width = PoseidonConstants::<Fr>::preset::<5>();
+        assert_eq!(other_width.mds_matrices.m.num_rows(), 5);
+        let other_field = PoseidonConstants::<ark_bls12_381::Fr>::preset::<3>();
+        assert_eq!(*other_field, PoseidonConstants::generate::<3>());
+    }
 }
@@ -1,6 +1,7 @@
 //! acknowledgement: adapted from FileCoin Project: https://github.com/filecoin-project/neptune/blob/master/src/matrix.rs
 
 use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 use core::ops::{Index, IndexMut};
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -12,6 +13,22 @@ impl<T: Clone> From<Vec<Vec<T>>> for Matrix<T> {
     }
 }
 
+impl<T: Clone + CanonicalSerialize> CanonicalSerialize for Matrix<T> {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        self.0.serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        self.0.serialized_size()
+    }
+}
+
+impl<T: Clone + CanonicalDeserialize> CanonicalDeserialize for Matrix<T> {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        Ok(Matrix(Vec::deserialize(reader)?))
+    }
+}
+
 impl<T: Clone> Matrix<T> {
     pub fn num_rows(&self) -> usize {
         self.0.len()
@@ -2,10 +2,11 @@
 
 use ark_ff::vec::*;
 use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
 
 use super::matrix::Matrix;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, CanonicalDeserialize, CanonicalSerialize)]
 pub struct MdsMatrices<F: PrimeField> {
     pub m: Matrix<F>,
     pub m_inv: Matrix<F>,
@@ -122,7 +123,7 @@ impl<F: PrimeField> MdsMatrices<F> {
 /// (minor to the element in both the row and column) is the identity.
 /// We will pluralize this compact structure `sparse_matrixes` to distinguish
 /// from `sparse_matrices` from which they are created.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, CanonicalDeserialize, CanonicalSerialize)]
 pub struct SparseMatrix<F: PrimeField> {
     /// `w_hat` is the first column of the M'' matrix. It will be directly
     /// multiplied (scalar product) with a row of state elements.
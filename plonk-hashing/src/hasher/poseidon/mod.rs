@@ -11,10 +11,15 @@ pub use matrix::*;
 pub use mds::*;
 pub use spec::*;
 
+use ark_serialize::SerializationError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum PoseidonError {
     #[error("Buffer is full")]
     FullBuffer,
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] SerializationError),
+    #[error("Digest of constants doesn't match")]
+    DigestMismatch,
 }
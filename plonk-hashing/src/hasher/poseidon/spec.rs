@@ -1,9 +1,10 @@
 //! Correct, Naive, reference implementation of Poseidon hash function.
 
-use ark_ff::PrimeField;
+use std::sync::Arc;
+use ark_ff::{PrimeField, Zero};
 use core::{fmt::Debug, marker::PhantomData};
 use derivative::Derivative;
-use plonk_core::constraint_system::{ConstraintSystem, LTVariable};
+use plonk_core::constraint_system::{ConstraintSystem, Gadget, LTVariable, LinearCombination};
 
 use crate::hasher::FieldHasher;
 use super::{PoseidonError, constants::PoseidonConstants};
@@ -77,11 +78,12 @@ pub trait PoseidonRefSpec<CS, const WIDTH: usize> {
         let matrix = &constants.mds_matrices.m;
         let mut result = Self::zeros::<WIDTH>();
         for (j, val) in result.iter_mut().enumerate() {
-            for (i, row) in matrix.iter_rows().enumerate() {
-                // *val += row[j] * state[i];
-                let tmp = Self::mul_constant(cs, &state[i], &row[j]);
-                *val = Self::add(cs, val, &tmp);
-            }
+            let terms = matrix
+                .iter_rows()
+                .zip(state.iter())
+                .map(|(row, x)| (x.clone(), row[j]))
+                .collect::<Vec<_>>();
+            *val = Self::linear_combination(cs, &terms);
         }
         *state = result;
     }
@@ -133,6 +135,17 @@ pub trait PoseidonRefSpec<CS, const WIDTH: usize> {
         x: &Self::Field,
         y: &Self::ParameterField,
     ) -> Self::Field;
+
+    /// return sum(x_i * c_i) for `terms` of (x_i, c_i)
+    fn linear_combination(
+        cs: &mut CS,
+        terms: &[(Self::Field, Self::ParameterField)],
+    ) -> Self::Field {
+        terms.iter().fold(Self::zero(), |acc, (x, c)| {
+            let tmp = Self::mul_constant(cs, x, c);
+            Self::add(cs, &acc, &tmp)
+        })
+    }
 }
 
 pub struct NativeSpecRef<F: PrimeField> {
@@ -215,11 +228,90 @@ impl<F: PrimeField, const WIDTH: usize>
     ) -> Self::Field {
         x.linear_transform(*y, F::zero())
     }
+
+    fn linear_combination(
+        cs: &mut ConstraintSystem<F>,
+        terms: &[(Self::Field, Self::ParameterField)],
+    ) -> Self::Field {
+        let lc = terms
+            .iter()
+            .map(|(x, c)| x.linear_transform(*c, F::zero()))
+            .sum::<LinearCombination<F>>();
+        cs.materialize(&lc)
+    }
 }
 
+/// Poseidon hasher, which is stateless and only holds shared constants, so it
+/// is cheap to clone and can be used from several threads at once.
 #[derive(Derivative)]
-#[derivative(Debug(bound = ""))]
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
 pub struct PoseidonRef<CS, S: PoseidonRefSpec<CS, WIDTH>, const WIDTH: usize>
+where
+    S: ?Sized,
+{
+    pub(crate) constants: Arc<PoseidonConstants<S::ParameterField>>,
+    empty_hash: S::ParameterField,
+    #[derivative(Debug = "ignore")]
+    _spec: PhantomData<fn(&mut CS) -> Box<S>>,
+}
+
+impl<
+    CS,
+    S: PoseidonRefSpec<CS, WIDTH>,
+    const WIDTH: usize,
+> PoseidonRef<CS, S, WIDTH> {
+    pub fn new<C>(constants: C) -> Self
+    where
+        C: Into<Arc<PoseidonConstants<S::ParameterField>>>,
+    {
+        let constants = constants.into();
+        // The empty hash is the native hash of zeros.
+        let mut state = PoseidonState::<(), NativeSpecRef<S::ParameterField>, WIDTH>::new(
+            constants.clone(),
+        );
+        for _ in 1..WIDTH {
+            state
+                .input(S::ParameterField::zero())
+                .unwrap_or_else(|e| panic!("input failed: {}", e));
+        }
+        let empty_hash = state.output_hash(&mut ());
+
+        PoseidonRef {
+            constants,
+            empty_hash,
+            _spec: PhantomData,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        WIDTH - 1
+    }
+
+    pub fn constants(&self) -> &Arc<PoseidonConstants<S::ParameterField>> {
+        &self.constants
+    }
+
+    /// Returns a fresh state for absorbing inputs one by one.
+    pub fn state(&self) -> PoseidonState<CS, S, WIDTH> {
+        PoseidonState::new(self.constants.clone())
+    }
+}
+
+impl<
+    CS,
+    S: PoseidonRefSpec<CS, WIDTH>,
+    const WIDTH: usize,
+> Default for PoseidonRef<CS, S, WIDTH> {
+    /// The hasher over the [`PoseidonConstants::preset`] constants.
+    fn default() -> Self {
+        Self::new(PoseidonConstants::preset::<WIDTH>())
+    }
+}
+
+/// State of a single Poseidon hash computation.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct PoseidonState<CS, S: PoseidonRefSpec<CS, WIDTH>, const WIDTH: usize>
 where
     S: ?Sized,
 {
@@ -227,18 +319,18 @@ where
     pub(crate) current_round: usize,
     pub elements: [S::Field; WIDTH],
     pos: usize,
-    pub(crate) constants: PoseidonConstants<S::ParameterField>,
+    pub(crate) constants: Arc<PoseidonConstants<S::ParameterField>>,
 }
 
 impl<
     CS,
     S: PoseidonRefSpec<CS, WIDTH>,
     const WIDTH: usize,
-> PoseidonRef<CS, S, WIDTH> {
-    pub fn new(constants: PoseidonConstants<S::ParameterField>) -> Self {
+> PoseidonState<CS, S, WIDTH> {
+    pub fn new(constants: Arc<PoseidonConstants<S::ParameterField>>) -> Self {
         let mut elements = S::zeros();
         elements[0] = S::constant(constants.domain_tag);
-        PoseidonRef {
+        PoseidonState {
             constants_offset: 0,
             current_round: 0,
             elements,
@@ -247,18 +339,6 @@ impl<
         }
     }
 
-    pub fn arity(&self) -> usize {
-        WIDTH - 1
-    }
-
-    pub fn reset(&mut self) {
-        self.constants_offset = 0;
-        self.current_round = 0;
-        self.elements[1..].iter_mut().for_each(|l| *l = S::zero());
-        self.elements[0] = S::constant(self.constants.domain_tag);
-        self.pos = 1;
-    }
-
     /// input one field element to Poseidon. Return the position of the element
     /// in state.
     pub fn input(&mut self, input: S::Field) -> Result<usize, PoseidonError> {
@@ -275,7 +355,7 @@ impl<
     }
 
     /// Output the hash
-    pub fn output_hash(&mut self, cs: &mut CS) -> S::Field {
+    pub fn output_hash(mut self, cs: &mut CS) -> S::Field {
         S::full_round(
             cs,
             &self.constants,
@@ -327,16 +407,37 @@ impl<
     const WIDTH: usize,
 > FieldHasher<CS, S::Field> for PoseidonRef<CS, S, WIDTH> {
 
-    fn empty_hash() -> S::Field {
-        S::zero()
+    fn empty_hash(&self) -> S::Field {
+        S::constant(self.empty_hash)
     }
 
-    fn hash(&mut self, cs: &mut CS, input: &[S::Field]) -> S::Field {
-        self.reset();
+    fn hash(&self, cs: &mut CS, input: &[S::Field]) -> S::Field {
+        let mut state = self.state();
         for element in input {
-            self.input(element.clone()).unwrap_or_else(|e| panic!("input failed: {}", e));
+            state.input(element.clone()).unwrap_or_else(|e| panic!("input failed: {}", e));
         }
-        self.output_hash(cs)
+        state.output_hash(cs)
+    }
+}
+
+impl<F: PrimeField, const WIDTH: usize> Gadget<F>
+    for PoseidonRef<ConstraintSystem<F>, PlonkSpecRef, WIDTH>
+{
+    type NativeInput = [F];
+    type NativeOutput = F;
+    type Input = [LTVariable<F>];
+    type Output = LTVariable<F>;
+
+    fn native(&self, input: &[F]) -> F {
+        let mut state = PoseidonState::<(), NativeSpecRef<F>, WIDTH>::new(self.constants.clone());
+        for element in input {
+            state.input(*element).unwrap_or_else(|e| panic!("input failed: {}", e));
+        }
+        state.output_hash(&mut ())
+    }
+
+    fn synthesize(&self, cs: &mut ConstraintSystem<F>, input: &[LTVariable<F>]) -> LTVariable<F> {
+        self.hash(cs, input)
     }
 }
 
@@ -361,9 +462,9 @@ mod tests {
                 let rng = &mut test_rng();
 
                 // native poseidon
-                let param = PoseidonConstants::generate::<WIDTH>();
+                let param = PoseidonConstants::preset::<WIDTH>();
                 let mut poseidon =
-                    PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param.clone());
+                    PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param.clone()).state();
                 let inputs = (0..ARITY).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
                 inputs.iter().for_each(|x| {
                     let _ = poseidon.input(*x).unwrap();
@@ -374,7 +475,8 @@ mod tests {
                 let inputs_var =
                     inputs.iter().map(|x| cs.assign_variable(*x)).collect::<Vec<_>>();
                 let mut poseidon =
-                    PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(param.clone());
+                    PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(param.clone())
+                        .state();
                 inputs_var.into_iter().for_each(|x| {
                     let _ = poseidon.input(x.into()).unwrap();
                 });
@@ -394,12 +496,50 @@ mod tests {
         const WIDTH: usize = ARITY + 1;
         let mut rng = test_rng();
 
-        let param = PoseidonConstants::generate::<WIDTH>();
+        let param = PoseidonConstants::preset::<WIDTH>();
         let mut poseidon =
-            PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param);
+            PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param).state();
         (0..(ARITY + 1)).for_each(|_| {
             let _ = poseidon.input(Fr::rand(&mut rng)).unwrap();
         });
         let _ = poseidon.output_hash(&mut ());
     }
+
+    #[test]
+    fn test_shared_hasher() {
+        const WIDTH: usize = 3;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PoseidonRef<(), NativeSpecRef<Fr>, WIDTH>>();
+        assert_send_sync::<PoseidonRef<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>>();
+
+        let rng = &mut test_rng();
+        let param = PoseidonConstants::preset::<WIDTH>();
+        let poseidon = PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param.clone());
+        let inputs = (0..8).map(|_| [Fr::rand(rng), Fr::rand(rng)]).collect::<Vec<_>>();
+        let expected = inputs
+            .iter()
+            .map(|input| poseidon.hash(&mut (), input))
+            .collect::<Vec<_>>();
+
+        let hashes = std::thread::scope(|s| {
+            inputs
+                .iter()
+                .map(|input| s.spawn(|| poseidon.hash(&mut (), input)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(hashes, expected);
+
+        // the empty hash is derived from the constants
+        assert_eq!(poseidon.empty_hash(), poseidon.hash(&mut (), &[Fr::zero(); 2]));
+        let circuit_poseidon =
+            PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(param);
+        assert_eq!(
+            circuit_poseidon.empty_hash(),
+            LTVariable::constant(poseidon.empty_hash()),
+        );
+    }
 }
@@ -0,0 +1,262 @@
+//! SHA-256 in circuit, as specified by FIPS 180-4.
+//!
+//! Like [`keccak`](super::keccak), words are kept as little-endian [`Byte`]s,
+//! so XOR and AND are byte lookups. Rotations by whole bytes are free, and
+//! the others split every byte with an AND lookup, which is still cheaper
+//! than XOR and AND over bits. Additions recompose the words from their
+//! bytes and decompose the sum with a carry byte. A compression thus takes
+//! about 27k gates, against about 70k with words of [`Boolean`] gates. The
+//! lookup table of the circuit must contain
+//! [`byte_table`](plonk_core::constraint_system::byte_table).
+//!
+//! [`Boolean`]: plonk_core::constraint_system::Boolean
+
+use ark_ff::PrimeField;
+use itertools::Itertools;
+use plonk_core::constraint_system::{Byte, ConstraintSystem, LTVariable};
+/// Initial hash value.
+pub const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Word as little-endian bytes.
+type Word = Vec<Byte>;
+
+fn constant_word<F: PrimeField>(cs: &mut ConstraintSystem<F>, value: u32) -> Word {
+    value.to_le_bytes().into_iter().map(|byte| cs.constant_byte(byte)).collect()
+}
+
+fn xor_words<F: PrimeField>(cs: &mut ConstraintSystem<F>, x: &Word, y: &Word) -> Word {
+    x.iter().zip(y).map(|(&x, &y)| cs.xor_byte_gate(x, y)).collect()
+}
+
+fn and_words<F: PrimeField>(cs: &mut ConstraintSystem<F>, x: &Word, y: &Word) -> Word {
+    x.iter().zip(y).map(|(&x, &y)| cs.and_byte_gate(x, y)).collect()
+}
+
+/// XOR of three words.
+fn xor3<F: PrimeField>(cs: &mut ConstraintSystem<F>, a: &Word, b: &Word, c: &Word) -> Word {
+    let ab = xor_words(cs, a, b);
+    xor_words(cs, &ab, c)
+}
+
+fn rotr<F: PrimeField>(cs: &mut ConstraintSystem<F>, x: &Word, by: usize) -> Word {
+    cs.rotl_bytes_le(x, 32 - by)
+}
+
+/// Shifts right by rotating and clearing the rotated bits, whole bytes for
+/// free and the others by an AND lookup.
+fn shr<F: PrimeField>(cs: &mut ConstraintSystem<F>, x: &Word, by: usize) -> Word {
+    rotr(cs, x, by)
+        .into_iter()
+        .enumerate()
+        .map(|(i, byte)| match (32 - by).saturating_sub(8 * i).min(8) {
+            8 => byte,
+            0 => cs.constant_byte(0),
+            kept => cs.and_constant_byte_gate(byte, (1 << kept) - 1),
+        })
+        .collect()
+}
+
+/// Adds the words and a constant modulo `2^32`. The sum of up to 255 words
+/// has a carry of a byte, which is dropped.
+fn add_words<F: PrimeField>(cs: &mut ConstraintSystem<F>, words: &[Word], constant: u32) -> Word {
+    assert!(!words.is_empty() && words.len() < 256, "number of words is out of range");
+
+    let mut terms = words.iter().flat_map(|word| {
+        word.iter().enumerate().map(|(i, &byte)| {
+            LTVariable::from(byte).linear_transform(F::from(1u64 << (8 * i)), F::zero())
+        })
+    });
+    let first = terms.next().unwrap().linear_transform(F::one(), F::from(constant));
+    let sum = terms.fold(first, |acc, term| cs.add_gate(&acc, &term).into());
+
+    let mut bytes = cs.to_bytes_le(&sum, 5);
+    bytes.truncate(4);
+    bytes
+}
+
+/// Compresses a block of 64 bytes into the state of 8 words, which are
+/// given and returned as little-endian bytes.
+pub fn sha256_compression<F: PrimeField>(
+    cs: &mut ConstraintSystem<F>,
+    state: &[Byte],
+    block: &[Byte],
+) -> Vec<Byte> {
+    assert_eq!(state.len(), 32, "invalid state length");
+    assert_eq!(block.len(), 64, "invalid block length");
+
+    // message schedule, whose words are big-endian in the block
+    let mut w = block
+        .chunks(4)
+        .map(|word| word.iter().rev().copied().collect_vec())
+        .collect_vec();
+    for t in 16..64 {
+        let x = &w[t - 15];
+        let (r7, r18, s3) = (rotr(cs, x, 7), rotr(cs, x, 18), shr(cs, x, 3));
+        let s0 = xor3(cs, &r7, &r18, &s3);
+        let x = &w[t - 2];
+        let (r17, r19, s10) = (rotr(cs, x, 17), rotr(cs, x, 19), shr(cs, x, 10));
+        let s1 = xor3(cs, &r17, &r19, &s10);
+        let word = add_words(cs, &[w[t - 16].clone(), s0, w[t - 7].clone(), s1], 0);
+        w.push(word);
+    }
+
+    let state = state.chunks(4).map(|word| word.to_vec()).collect_vec();
+    let mut v = state.clone();
+    for t in 0..64 {
+        let (a, b, c, d) = (&v[0], &v[1], &v[2], &v[3]);
+        let (e, f, g, h) = (&v[4], &v[5], &v[6], &v[7]);
+
+        let (r6, r11, r25) = (rotr(cs, e, 6), rotr(cs, e, 11), rotr(cs, e, 25));
+        let s1 = xor3(cs, &r6, &r11, &r25);
+        // ch = (e AND f) XOR ((NOT e) AND g) = g XOR (e AND (f XOR g))
+        let f_xor_g = xor_words(cs, f, g);
+        let e_and_fg = and_words(cs, e, &f_xor_g);
+        let ch = xor_words(cs, g, &e_and_fg);
+        let temp1 = add_words(cs, &[h.clone(), s1, ch, w[t].clone()], ROUND_CONSTANTS[t]);
+
+        let (r2, r13, r22) = (rotr(cs, a, 2), rotr(cs, a, 13), rotr(cs, a, 22));
+        let s0 = xor3(cs, &r2, &r13, &r22);
+        // maj = (a AND b) XOR (a AND c) XOR (b AND c) = (a AND b) XOR (c AND (a XOR b))
+        let a_and_b = and_words(cs, a, b);
+        let a_xor_b = xor_words(cs, a, b);
+        let c_and_ab = and_words(cs, c, &a_xor_b);
+        let maj = xor_words(cs, &a_and_b, &c_and_ab);
+
+        let new_e = add_words(cs, &[d.clone(), temp1.clone()], 0);
+        let new_a = add_words(cs, &[temp1, s0, maj], 0);
+
+        v.rotate_right(1);
+        v[0] = new_a;
+        v[4] = new_e;
+    }
+
+    state
+        .into_iter()
+        .zip(v)
+        .flat_map(|(x, y)| add_words(cs, &[x, y], 0))
+        .collect()
+}
+
+/// Returns the SHA-256 digest of `input` bytes, which are padded in circuit.
+/// The length of the input is part of the circuit structure.
+pub fn sha256<F: PrimeField>(cs: &mut ConstraintSystem<F>, input: &[Byte]) -> Vec<Byte> {
+    // padding: 0x80, zeros up to 56 mod 64 bytes, then the bit length
+    let bit_len = (input.len() as u64) * 8;
+    let zeros = (119 - input.len() % 64) % 64;
+    let padding = [0x80u8]
+        .into_iter()
+        .chain(core::iter::repeat(0).take(zeros))
+        .chain(bit_len.to_be_bytes())
+        .map(|byte| cs.constant_byte(byte))
+        .collect_vec();
+    let message = input.iter().copied().chain(padding).collect_vec();
+    debug_assert_eq!(message.len() % 64, 0);
+
+    let mut state = IV.iter().flat_map(|&word| constant_word(cs, word)).collect_vec();
+    for block in message.chunks(64) {
+        state = sha256_compression(cs, &state, block);
+    }
+
+    // the digest is big-endian
+    state
+        .chunks(4)
+        .flat_map(|word| word.iter().rev().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, rand::{Rng, RngCore}};
+    use plonk_core::constraint_system::{byte_table, test_gate_constraints_with_table};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    fn new_byte(cs: &mut ConstraintSystem<Fr>, value: u8) -> Byte {
+        let x = cs.assign_variable(Fr::from(value));
+        cs.byte_gate(x)
+    }
+
+    fn test_sha256(input: &[u8]) {
+        let expected = Sha256::digest(input);
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let input_vars = input.iter().map(|&byte| new_byte(cs, byte)).collect_vec();
+                let digest = sha256(cs, &input_vars);
+                assert_eq!(digest.len(), 32);
+
+                digest
+                    .into_iter()
+                    .zip(expected.iter())
+                    .map(|(byte, &value)| (byte.into(), Fr::from(value)))
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        );
+    }
+
+    #[test]
+    fn test_sha256_abc() {
+        test_sha256(b"abc");
+    }
+
+    #[test]
+    fn test_sha256_empty() {
+        test_sha256(b"");
+    }
+
+    #[test]
+    fn test_sha256_padding_boundaries() {
+        let rng = &mut test_rng();
+        // 55 bytes fit in one block with padding, 56 and 64 bytes need two
+        for len in [55, 56, 64] {
+            let mut input = vec![0u8; len];
+            rng.fill_bytes(&mut input);
+            test_sha256(&input);
+        }
+    }
+
+    #[test]
+    fn test_sha256_compression() {
+        // a random block, compressed from the initial hash value
+        let rng = &mut test_rng();
+        let block: [u8; 64] = std::array::from_fn(|_| rng.gen());
+        let mut state = IV;
+        sha2::compress256(&mut state, &[block.iter().copied().collect()]);
+        test_gate_constraints_with_table(
+            |cs| -> Vec<_> {
+                let iv = IV
+                    .iter()
+                    .flat_map(|word| word.to_le_bytes())
+                    .map(|byte| new_byte(cs, byte))
+                    .collect_vec();
+                let block = block.iter().map(|&byte| new_byte(cs, byte)).collect_vec();
+                let output = sha256_compression(cs, &iv, &block);
+
+                output
+                    .into_iter()
+                    .zip(state.iter().flat_map(|word| word.to_le_bytes()))
+                    .map(|(byte, value)| (byte.into(), Fr::from(value)))
+                    .collect()
+            },
+            &[],
+            byte_table(),
+        );
+    }
+}
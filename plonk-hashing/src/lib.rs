@@ -13,5 +13,6 @@
 
 pub extern crate alloc;
 
+pub mod eddsa;
 pub mod hasher;
 pub mod merkle;
@@ -1,11 +1,14 @@
 use ark_ff::Field;
 use itertools::Itertools;
-use plonk_core::constraint_system::{ConstraintSystem, Boolean, LTVariable};
+use plonk_core::{
+    constraint_system::{AllocMode, AllocVar, Boolean, ConstraintSystem, Gadget, LTVariable},
+    error::Error,
+};
 
-use crate::hasher::FieldHasher;
+use crate::hasher::{FieldHasher, FieldHasherGadget};
 
 pub fn native_merkle_proof<F, H>(
-    hasher: &mut H,
+    hasher: &H,
     path_elements: impl IntoIterator<Item = (bool, F)>,
     leaf_node: F,
 ) -> Vec<F>
@@ -28,70 +31,113 @@ where
         .collect()
 }
 
-fn merkle_proof<F, H>(
-    hasher: &mut H,
-    cs: &mut ConstraintSystem<F>,
-    path_elements: impl IntoIterator<Item = (Boolean, LTVariable<F>)>,
-    leaf_node: &LTVariable<F>,
-) -> Vec<LTVariable<F>>
-where
-    F: Field,
-    H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>,
-{
-    let mut cur_hash = *leaf_node;
-    path_elements
-        .into_iter()
-        .map(|(is_left, node_hash)| {
-            let left_node = cs.conditional_select(is_left, &node_hash, &cur_hash);
-            let right_node = cs.conditional_select(is_left, &cur_hash, &node_hash);
-            cur_hash = hasher.hash_two(cs, &left_node.into(), &right_node.into());
-
-            cur_hash
-        })
-        .collect()
-}
-
 /// Proof of Existance Circuit
+#[derive(Clone, Debug)]
 pub struct PoECircuit<F: Field, const HEIGHT: usize> {
     leaf_index: u64,
     path_elements: Vec<F>,
 }
 
 impl<F: Field, const HEIGHT: usize> PoECircuit<F, HEIGHT> {
-    pub fn synthesize<H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>>(
-        self,
+    pub fn new(leaf_index: u64, path_elements: Vec<F>) -> Self {
+        Self { leaf_index, path_elements }
+    }
+
+    /// Returns whether the node at each layer is the left child, from the
+    /// leaf up.
+    pub fn positions(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..HEIGHT).map(|layer| (self.leaf_index >> layer) & 1 == 1)
+    }
+}
+
+impl<F: Field, const HEIGHT: usize> Default for PoECircuit<F, HEIGHT> {
+    /// The path of the first leaf with zero nodes, which has the shape of
+    /// any path.
+    fn default() -> Self {
+        Self::new(0, vec![F::zero(); HEIGHT])
+    }
+}
+
+/// Authentication path in circuit, whose positions are constrained to be
+/// booleans.
+#[derive(Clone, Debug)]
+pub struct PoEVar<F: Field, const HEIGHT: usize> {
+    positions: Vec<Boolean>,
+    path_elements: Vec<LTVariable<F>>,
+}
+
+impl<F: Field, const HEIGHT: usize> PoEVar<F, HEIGHT> {
+    ///
+    pub fn positions(&self) -> &[Boolean] {
+        &self.positions
+    }
+}
+
+impl<F: Field, const HEIGHT: usize> AllocVar<PoECircuit<F, HEIGHT>, F> for PoEVar<F, HEIGHT> {
+    fn new_variable(
         cs: &mut ConstraintSystem<F>,
-        hasher: &mut H,
-        leaf_node: &LTVariable<F>,
-    ) -> (LTVariable<F>, Vec<Boolean>) {
-        assert_eq!(self.path_elements.len(), HEIGHT, "invalid auth path length");
-
-        let positions = (0..HEIGHT)
-            .map(|layer| {
-                let value = (self.leaf_index >> layer) & 1 == 1;
-                let var = cs.assign_variable(value.into());
-                cs.boolean_gate(var)
+        value: &PoECircuit<F, HEIGHT>,
+        mode: AllocMode,
+    ) -> Result<Self, Error> {
+        if value.path_elements.len() != HEIGHT {
+            return Err(Error::InvalidValueShape);
+        }
+
+        let positions = value.positions().collect_vec();
+        Ok(Self {
+            positions: Vec::new_variable(cs, &positions, mode)?,
+            path_elements: Vec::new_variable(cs, &value.path_elements, mode)?,
+        })
+    }
+}
+
+/// Computes the Merkle root of a leaf from its authentication path.
+#[derive(Debug)]
+pub struct PoEGadget<'a, H, const HEIGHT: usize> {
+    hasher: &'a H,
+}
+
+impl<'a, H, const HEIGHT: usize> PoEGadget<'a, H, HEIGHT> {
+    pub fn new(hasher: &'a H) -> Self {
+        Self { hasher }
+    }
+}
+
+impl<'a, F, H, const HEIGHT: usize> Gadget<F> for PoEGadget<'a, H, HEIGHT>
+where
+    F: Field,
+    H: FieldHasherGadget<F>,
+{
+    /// The leaf and its path.
+    type NativeInput = (F, PoECircuit<F, HEIGHT>);
+    /// The root.
+    type NativeOutput = F;
+    type Input = (LTVariable<F>, PoEVar<F, HEIGHT>);
+    type Output = LTVariable<F>;
+
+    fn native(&self, (leaf, path): &Self::NativeInput) -> F {
+        assert_eq!(path.path_elements.len(), HEIGHT, "invalid auth path length");
+
+        path.positions()
+            .zip(path.path_elements.iter())
+            .fold(*leaf, |cur_hash, (is_left, &node_hash)| {
+                if is_left {
+                    self.hasher.native(&[node_hash, cur_hash])
+                } else {
+                    self.hasher.native(&[cur_hash, node_hash])
+                }
             })
-            .collect_vec();
-        let witness_elements = positions
-            .clone()
-            .into_iter()
-            .zip(
-                self.path_elements
-                    .into_iter()
-                    .map(|node| cs.assign_variable(node).into())
-            )
-            .collect_vec();
-
-        let mut paths = merkle_proof(
-            hasher,
-            cs,
-            witness_elements,
-            leaf_node,
-        );
-        let root = paths.pop().unwrap();
+    }
 
-        (root, positions)
+    fn synthesize(&self, cs: &mut ConstraintSystem<F>, (leaf, path): &Self::Input) -> LTVariable<F> {
+        path.positions
+            .iter()
+            .zip(path.path_elements.iter())
+            .fold(*leaf, |cur_hash, (&is_left, node_hash)| {
+                let left_node = cs.conditional_select(is_left, node_hash, &cur_hash);
+                let right_node = cs.conditional_select(is_left, &cur_hash, node_hash);
+                self.hasher.synthesize(cs, &[left_node.into(), right_node.into()])
+            })
     }
 }
 
@@ -113,11 +159,11 @@ mod tests {
         (0..HEIGHT).map(|_| (bool::rand(rng), Fr::rand(rng))).collect()
     }
 
-    fn native_poseidon_hasher(param: PoseidonConstants<Fr>) -> PoseidonRef<(), NativeSpecRef<Fr>, WIDTH> {
+    fn native_poseidon_hasher(param: std::sync::Arc<PoseidonConstants<Fr>>) -> PoseidonRef<(), NativeSpecRef<Fr>, WIDTH> {
         PoseidonRef::new(param)
     }
 
-    fn poseidon_hasher(param: PoseidonConstants<Fr>) -> PoseidonRef<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH> {
+    fn poseidon_hasher(param: std::sync::Arc<PoseidonConstants<Fr>>) -> PoseidonRef<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH> {
         PoseidonRef::new(param)
     }
 
@@ -126,37 +172,48 @@ mod tests {
         test_gate_constraints(
             |cs| {
                 let rng = &mut test_rng();
-                let param = PoseidonConstants::generate::<WIDTH>();
-                let mut hasher = native_poseidon_hasher(param.clone());
+                let param = PoseidonConstants::preset::<WIDTH>();
+                let hasher = native_poseidon_hasher(param.clone());
 
                 // native merkle path computation
                 let leaf = Fr::rand(rng);
                 let witness_nodes: Vec<(bool, ark_ff::Fp256<ark_bn254::FrParameters>)> = random_merkle_witness(rng);
                 let mut paths = native_merkle_proof(
-                    &mut hasher,
+                    &hasher,
                     witness_nodes.clone(),
                     leaf,
                 );
                 let root = paths.pop().unwrap();
 
                 // circuit merkle path computation
-                let mut hasher = poseidon_hasher(param);
+                let hasher = poseidon_hasher(param);
                 let (index_iter, nodes_iter): (Vec<_>, Vec<_>)
                     = witness_nodes.into_iter().unzip();
                 let circuit = PoECircuit::<Fr, HEIGHT> {
                     leaf_index: BitVec::<u8>::from_iter(index_iter).load_le(),
                     path_elements: nodes_iter,
                 };
-                let leaf_var = cs.assign_variable(leaf);
-                let (root_var, _) = circuit.synthesize(
-                    cs,
-                    &mut hasher,
-                    &leaf_var.into(),
-                );
+                let gadget = PoEGadget::new(&hasher);
+                assert_eq!(gadget.native(&(leaf, circuit.clone())), root);
+
+                let leaf_var = LTVariable::new_witness(cs, &leaf).unwrap();
+                let path_var = PoEVar::new_witness(cs, &circuit).unwrap();
+                assert_eq!(path_var.positions().len(), HEIGHT);
+                let root_var = gadget.synthesize(cs, &(leaf_var, path_var));
 
                 [(root_var, root)]
             },
             &[],
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_path_of_other_height() {
+        let mut cs = ConstraintSystem::<Fr>::new(false, Default::default());
+        let circuit = PoECircuit::<Fr, HEIGHT>::new(0, vec![Fr::from(1u64); HEIGHT - 1]);
+        assert!(matches!(
+            PoEVar::new_optional(&mut cs, Some(&circuit), AllocMode::Witness),
+            Err(Error::InvalidValueShape),
+        ));
+    }
+}
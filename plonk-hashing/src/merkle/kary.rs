@@ -0,0 +1,267 @@
+use core::cmp::Ordering;
+use ark_ff::Field;
+use itertools::Itertools;
+use plonk_core::constraint_system::{ConstraintSystem, Boolean, LTVariable};
+
+use crate::hasher::FieldHasher;
+
+/// Returns the number of index bits of each layer, `ARITY` must be a power of
+/// two.
+fn position_bits<const ARITY: usize>() -> usize {
+    assert!(
+        ARITY >= 2 && ARITY.is_power_of_two(),
+        "arity must be a power of two",
+    );
+
+    ARITY.trailing_zeros() as usize
+}
+
+/// Inserts `node` at `position` among its siblings.
+fn children_of<T: Clone>(position: usize, node: &T, siblings: &[T]) -> Vec<T> {
+    let mut children = siblings.to_vec();
+    children.insert(position, node.clone());
+    children
+}
+
+pub fn native_kary_merkle_proof<F, H, const ARITY: usize>(
+    hasher: &H,
+    path_elements: impl IntoIterator<Item = (usize, Vec<F>)>,
+    leaf_node: F,
+) -> Vec<F>
+where
+    F: Field,
+    H: FieldHasher<(), F>,
+{
+    let mut cur_hash = leaf_node;
+    path_elements
+        .into_iter()
+        .map(|(position, siblings)| {
+            assert!(position < ARITY, "invalid position");
+            assert_eq!(siblings.len(), ARITY - 1, "invalid siblings length");
+
+            let children = children_of(position, &cur_hash, &siblings);
+            cur_hash = hasher.hash(&mut (), &children);
+
+            cur_hash
+        })
+        .collect()
+}
+
+fn kary_merkle_proof<F, H, const ARITY: usize>(
+    hasher: &H,
+    cs: &mut ConstraintSystem<F>,
+    path_elements: impl IntoIterator<Item = (Vec<Boolean>, Vec<LTVariable<F>>)>,
+    leaf_node: &LTVariable<F>,
+) -> Vec<LTVariable<F>>
+where
+    F: Field,
+    H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>,
+{
+    let mut cur_hash = *leaf_node;
+    path_elements
+        .into_iter()
+        .map(|(position, siblings)| {
+            // For position `p`, the child `j` is:
+            // j < p => siblings[j],
+            // j == p => current hash,
+            // j > p => siblings[j - 1].
+            let children = (0..ARITY)
+                .map(|j| {
+                    let choices = (0..ARITY)
+                        .map(|p| match p.cmp(&j) {
+                            Ordering::Greater => siblings[j],
+                            Ordering::Equal => cur_hash,
+                            Ordering::Less => siblings[j - 1],
+                        })
+                        .collect_vec();
+                    cs.multiplexer(&position, &choices)
+                })
+                .collect_vec();
+            cur_hash = hasher.hash(cs, &children);
+
+            cur_hash
+        })
+        .collect()
+}
+
+/// Proof of Existance Circuit of a Merkle tree with `ARITY` children per node.
+///
+/// Each layer selects the position of the current node among its siblings by
+/// a `log2(ARITY)`-bit index, so the positions of all layers concatenated are
+/// the little-endian bits of the leaf index.
+pub struct KaryPoECircuit<F: Field, const ARITY: usize, const HEIGHT: usize> {
+    leaf_index: u64,
+    path_elements: Vec<Vec<F>>,
+}
+
+/// Proof of Existance Circuit of a quaternary Merkle tree.
+pub type QuaternaryPoECircuit<F, const HEIGHT: usize> = KaryPoECircuit<F, 4, HEIGHT>;
+
+/// Proof of Existance Circuit of an octary Merkle tree.
+pub type OctaryPoECircuit<F, const HEIGHT: usize> = KaryPoECircuit<F, 8, HEIGHT>;
+
+impl<F: Field, const ARITY: usize, const HEIGHT: usize> KaryPoECircuit<F, ARITY, HEIGHT> {
+    pub fn new(leaf_index: u64, path_elements: Vec<Vec<F>>) -> Self {
+        Self { leaf_index, path_elements }
+    }
+
+    pub fn synthesize<H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>>(
+        self,
+        cs: &mut ConstraintSystem<F>,
+        hasher: &H,
+        leaf_node: &LTVariable<F>,
+    ) -> (LTVariable<F>, Vec<Boolean>) {
+        assert_eq!(self.path_elements.len(), HEIGHT, "invalid auth path length");
+        let bits = position_bits::<ARITY>();
+
+        // bits beyond the 64 bits of the index are zero in trees of more leaves
+        let positions = (0..HEIGHT * bits)
+            .map(|i| {
+                let value = self
+                    .leaf_index
+                    .checked_shr(i as u32)
+                    .is_some_and(|index| index & 1 == 1);
+                let var = cs.assign_variable(value.into());
+                cs.boolean_gate(var)
+            })
+            .collect_vec();
+        let witness_elements = positions
+            .chunks(bits)
+            .map(|position| position.to_vec())
+            .zip(
+                self.path_elements
+                    .into_iter()
+                    .map(|siblings| {
+                        assert_eq!(siblings.len(), ARITY - 1, "invalid siblings length");
+                        siblings
+                            .into_iter()
+                            .map(|node| cs.assign_variable(node).into())
+                            .collect_vec()
+                    })
+                    .collect_vec()
+            )
+            .collect_vec();
+
+        let mut paths = kary_merkle_proof::<_, _, ARITY>(
+            hasher,
+            cs,
+            witness_elements,
+            leaf_node,
+        );
+        let root = paths.pop().unwrap();
+
+        (root, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, UniformRand, rand::{Rng, prelude::StdRng}};
+    use plonk_core::constraint_system::test_gate_constraints;
+
+    use crate::hasher::poseidon::*;
+    use super::*;
+
+    /// Positions of the layers beyond the 64 bits of a leaf index are zero.
+    fn random_merkle_witness<const ARITY: usize>(
+        rng: &mut StdRng,
+        height: usize,
+    ) -> Vec<(usize, Vec<Fr>)> {
+        let bits = position_bits::<ARITY>();
+        (0..height)
+            .map(|layer| {
+                let position = if layer * bits < 64 { rng.gen_range(0..ARITY) } else { 0 };
+                let siblings = (1..ARITY).map(|_| Fr::rand(rng)).collect();
+                (position, siblings)
+            })
+            .collect()
+    }
+
+    fn test_kary_circuit<const ARITY: usize, const WIDTH: usize, const HEIGHT: usize>() {
+        test_gate_constraints(
+            |cs| {
+                let rng = &mut test_rng();
+                let param = PoseidonConstants::preset::<WIDTH>();
+                let hasher = PoseidonRef::<(), NativeSpecRef<Fr>, WIDTH>::new(param.clone());
+
+                // native merkle path computation
+                let leaf = Fr::rand(rng);
+                let witness_nodes = random_merkle_witness::<ARITY>(rng, HEIGHT);
+                let mut paths = native_kary_merkle_proof::<_, _, ARITY>(
+                    &hasher,
+                    witness_nodes.clone(),
+                    leaf,
+                );
+                let root = paths.pop().unwrap();
+
+                // circuit merkle path computation
+                let hasher =
+                    PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(param);
+                let (positions, nodes): (Vec<_>, Vec<_>) = witness_nodes.into_iter().unzip();
+                let leaf_index = positions
+                    .into_iter()
+                    .rev()
+                    .fold(0u64, |acc, position| acc * ARITY as u64 + position as u64);
+                let circuit = KaryPoECircuit::<Fr, ARITY, HEIGHT>::new(leaf_index, nodes);
+                let leaf_var = cs.assign_variable(leaf);
+                let (root_var, _) = circuit.synthesize(
+                    cs,
+                    &hasher,
+                    &leaf_var.into(),
+                );
+
+                [(root_var, root)]
+            },
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_quaternary_circuit() {
+        test_kary_circuit::<4, 5, 10>();
+    }
+
+    #[test]
+    fn test_octary_circuit() {
+        test_kary_circuit::<8, 9, 7>();
+    }
+
+    #[test]
+    fn test_tall_quaternary_circuit() {
+        // 68 index bits
+        test_kary_circuit::<4, 5, 34>();
+    }
+
+    #[test]
+    fn test_binary_circuit_matches_binary_tree() {
+        use crate::merkle::binary::native_merkle_proof;
+
+        let rng = &mut test_rng();
+        let param = PoseidonConstants::preset::<3>();
+        let hasher = PoseidonRef::<(), NativeSpecRef<Fr>, 3>::new(param);
+
+        let leaf = Fr::rand(rng);
+        let witness_nodes = random_merkle_witness::<2>(rng, 8);
+        let kary_paths = native_kary_merkle_proof::<_, _, 2>(
+            &hasher,
+            witness_nodes.clone(),
+            leaf,
+        );
+        let binary_paths = native_merkle_proof(
+            &hasher,
+            witness_nodes
+                .into_iter()
+                .map(|(position, siblings)| (position == 1, siblings[0])),
+            leaf,
+        );
+
+        assert_eq!(kary_paths, binary_paths);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_arity() {
+        position_bits::<3>();
+    }
+}
@@ -1,2 +1,5 @@
-
-pub mod binary;
\ No newline at end of file
+pub mod binary;
+pub mod kary;
+pub mod multi;
+pub mod store;
+pub mod tree;
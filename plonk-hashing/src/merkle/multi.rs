@@ -0,0 +1,365 @@
+use ark_ff::Field;
+use itertools::Itertools;
+use plonk_core::constraint_system::{ConstraintSystem, Boolean, LTVariable};
+
+use crate::hasher::FieldHasher;
+
+/// Compact Merkle multi-proof of several leaves of a binary tree.
+///
+/// Only the sibling nodes that cannot be computed from the leaves themselves
+/// are kept. They are ordered as consumed by a bottom-up, left-to-right walk
+/// over the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiProof<F: Field> {
+    leaf_indices: Vec<u64>,
+    nodes: Vec<F>,
+}
+
+/// A group of adjacent leaves sharing the same node at the current layer.
+#[derive(Clone, Copy, Debug)]
+struct Group<T> {
+    start: usize,
+    end: usize,
+    node: T,
+}
+
+/// Returns the layer at which the paths of two distinct leaves join, i.e. the
+/// layer where their nodes become siblings.
+fn merge_level(x: u64, y: u64) -> usize {
+    assert_ne!(x, y, "leaf indices must be distinct");
+    (63 - (x ^ y).leading_zeros()) as usize
+}
+
+/// Walks a binary tree of height `height` from the leaves to the root.
+///
+/// At each layer two adjacent groups are merged if their boundary is in
+/// `merge_levels`, otherwise a group is combined with a sibling node taken
+/// from outside. `combine` receives the layer, the (left) group and the right
+/// group in case of a merge.
+fn walk<T, C>(height: usize, merge_levels: &[usize], leaves: &[T], mut combine: C) -> T
+where
+    T: Clone,
+    C: FnMut(usize, &Group<T>, Option<&Group<T>>) -> T,
+{
+    assert!(!leaves.is_empty(), "leaves must not be empty");
+    assert_eq!(merge_levels.len() + 1, leaves.len(), "invalid merge levels length");
+    assert!(
+        merge_levels.iter().all(|&level| level < height),
+        "merge level exceeds tree height",
+    );
+
+    let mut groups = leaves
+        .iter()
+        .enumerate()
+        .map(|(i, leaf)| Group { start: i, end: i + 1, node: leaf.clone() })
+        .collect_vec();
+    for layer in 0..height {
+        let mut next_groups = Vec::with_capacity(groups.len());
+        let mut i = 0;
+        while i < groups.len() {
+            let group = &groups[i];
+            if i + 1 < groups.len() && merge_levels[group.end - 1] == layer {
+                let right = &groups[i + 1];
+                next_groups.push(Group {
+                    start: group.start,
+                    end: right.end,
+                    node: combine(layer, group, Some(right)),
+                });
+                i += 2;
+            } else {
+                next_groups.push(Group {
+                    start: group.start,
+                    end: group.end,
+                    node: combine(layer, group, None),
+                });
+                i += 1;
+            }
+        }
+        groups = next_groups;
+    }
+    assert_eq!(groups.len(), 1, "leaves are not joined at the root");
+
+    groups.pop().unwrap().node
+}
+
+impl<F: Field> MultiProof<F> {
+    /// Builds the compact multi-proof from the authentication paths of
+    /// leaves given in ascending order of their indices.
+    pub fn from_paths(leaf_indices: Vec<u64>, paths: &[Vec<F>]) -> Self {
+        assert_eq!(leaf_indices.len(), paths.len(), "invalid paths length");
+        let height = paths.first().map_or(0, |path| path.len());
+        assert!(paths.iter().all(|path| path.len() == height), "invalid auth path length");
+        assert!(
+            leaf_indices.iter().tuple_windows().all(|(x, y)| x < y),
+            "leaf indices must be strictly ascending",
+        );
+
+        let merge_levels = leaf_indices
+            .iter()
+            .tuple_windows()
+            .map(|(&x, &y)| merge_level(x, y))
+            .collect_vec();
+        let mut nodes = Vec::new();
+        walk(
+            height,
+            &merge_levels,
+            &vec![(); leaf_indices.len()],
+            |layer, group, right| {
+                if right.is_none() {
+                    nodes.push(paths[group.start][layer]);
+                }
+            },
+        );
+
+        Self { leaf_indices, nodes }
+    }
+
+    /// Returns the indices of leaves.
+    pub fn leaf_indices(&self) -> &[u64] {
+        &self.leaf_indices
+    }
+
+    /// Returns the sibling nodes.
+    pub fn nodes(&self) -> &[F] {
+        &self.nodes
+    }
+
+    /// Returns the layers at which the paths of adjacent leaves join. This is
+    /// the shape of [`MultiPoECircuit`].
+    pub fn merge_levels(&self) -> Vec<usize> {
+        self.leaf_indices
+            .iter()
+            .tuple_windows()
+            .map(|(&x, &y)| merge_level(x, y))
+            .collect()
+    }
+}
+
+/// Computes the Merkle root from the leaves and their compact multi-proof.
+pub fn native_multi_merkle_proof<F, H>(
+    hasher: &H,
+    height: usize,
+    proof: &MultiProof<F>,
+    leaf_nodes: &[F],
+) -> F
+where
+    F: Field,
+    H: FieldHasher<(), F>,
+{
+    assert_eq!(leaf_nodes.len(), proof.leaf_indices.len(), "invalid leaves length");
+
+    let mut nodes = proof.nodes.iter();
+    let root = walk(
+        height,
+        &proof.merge_levels(),
+        leaf_nodes,
+        |layer, group, right| {
+            if let Some(right) = right {
+                return hasher.hash_two(&mut (), &group.node, &right.node);
+            }
+
+            let node = nodes.next().expect("not enough nodes in multi-proof");
+            if (proof.leaf_indices[group.start] >> layer) & 1 == 1 {
+                hasher.hash_two(&mut (), node, &group.node)
+            } else {
+                hasher.hash_two(&mut (), &group.node, node)
+            }
+        },
+    );
+    assert!(nodes.next().is_none(), "too many nodes in multi-proof");
+
+    root
+}
+
+/// Proof of Existance Circuit of several leaves against the same root.
+///
+/// Paths of the leaves are hashed only once above the layer where they join,
+/// so the circuit shape depends on these layers (see
+/// [`MultiProof::merge_levels`]). The layers must be the same during setup
+/// and proving, while the leaf indices themselves remain private.
+pub struct MultiPoECircuit<F: Field, const HEIGHT: usize> {
+    proof: MultiProof<F>,
+}
+
+impl<F: Field, const HEIGHT: usize> MultiPoECircuit<F, HEIGHT> {
+    pub fn new(proof: MultiProof<F>) -> Self {
+        Self { proof }
+    }
+
+    /// Returns the root and the position bits (little-endian leaf index) of
+    /// each leaf.
+    pub fn synthesize<H: FieldHasher<ConstraintSystem<F>, LTVariable<F>>>(
+        self,
+        cs: &mut ConstraintSystem<F>,
+        hasher: &H,
+        leaf_nodes: &[LTVariable<F>],
+    ) -> (LTVariable<F>, Vec<Vec<Boolean>>) {
+        assert_eq!(leaf_nodes.len(), self.proof.leaf_indices.len(), "invalid leaves length");
+
+        let merge_levels = self.proof.merge_levels();
+        let mut nodes = self.proof.nodes.into_iter();
+        let leaf_indices = self.proof.leaf_indices;
+        let mut positions = vec![Vec::with_capacity(HEIGHT); leaf_nodes.len()];
+
+        // Position bits of merged nodes are known from the shape.
+        let zero = cs.constant_boolean(false);
+        let one = if merge_levels.is_empty() {
+            zero
+        } else {
+            cs.constant_boolean(true)
+        };
+
+        let root = walk(
+            HEIGHT,
+            &merge_levels,
+            leaf_nodes,
+            |layer, group, right| {
+                if let Some(right) = right {
+                    positions[group.start..group.end]
+                        .iter_mut()
+                        .for_each(|position| position.push(zero));
+                    positions[right.start..right.end]
+                        .iter_mut()
+                        .for_each(|position| position.push(one));
+
+                    return hasher.hash_two(cs, &group.node, &right.node);
+                }
+
+                let node = nodes.next().expect("not enough nodes in multi-proof");
+                let node = cs.assign_variable(node).into();
+                let bit = (leaf_indices[group.start] >> layer) & 1 == 1;
+                let bit = cs.assign_variable(bit.into());
+                let bit = cs.boolean_gate(bit);
+                positions[group.start..group.end]
+                    .iter_mut()
+                    .for_each(|position| position.push(bit));
+
+                let left = cs.conditional_select(bit, &node, &group.node);
+                let right = cs.conditional_select(bit, &group.node, &node);
+                hasher.hash_two(cs, &left.into(), &right.into())
+            },
+        );
+        assert!(nodes.next().is_none(), "too many nodes in multi-proof");
+
+        (root, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, UniformRand, rand::{Rng, prelude::StdRng}};
+    use plonk_core::constraint_system::test_gate_constraints;
+
+    use crate::hasher::poseidon::*;
+    use super::*;
+
+    const WIDTH: usize = 3;
+    const HEIGHT: usize = 6;
+
+    type NativeHasher = PoseidonRef<(), NativeSpecRef<Fr>, WIDTH>;
+
+    /// Returns all layers of a full tree, from the leaves to the root.
+    fn full_tree(hasher: &NativeHasher, leaves: Vec<Fr>) -> Vec<Vec<Fr>> {
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hasher.hash_two(&mut (), &pair[0], &pair[1]))
+                .collect();
+            layers.push(layer);
+        }
+        layers
+    }
+
+    fn auth_path(layers: &[Vec<Fr>], index: u64) -> Vec<Fr> {
+        layers[..layers.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(layer, nodes)| nodes[((index >> layer) ^ 1) as usize])
+            .collect()
+    }
+
+    fn random_indices(rng: &mut StdRng, size: usize) -> Vec<u64> {
+        let mut indices = Vec::with_capacity(size);
+        while indices.len() < size {
+            let index = rng.gen_range(0..(1u64 << HEIGHT));
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        indices.sort_unstable();
+        indices
+    }
+
+    #[test]
+    fn test_native_multi_proof() {
+        let rng = &mut test_rng();
+        let hasher = NativeHasher::new(PoseidonConstants::preset::<WIDTH>());
+        let leaves = (0..(1 << HEIGHT)).map(|_| Fr::rand(rng)).collect_vec();
+        let layers = full_tree(&hasher, leaves.clone());
+        let root = layers.last().unwrap()[0];
+
+        for size in [1, 2, 5, 16] {
+            let indices = random_indices(rng, size);
+            let paths = indices.iter().map(|&i| auth_path(&layers, i)).collect_vec();
+            let proof = MultiProof::from_paths(indices.clone(), &paths);
+            assert!(proof.nodes().len() <= size * HEIGHT);
+
+            let leaf_nodes = indices.iter().map(|&i| leaves[i as usize]).collect_vec();
+            assert_eq!(
+                native_multi_merkle_proof(&hasher, HEIGHT, &proof, &leaf_nodes),
+                root,
+            );
+        }
+
+        // adjacent leaves only need the siblings of their common parent
+        let paths = vec![auth_path(&layers, 4), auth_path(&layers, 5)];
+        let proof = MultiProof::from_paths(vec![4, 5], &paths);
+        assert_eq!(proof.nodes(), &paths[0][1..]);
+    }
+
+    #[test]
+    fn test_circuit() {
+        let rng = &mut test_rng();
+        let param = PoseidonConstants::preset::<WIDTH>();
+        let hasher = NativeHasher::new(param.clone());
+        let leaves = (0..(1 << HEIGHT)).map(|_| Fr::rand(rng)).collect_vec();
+        let layers = full_tree(&hasher, leaves.clone());
+        let root = layers.last().unwrap()[0];
+
+        let indices = random_indices(rng, 5);
+        let paths = indices.iter().map(|&i| auth_path(&layers, i)).collect_vec();
+        let proof = MultiProof::from_paths(indices.clone(), &paths);
+
+        test_gate_constraints(
+            |cs| {
+                let hasher =
+                    PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(param.clone());
+                let leaf_vars = indices
+                    .iter()
+                    .map(|&i| cs.assign_variable(leaves[i as usize]).into())
+                    .collect_vec();
+                let circuit = MultiPoECircuit::<Fr, HEIGHT>::new(proof.clone());
+                let (root_var, positions) = circuit.synthesize(cs, &hasher, &leaf_vars);
+                // the root is exposed only once
+                cs.set_variable_public(&root_var);
+                assert_eq!(positions.len(), indices.len());
+                assert!(positions.iter().all(|position| position.len() == HEIGHT));
+
+                let mut expects = vec![(root_var, root)];
+                // position bits recompose the leaf indices
+                for (position, index) in positions.into_iter().zip(&indices) {
+                    for (layer, bit) in position.into_iter().enumerate() {
+                        let value = Fr::from((index >> layer) & 1);
+                        expects.push((bit.into(), value));
+                    }
+                }
+                expects
+            },
+            &[root],
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! Storage backends of the nodes of native Merkle trees.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    marker::PhantomData,
+    os::unix::fs::FileExt,
+    path::Path,
+};
+use ark_ff::Field;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[from] SerializationError),
+    #[error("node at layer {layer} and index {index} is out of range")]
+    OutOfRange { layer: usize, index: u64 },
+    #[error("file is not a node store of this tree height and field")]
+    InvalidHeader,
+}
+
+/// Reads the nodes of a snapshot written by [`NodeStore::snapshot`].
+fn read_snapshot<F: Field, R: Read>(mut reader: R) -> Result<Vec<(usize, u64, F)>, StoreError> {
+    let size = u64::deserialize(&mut reader)?;
+    (0..size)
+        .map(|_| {
+            let layer = u64::deserialize(&mut reader)? as usize;
+            let index = u64::deserialize(&mut reader)?;
+            let node = F::deserialize(&mut reader)?;
+            Ok((layer, index, node))
+        })
+        .collect()
+}
+
+/// Storage of the nodes of a binary Merkle tree, addressed by layer (0 for
+/// the leaves) and index within the layer.
+///
+/// Nodes that were never set are absent, the tree then falls back to the
+/// corresponding empty node.
+pub trait NodeStore<F: Field> {
+    /// Returns the node at `layer` and `index`, if any.
+    fn get(&self, layer: usize, index: u64) -> Result<Option<F>, StoreError>;
+
+    /// Sets the node at `layer` and `index`.
+    fn set(&mut self, layer: usize, index: u64, node: F) -> Result<(), StoreError>;
+
+    /// Returns all stored nodes, ordered by layer then index.
+    fn nodes(&self) -> Result<Vec<(usize, u64, F)>, StoreError>;
+
+    /// Removes all stored nodes.
+    fn clear(&mut self) -> Result<(), StoreError>;
+
+    /// Makes sure all nodes are persisted.
+    fn flush(&mut self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    /// Writes all stored nodes into `writer`. The format is shared by all
+    /// backends, so a snapshot of one backend can be restored into another.
+    fn snapshot<W: Write>(&self, mut writer: W) -> Result<(), StoreError> {
+        let nodes = self.nodes()?;
+        (nodes.len() as u64).serialize(&mut writer)?;
+        for (layer, index, node) in nodes {
+            (layer as u64).serialize(&mut writer)?;
+            index.serialize(&mut writer)?;
+            node.serialize(&mut writer)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Replaces all stored nodes by the ones of a snapshot. The snapshot is
+    /// read as a whole first, so the stored nodes are kept if it is invalid.
+    fn restore<R: Read>(&mut self, reader: R) -> Result<(), StoreError> {
+        let nodes = read_snapshot(reader)?;
+        self.clear()?;
+        for (layer, index, node) in nodes {
+            self.set(layer, index, node)?;
+        }
+
+        self.flush()
+    }
+}
+
+/// In-memory node storage.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryNodeStore<F: Field>(HashMap<(usize, u64), F>);
+
+impl<F: Field> MemoryNodeStore<F> {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<F: Field> NodeStore<F> for MemoryNodeStore<F> {
+    fn get(&self, layer: usize, index: u64) -> Result<Option<F>, StoreError> {
+        Ok(self.0.get(&(layer, index)).copied())
+    }
+
+    fn set(&mut self, layer: usize, index: u64, node: F) -> Result<(), StoreError> {
+        self.0.insert((layer, index), node);
+        Ok(())
+    }
+
+    fn nodes(&self) -> Result<Vec<(usize, u64, F)>, StoreError> {
+        let mut nodes = self.0
+            .iter()
+            .map(|(&(layer, index), &node)| (layer, index, node))
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|&(layer, index, _)| (layer, index));
+
+        Ok(nodes)
+    }
+
+    fn clear(&mut self) -> Result<(), StoreError> {
+        self.0.clear();
+        Ok(())
+    }
+}
+
+/// File-backed node storage of a tree with fixed height.
+///
+/// The file starts with a header of the tree height, the node size and the
+/// field. Every node then has a fixed slot, layer after layer, so reading or
+/// writing a node is a single positioned read or write, which doesn't move
+/// a shared cursor and may run concurrently. A slot starts with a presence
+/// flag, followed by the serialized node. Never written slots are holes of
+/// the file and read as absent.
+#[derive(Debug)]
+pub struct FileNodeStore<F: Field> {
+    file: File,
+    height: usize,
+    node_size: usize,
+    header_size: u64,
+    _f: PhantomData<F>,
+}
+
+impl<F: Field> FileNodeStore<F> {
+    const MAGIC: &'static [u8] = b"PLKNODES";
+    /// Number of slots read at once when listing all nodes.
+    const CHUNK_SLOTS: u64 = 1 << 12;
+
+    /// Opens the store at `path`, creating the file if it doesn't exist.
+    /// Reopening an existing file restores all of its nodes, after checking
+    /// that it was created for the same tree height and field.
+    pub fn open<P: AsRef<Path>>(path: P, height: usize) -> Result<Self, StoreError> {
+        assert!(height < 63, "tree height is too large");
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let node_size = F::zero().serialized_size();
+        let header = Self::header(height, node_size)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&header)?;
+        } else {
+            let mut buf = vec![0u8; header.len()];
+            match file.read_exact(&mut buf) {
+                Ok(()) if buf == header => {}
+                Ok(()) => return Err(StoreError::InvalidHeader),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(StoreError::InvalidHeader)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(Self {
+            file,
+            height,
+            node_size,
+            header_size: header.len() as u64,
+            _f: PhantomData,
+        })
+    }
+
+    fn header(height: usize, node_size: usize) -> Result<Vec<u8>, StoreError> {
+        let mut header = Self::MAGIC.to_vec();
+        (height as u64).serialize(&mut header)?;
+        (node_size as u64).serialize(&mut header)?;
+        F::extension_degree().serialize(&mut header)?;
+        F::characteristic().to_vec().serialize(&mut header)?;
+
+        Ok(header)
+    }
+
+    fn slot_size(&self) -> u64 {
+        self.node_size as u64 + 1
+    }
+
+    fn slot_offset(&self, slot: u64) -> u64 {
+        self.header_size + slot * self.slot_size()
+    }
+
+    fn slots(&self) -> u64 {
+        (1 << (self.height + 1)) - 1
+    }
+
+    /// Returns the slot of the node, layer `l` starts after the
+    /// `2^(h + 1) - 2^(h + 1 - l)` nodes of lower layers.
+    fn slot(&self, layer: usize, index: u64) -> Result<u64, StoreError> {
+        if layer > self.height || index >= 1 << (self.height - layer) {
+            return Err(StoreError::OutOfRange { layer, index });
+        }
+
+        Ok((1 << (self.height + 1)) - (1 << (self.height + 1 - layer)) + index)
+    }
+
+    /// Returns the layer and index of a slot.
+    fn position(&self, slot: u64) -> (usize, u64) {
+        let mut layer = 0;
+        let mut start = 0;
+        loop {
+            let size = 1 << (self.height - layer);
+            if slot < start + size {
+                return (layer, slot - start);
+            }
+            start += size;
+            layer += 1;
+        }
+    }
+
+    fn read_slot(&self, slot: u64) -> Result<Option<F>, StoreError> {
+        let mut buf = vec![0u8; self.slot_size() as usize];
+        match self.file.read_exact_at(&mut buf, self.slot_offset(slot)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        Self::parse_slot(&buf)
+    }
+
+    fn parse_slot(buf: &[u8]) -> Result<Option<F>, StoreError> {
+        if buf[0] == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(F::deserialize(&buf[1..])?))
+    }
+}
+
+impl<F: Field> NodeStore<F> for FileNodeStore<F> {
+    fn get(&self, layer: usize, index: u64) -> Result<Option<F>, StoreError> {
+        self.read_slot(self.slot(layer, index)?)
+    }
+
+    fn set(&mut self, layer: usize, index: u64, node: F) -> Result<(), StoreError> {
+        let slot = self.slot(layer, index)?;
+        let mut buf = Vec::with_capacity(self.slot_size() as usize);
+        buf.push(1u8);
+        node.serialize(&mut buf)?;
+
+        self.file.write_all_at(&buf, self.slot_offset(slot))?;
+
+        Ok(())
+    }
+
+    /// Reads the slots in chunks rather than one by one.
+    fn nodes(&self) -> Result<Vec<(usize, u64, F)>, StoreError> {
+        let len = self.file.metadata()?.len();
+        let slots = self.slots().min(len.saturating_sub(self.header_size) / self.slot_size());
+        let slot_size = self.slot_size() as usize;
+
+        let mut nodes = Vec::new();
+        let mut buf = Vec::new();
+        for start in (0..slots).step_by(Self::CHUNK_SLOTS as usize) {
+            let end = slots.min(start + Self::CHUNK_SLOTS);
+            buf.resize((end - start) as usize * slot_size, 0);
+            self.file.read_exact_at(&mut buf, self.slot_offset(start))?;
+            for (slot, slot_buf) in (start..end).zip(buf.chunks(slot_size)) {
+                if let Some(node) = Self::parse_slot(slot_buf)? {
+                    let (layer, index) = self.position(slot);
+                    nodes.push((layer, index, node));
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    fn clear(&mut self) -> Result<(), StoreError> {
+        self.file.set_len(self.header_size)?;
+        Ok(())
+    }
+
+    /// Checks that all nodes of the snapshot fit the tree before clearing.
+    fn restore<R: Read>(&mut self, reader: R) -> Result<(), StoreError> {
+        let nodes = read_snapshot(reader)?;
+        for &(layer, index, _) in &nodes {
+            self.slot(layer, index)?;
+        }
+
+        self.clear()?;
+        for (layer, index, node) in nodes {
+            self.set(layer, index, node)?;
+        }
+
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), StoreError> {
+        self.file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, UniformRand};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const HEIGHT: usize = 4;
+
+    fn random_nodes() -> Vec<(usize, u64, Fr)> {
+        let rng = &mut test_rng();
+        (0..=HEIGHT)
+            .flat_map(|layer| {
+                (0..(1u64 << (HEIGHT - layer)))
+                    .step_by(3)
+                    .map(move |index| (layer, index))
+            })
+            .map(|(layer, index)| (layer, index, Fr::rand(rng)))
+            .collect()
+    }
+
+    fn test_store<S: NodeStore<Fr>>(store: &mut S) {
+        let nodes = random_nodes();
+        for &(layer, index, node) in &nodes {
+            store.set(layer, index, node).unwrap();
+        }
+        for &(layer, index, node) in &nodes {
+            assert_eq!(store.get(layer, index).unwrap(), Some(node));
+        }
+        assert_eq!(store.get(0, 1).unwrap(), None);
+        assert_eq!(store.nodes().unwrap(), nodes);
+
+        // snapshot and restore
+        let mut snapshot = Vec::new();
+        store.snapshot(&mut snapshot).unwrap();
+        store.clear().unwrap();
+        assert!(store.nodes().unwrap().is_empty());
+        store.restore(snapshot.as_slice()).unwrap();
+        assert_eq!(store.nodes().unwrap(), nodes);
+
+        // an invalid snapshot keeps the stored nodes
+        assert!(store.restore(&snapshot[..snapshot.len() - 1]).is_err());
+        assert_eq!(store.nodes().unwrap(), nodes);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        test_store(&mut MemoryNodeStore::new());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = TempDir::new("node_store").unwrap();
+        let path = dir.path().join("nodes");
+        let mut store = FileNodeStore::open(&path, HEIGHT).unwrap();
+        test_store(&mut store);
+        assert!(matches!(
+            store.set(HEIGHT + 1, 0, Fr::from(1u64)),
+            Err(StoreError::OutOfRange { .. }),
+        ));
+        assert!(matches!(
+            store.get(1, 1 << (HEIGHT - 1)),
+            Err(StoreError::OutOfRange { .. }),
+        ));
+
+        // nodes survive reopening
+        let nodes = store.nodes().unwrap();
+        drop(store);
+        let store = FileNodeStore::<Fr>::open(&path, HEIGHT).unwrap();
+        assert_eq!(store.nodes().unwrap(), nodes);
+
+        // but not with another height or field
+        drop(store);
+        assert!(matches!(
+            FileNodeStore::<Fr>::open(&path, HEIGHT + 1),
+            Err(StoreError::InvalidHeader),
+        ));
+        assert!(matches!(
+            FileNodeStore::<ark_bls12_381::Fr>::open(&path, HEIGHT),
+            Err(StoreError::InvalidHeader),
+        ));
+    }
+
+    #[test]
+    fn test_file_store_many_chunks() {
+        const HEIGHT: usize = 13;
+        let dir = TempDir::new("node_store").unwrap();
+        let mut store = FileNodeStore::open(dir.path().join("nodes"), HEIGHT).unwrap();
+        assert!(store.slots() > 2 * FileNodeStore::<Fr>::CHUNK_SLOTS);
+
+        let rng = &mut test_rng();
+        let nodes: Vec<_> = (0..=HEIGHT)
+            .flat_map(|layer| {
+                (0..(1u64 << (HEIGHT - layer)))
+                    .step_by(1000)
+                    .map(move |index| (layer, index))
+            })
+            .map(|(layer, index)| (layer, index, Fr::rand(rng)))
+            .collect();
+        for &(layer, index, node) in &nodes {
+            store.set(layer, index, node).unwrap();
+        }
+        assert_eq!(store.nodes().unwrap(), nodes);
+
+        // concurrent reads don't share a cursor
+        let store = &store;
+        std::thread::scope(|s| {
+            for chunk in nodes.chunks(4) {
+                s.spawn(move || {
+                    for &(layer, index, node) in chunk {
+                        assert_eq!(store.get(layer, index).unwrap(), Some(node));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_file_store_restore_out_of_range() {
+        let mut memory_store = MemoryNodeStore::new();
+        memory_store.set(HEIGHT + 1, 0, Fr::from(1u64)).unwrap();
+        let mut snapshot = Vec::new();
+        memory_store.snapshot(&mut snapshot).unwrap();
+
+        let dir = TempDir::new("node_store").unwrap();
+        let mut file_store = FileNodeStore::open(dir.path().join("nodes"), HEIGHT).unwrap();
+        file_store.set(0, 0, Fr::from(2u64)).unwrap();
+        assert!(matches!(
+            file_store.restore(snapshot.as_slice()),
+            Err(StoreError::OutOfRange { .. }),
+        ));
+        assert_eq!(file_store.get(0, 0).unwrap(), Some(Fr::from(2u64)));
+    }
+
+    #[test]
+    fn test_snapshot_between_backends() {
+        let mut memory_store = MemoryNodeStore::new();
+        for (layer, index, node) in random_nodes() {
+            memory_store.set(layer, index, node).unwrap();
+        }
+        let mut snapshot = Vec::new();
+        memory_store.snapshot(&mut snapshot).unwrap();
+
+        let dir = TempDir::new("node_store").unwrap();
+        let mut file_store = FileNodeStore::open(dir.path().join("nodes"), HEIGHT).unwrap();
+        file_store.restore(snapshot.as_slice()).unwrap();
+        assert_eq!(file_store.nodes().unwrap(), memory_store.nodes().unwrap());
+    }
+}
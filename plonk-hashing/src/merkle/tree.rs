@@ -0,0 +1,336 @@
+//! Native binary Merkle tree on top of a [`NodeStore`].
+
+use ark_ff::Field;
+use ark_std::cfg_chunks;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::hasher::FieldHasher;
+use super::{binary::PoECircuit, store::{NodeStore, StoreError}};
+
+/// Sparse binary Merkle tree of fixed height whose nodes live in a
+/// [`NodeStore`].
+///
+/// Absent nodes are the roots of empty subtrees, whose leaves are all
+/// [`FieldHasher::empty_hash`]. Since the root is stored as well, a tree over
+/// a persistent store can be reopened without rehashing.
+pub struct MerkleTree<F, H, S>
+where
+    F: Field,
+    H: FieldHasher<(), F>,
+    S: NodeStore<F>,
+{
+    height: usize,
+    hasher: H,
+    store: S,
+    empty_nodes: Vec<F>,
+}
+
+impl<F, H, S> MerkleTree<F, H, S>
+where
+    F: Field,
+    H: FieldHasher<(), F>,
+    S: NodeStore<F>,
+{
+    pub fn new(height: usize, hasher: H, store: S) -> Self {
+        assert!(height < 64, "tree height is too large");
+
+        let mut empty_nodes = Vec::with_capacity(height + 1);
+        empty_nodes.push(hasher.empty_hash());
+        for layer in 0..height {
+            let node = hasher.hash_two(&mut (), &empty_nodes[layer], &empty_nodes[layer]);
+            empty_nodes.push(node);
+        }
+
+        Self { height, hasher, store, empty_nodes }
+    }
+
+    /// Builds the tree whose leaves at indices `0..leaves.len()` are `leaves`
+    /// and the others are empty, replacing all nodes of `store`. Each layer is
+    /// hashed in parallel when the `parallel` feature is enabled.
+    pub fn from_leaves(
+        height: usize,
+        hasher: H,
+        mut store: S,
+        leaves: &[F],
+    ) -> Result<Self, StoreError>
+    where
+        H: Sync,
+    {
+        if leaves.len() as u64 > 1 << height {
+            return Err(StoreError::OutOfRange { layer: 0, index: leaves.len() as u64 - 1 });
+        }
+
+        store.clear()?;
+        let mut tree = Self::new(height, hasher, store);
+        let mut nodes = leaves.to_vec();
+        for layer in 0..=height {
+            for (index, node) in nodes.iter().enumerate() {
+                tree.store.set(layer, index as u64, *node)?;
+            }
+            if layer == height {
+                break;
+            }
+
+            if nodes.len() % 2 == 1 {
+                nodes.push(tree.empty_nodes[layer]);
+            }
+            let hasher = &tree.hasher;
+            nodes = cfg_chunks!(nodes, 2)
+                .map(|pair| hasher.hash_two(&mut (), &pair[0], &pair[1]))
+                .collect();
+        }
+
+        Ok(tree)
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    fn check_index(&self, index: u64) -> Result<(), StoreError> {
+        if index >> self.height != 0 {
+            return Err(StoreError::OutOfRange { layer: 0, index });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the node at `layer` and `index`.
+    pub fn node(&self, layer: usize, index: u64) -> Result<F, StoreError> {
+        Ok(self.store.get(layer, index)?.unwrap_or(self.empty_nodes[layer]))
+    }
+
+    pub fn root(&self) -> Result<F, StoreError> {
+        self.node(self.height, 0)
+    }
+
+    pub fn leaf(&self, index: u64) -> Result<F, StoreError> {
+        self.check_index(index)?;
+        self.node(0, index)
+    }
+
+    /// Sets the leaf at `index` and updates its path, returns the new root.
+    pub fn update(&mut self, index: u64, leaf: F) -> Result<F, StoreError> {
+        self.check_index(index)?;
+
+        let mut cur_hash = leaf;
+        self.store.set(0, index, cur_hash)?;
+        for layer in 0..self.height {
+            let index = index >> layer;
+            let sibling = self.node(layer, index ^ 1)?;
+            cur_hash = if index & 1 == 1 {
+                self.hasher.hash_two(&mut (), &sibling, &cur_hash)
+            } else {
+                self.hasher.hash_two(&mut (), &cur_hash, &sibling)
+            };
+            self.store.set(layer + 1, index >> 1, cur_hash)?;
+        }
+
+        Ok(cur_hash)
+    }
+
+    /// Makes sure all updates are persisted by the store.
+    pub fn flush(&mut self) -> Result<(), StoreError> {
+        self.store.flush()
+    }
+
+    /// Returns the siblings of the leaf at `index`, from the leaf layer up.
+    pub fn auth_path(&self, index: u64) -> Result<Vec<F>, StoreError> {
+        self.check_index(index)?;
+
+        (0..self.height)
+            .map(|layer| self.node(layer, (index >> layer) ^ 1))
+            .collect()
+    }
+
+    /// Returns the existance proof circuit of the leaf at `index`.
+    pub fn poe_circuit<const HEIGHT: usize>(
+        &self,
+        index: u64,
+    ) -> Result<PoECircuit<F, HEIGHT>, StoreError> {
+        assert_eq!(HEIGHT, self.height, "invalid circuit height");
+
+        Ok(PoECircuit::new(index, self.auth_path(index)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_std::{test_rng, UniformRand, rand::Rng};
+    use plonk_core::constraint_system::{
+        AllocVar,
+        ConstraintSystem,
+        Gadget,
+        LTVariable,
+        test_gate_constraints,
+    };
+    use tempdir::TempDir;
+
+    use crate::{
+        hasher::poseidon::*,
+        merkle::{
+            binary::{native_merkle_proof, PoEGadget, PoEVar},
+            store::{MemoryNodeStore, FileNodeStore},
+        },
+    };
+    use super::*;
+
+    const WIDTH: usize = 3;
+    const HEIGHT: usize = 10;
+
+    type NativeHasher = PoseidonRef<(), NativeSpecRef<Fr>, WIDTH>;
+
+    fn native_hasher() -> NativeHasher {
+        PoseidonRef::new(PoseidonConstants::preset::<WIDTH>())
+    }
+
+    fn random_leaves() -> Vec<(u64, Fr)> {
+        let rng = &mut test_rng();
+        (0..16).map(|_| (rng.gen_range(0..(1 << HEIGHT)), Fr::rand(rng))).collect()
+    }
+
+    fn check_auth_path<S: NodeStore<Fr>>(tree: &MerkleTree<Fr, NativeHasher, S>, index: u64) {
+        let hasher = native_hasher();
+        let path = tree.auth_path(index).unwrap();
+        let mut paths = native_merkle_proof(
+            &hasher,
+            path
+                .into_iter()
+                .enumerate()
+                .map(|(layer, node)| ((index >> layer) & 1 == 1, node)),
+            tree.leaf(index).unwrap(),
+        );
+        assert_eq!(paths.pop().unwrap(), tree.root().unwrap());
+    }
+
+    #[test]
+    fn test_memory_tree() {
+        let mut tree = MerkleTree::new(HEIGHT, native_hasher(), MemoryNodeStore::new());
+        let empty_root = tree.root().unwrap();
+        check_auth_path(&tree, 0);
+
+        for (index, leaf) in random_leaves() {
+            let root = tree.update(index, leaf).unwrap();
+            assert_eq!(tree.root().unwrap(), root);
+            assert_eq!(tree.leaf(index).unwrap(), leaf);
+        }
+        assert_ne!(tree.root().unwrap(), empty_root);
+        for (index, _) in random_leaves() {
+            check_auth_path(&tree, index);
+        }
+        check_auth_path(&tree, 1);
+
+        assert!(tree.update(1 << HEIGHT, Fr::from(1u64)).is_err());
+    }
+
+    #[test]
+    fn test_file_tree_survives_restart() {
+        let dir = TempDir::new("merkle_tree").unwrap();
+        let path = dir.path().join("nodes");
+
+        let mut tree = MerkleTree::new(
+            HEIGHT,
+            native_hasher(),
+            FileNodeStore::open(&path, HEIGHT).unwrap(),
+        );
+        let mut memory_tree = MerkleTree::new(HEIGHT, native_hasher(), MemoryNodeStore::new());
+        for (index, leaf) in random_leaves() {
+            tree.update(index, leaf).unwrap();
+            memory_tree.update(index, leaf).unwrap();
+        }
+        tree.flush().unwrap();
+        let root = tree.root().unwrap();
+        assert_eq!(root, memory_tree.root().unwrap());
+        drop(tree);
+
+        // reopen without rehashing
+        let tree = MerkleTree::new(
+            HEIGHT,
+            native_hasher(),
+            FileNodeStore::open(&path, HEIGHT).unwrap(),
+        );
+        assert_eq!(tree.root().unwrap(), root);
+        for (index, _) in random_leaves() {
+            assert_eq!(tree.auth_path(index).unwrap(), memory_tree.auth_path(index).unwrap());
+            check_auth_path(&tree, index);
+        }
+
+        // restore the file tree into memory
+        let mut snapshot = Vec::new();
+        tree.store().snapshot(&mut snapshot).unwrap();
+        let mut store = MemoryNodeStore::new();
+        store.restore(snapshot.as_slice()).unwrap();
+        let tree = MerkleTree::new(HEIGHT, native_hasher(), store);
+        assert_eq!(tree.root().unwrap(), root);
+    }
+
+    #[test]
+    fn test_from_leaves() {
+        let rng = &mut test_rng();
+        let leaves = (0..100).map(|_| Fr::rand(rng)).collect::<Vec<_>>();
+
+        let mut tree = MerkleTree::new(HEIGHT, native_hasher(), MemoryNodeStore::new());
+        for (index, leaf) in leaves.iter().enumerate() {
+            tree.update(index as u64, *leaf).unwrap();
+        }
+        let built_tree = MerkleTree::from_leaves(
+            HEIGHT,
+            native_hasher(),
+            MemoryNodeStore::new(),
+            &leaves,
+        )
+        .unwrap();
+        assert_eq!(built_tree.root().unwrap(), tree.root().unwrap());
+        assert_eq!(built_tree.store().nodes().unwrap(), tree.store().nodes().unwrap());
+        check_auth_path(&built_tree, 57);
+        check_auth_path(&built_tree, 800);
+
+        assert!(MerkleTree::from_leaves(
+            2,
+            native_hasher(),
+            MemoryNodeStore::new(),
+            &leaves,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_poe_circuit() {
+        let mut tree = MerkleTree::new(HEIGHT, native_hasher(), MemoryNodeStore::new());
+        for (index, leaf) in random_leaves() {
+            tree.update(index, leaf).unwrap();
+        }
+        let (index, _) = random_leaves()[3];
+        let leaf = tree.leaf(index).unwrap();
+        let root = tree.root().unwrap();
+
+        test_gate_constraints(
+            |cs| {
+                let hasher = PoseidonRef::<ConstraintSystem<Fr>, PlonkSpecRef, WIDTH>::new(
+                    PoseidonConstants::preset::<WIDTH>(),
+                );
+                let circuit = tree.poe_circuit::<HEIGHT>(index).unwrap();
+                let leaf_var = LTVariable::new_witness(cs, &leaf).unwrap();
+                let path_var = PoEVar::new_witness(cs, &circuit).unwrap();
+                let root_var = PoEGadget::new(&hasher).synthesize(cs, &(leaf_var, path_var));
+
+                [(root_var, root)]
+            },
+            &[],
+        );
+    }
+}